@@ -1,3 +1,59 @@
 use gpui::actions;
 
-actions!(sublime_rust, [Quit, Save, SaveAs, SaveAll, FindAction, FindInFilesAction]);
+actions!(
+    sublime_rust,
+    [
+        Quit,
+        CloseFile,
+        NewFile,
+        Save,
+        SaveAs,
+        SaveAll,
+        ReloadFromDisk,
+        ReopenWithEncoding,
+        SaveWithEncoding,
+        OpenFile,
+        OpenFolder,
+        OpenProject,
+        RecentProjects,
+        SaveProjectAs,
+        AddFolderToProject,
+        RemoveFolderFromProject,
+        FindAction,
+        FindInFilesAction,
+        FindNext,
+        FindPrevious,
+        Undo,
+        Redo,
+        Copy,
+        Cut,
+        Paste,
+        PasteFromHistory,
+        SelectAll,
+        SingleSelection,
+        AddCursorAbove,
+        AddCursorBelow,
+        SelectNextOccurrence,
+        ExpandSelectionToLine,
+        SelectSyntax,
+        GotoAnything,
+        GotoSymbol,
+        GotoLine,
+        CommandPalette,
+        EditSettings,
+        EditSyntaxSettings,
+        EditKeyBindings,
+        SelectColorScheme,
+        ToggleIgnoredFiles,
+        RevealInSideBar,
+        ExplorerNewFile,
+        ExplorerNewFolder,
+        ExplorerRename,
+        ExplorerDelete,
+        ExplorerDuplicate,
+        ExplorerCopyPath,
+        ExplorerReveal,
+        LineEndingsUnix,
+        LineEndingsWindows,
+    ]
+);
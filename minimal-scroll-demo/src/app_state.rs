@@ -2,13 +2,33 @@ use gpui::*;
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 
 use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
 
 use ignore::WalkBuilder;
+use crate::atomic_write::{self, SaveOptions};
+use crate::buffer::Buffer;
+use crate::clipboard::ClipboardHistory;
+use crate::close::PendingClose;
+use crate::editor::TabView;
+use crate::encoding::{self, Decoded, TextEncoding};
+use crate::goto::{self, GotoLineOrigin};
+use crate::keymap::Keymap;
+use crate::explorer::TypeAhead;
+use crate::line_ending::LineEnding;
 use crate::menu::OpenMenu;
+use crate::notifications::{describe_io_error, Notifications};
+use crate::picker::Picker;
+use crate::project::{Project, RecentProjectsFile};
+use crate::project_tree::ProjectTree;
+use crate::selection::Selection;
+use crate::session::Session;
+use crate::settings::{Settings, SettingsLayers};
+use crate::tab::TabId;
+use crate::watcher::FileWatcher;
 
 pub struct ScrollDemo {
     pub left_handle: ScrollHandle,
@@ -18,28 +38,68 @@ pub struct ScrollDemo {
     pub fif_focus_find: FocusHandle,
     pub fif_focus_where: FocusHandle,
     pub fif_focus_replace: FocusHandle,
-    pub current_dir: PathBuf,
+    pub project: Project,
+    pub recent_projects: Vec<PathBuf>,
+    /// Files shown lately, most recent first, for ranking in Goto Anything.
+    pub recent_files: Vec<PathBuf>,
     pub expanded_dirs: HashSet<PathBuf>,
-    pub open_tabs: Vec<PathBuf>,
+    pub project_tree: ProjectTree,
+    /// The explorer entry the context menu and file operations act on.
+    pub explorer_selected: Option<PathBuf>,
+    /// Where the explorer's context menu is open, in window coordinates.
+    pub explorer_menu: Option<Point<Pixels>>,
+    pub explorer_focus_handle: FocusHandle,
+    pub explorer_type_ahead: TypeAhead,
+    /// Set while Reveal Active File waits for folders to be read before it
+    /// can scroll to the file.
+    pub explorer_reveal_pending: bool,
+    pub open_tabs: Vec<TabId>,
     pub active_tab_index: Option<usize>,
-    pub tab_contents: HashMap<PathBuf, Vec<String>>,
-    pub dirty_tabs: HashSet<PathBuf>,
+    pub tab_contents: HashMap<TabId, Buffer>,
+    pub dirty_tabs: HashSet<TabId>,
+    /// Tabs whose files were deleted on disk, and modified tabs whose files
+    /// were moved to the trash. They stay modified, even once undone back to
+    /// the old contents, until saved, closed or the file comes back.
+    pub deleted_tabs: HashSet<TabId>,
+    pub next_untitled: usize,
     pub open_menu: OpenMenu,
     pub sidebar_width: f32,
     pub is_dragging_sidebar: bool,
-    pub cursor_row: usize,
-    pub cursor_col: usize,
+    /// The active tab's selections; other tabs keep theirs, with their scroll
+    /// position, in `tab_views` until they are shown again.
+    pub selections: Vec<Selection>,
+    pub tab_views: HashMap<TabId, TabView>,
+    pub clipboard_history: ClipboardHistory,
+    pub notifications: Notifications,
+    pub save_options: SaveOptions,
+    pub file_watcher: Option<FileWatcher>,
+    pub settings_layers: SettingsLayers,
+    /// The settings outside of any one file; see `active_settings` for the
+    /// active tab's.
+    pub settings: Settings,
+    pub keymap: Keymap,
+    /// Modified tabs whose files changed on disk, waiting for a Merge /
+    /// Reload / Keep decision.
+    pub pending_reloads: Vec<TabId>,
+
+    // Picker overlay state
+    pub picker: Option<Picker>,
+    pub picker_focus_handle: FocusHandle,
+    pub goto_line_origin: Option<GotoLineOrigin>,
 
     // Syntect state
     pub syntax_set: SyntaxSet,
     pub theme_set: ThemeSet,
+    /// The scheme the Color Scheme picker is on, shown until it closes.
+    pub color_scheme_preview: Option<String>,
     pub current_syntax_name: String,
+    pub syntax_overrides: HashMap<TabId, String>,
 
     // Char widths
     pub char_widths: HashMap<char, f32>,
 
     // Confirmation dialog state
-    pub pending_close_path: Option<PathBuf>,
+    pub pending_close: Option<PendingClose>,
 
     // Find state
     pub find_active: bool,
@@ -55,6 +115,41 @@ pub struct ScrollDemo {
     pub fif_use_gitignore: bool,
 }
 
+/// Replaces `query` in one file for Replace in Files, returning how many
+/// matches there were. A file open in `buffer` is edited there, as an undoable
+/// change left to be saved, rather than on disk behind the buffer's back,
+/// which would only have the watcher offer to reload or merge it; its hex view
+/// is left alone. Closed files are decoded and written back in their own
+/// encoding; files that aren't text are skipped.
+fn replace_in_file(path: &Path, buffer: Option<&mut Buffer>, query: &str, replacement: &str, options: &SaveOptions) -> io::Result<usize> {
+    if let Some(buffer) = buffer {
+        return Ok(if buffer.read_only { 0 } else { buffer.replace_all(query, replacement) });
+    }
+    let Decoded::Text(content, text_encoding) = encoding::decode(&fs::read(path)?) else { return Ok(0) };
+    let count = content.matches(query).count();
+    if count > 0 {
+        let bytes = text_encoding
+            .encode(&content.replace(query, replacement))
+            .map_err(|reason| io::Error::new(io::ErrorKind::InvalidData, reason))?;
+        atomic_write::write(path, &bytes, options)?;
+    }
+    Ok(count)
+}
+
+/// The text Find in Files searches in one file, read the way Replace in Files
+/// edits it: an open buffer's text, unsaved changes included, or the closed
+/// file decoded in its own encoding. `None` for hex views and files that
+/// aren't text.
+fn searchable_text(path: &Path, buffer: Option<&Buffer>) -> Option<String> {
+    if let Some(buffer) = buffer {
+        return (!buffer.read_only).then(|| buffer.text());
+    }
+    match encoding::decode(&fs::read(path).ok()?) {
+        Decoded::Text(text, _) => Some(text),
+        Decoded::Binary => None,
+    }
+}
+
 impl ScrollDemo {
     pub fn new(cx: &mut Context<Self>) -> Self {
         let current_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
@@ -66,7 +161,8 @@ impl ScrollDemo {
             HashMap::new()
         };
 
-        Self {
+        let settings = Settings::default();
+        let mut this = Self {
             left_handle: ScrollHandle::new(),
             right_handle: ScrollHandle::new(),
             focus_handle: cx.focus_handle(),
@@ -75,22 +171,44 @@ impl ScrollDemo {
             fif_focus_where: cx.focus_handle(),
             fif_focus_replace: cx.focus_handle(),
             fif_where: current_dir.to_string_lossy().to_string(),
-            current_dir,
+            project: Project::from_folder(current_dir),
+            recent_projects: RecentProjectsFile::load(),
+            recent_files: Vec::new(),
             expanded_dirs: HashSet::new(),
+            project_tree: ProjectTree::default(),
+            explorer_selected: None,
+            explorer_menu: None,
+            explorer_focus_handle: cx.focus_handle(),
+            explorer_type_ahead: TypeAhead::default(),
+            explorer_reveal_pending: false,
             open_tabs: Vec::new(),
             active_tab_index: None,
             tab_contents: HashMap::new(),
             dirty_tabs: HashSet::new(),
+            deleted_tabs: HashSet::new(),
+            next_untitled: 1,
             open_menu: OpenMenu::None,
-            sidebar_width: 250.0,
+            sidebar_width: settings.sidebar_width,
             is_dragging_sidebar: false,
-            cursor_row: 0,
-            cursor_col: 0,
+            selections: vec![Selection::cursor(0)],
+            tab_views: HashMap::new(),
+            clipboard_history: ClipboardHistory::default(),
+            notifications: Notifications::default(),
+            save_options: settings.save_options(),
+            file_watcher: None,
+            settings_layers: SettingsLayers::default(),
+            keymap: Keymap::new(cx.key_bindings().borrow().bindings().cloned().collect(), Vec::new()),
+            pending_reloads: Vec::new(),
+            picker: None,
+            picker_focus_handle: cx.focus_handle(),
+            goto_line_origin: None,
             syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
+            theme_set: ThemeSet::new(),
+            color_scheme_preview: None,
             current_syntax_name: "Plain Text".to_string(),
+            syntax_overrides: HashMap::new(),
             char_widths,
-            pending_close_path: None,
+            pending_close: None,
             find_active: false,
             find_query: String::new(),
             find_matches: Vec::new(),
@@ -98,75 +216,337 @@ impl ScrollDemo {
             fif_active: false,
             fif_query: String::new(),
             fif_replace: String::new(),
-            fif_use_gitignore: true,
-        }
+            fif_use_gitignore: settings.find_in_files_use_gitignore,
+            settings,
+        };
+        this.load_keymap(cx);
+        this.load_color_schemes(cx);
+        this.load_syntaxes(cx);
+        this.load_user_settings(cx);
+        let session = Session::path().and_then(|path| Session::load(&path)).unwrap_or_default();
+        this.restore_session(session, cx);
+        this.load_project_settings(cx);
+        this.start_file_watcher(cx);
+        this.load_expanded_dirs(cx);
+        this
+    }
+
+    /// The syntax a tab is highlighted with: the one picked by hand if any,
+    /// else whatever its file name suggests, else plain text.
+    pub fn syntax_for(&self, tab: &TabId) -> &SyntaxReference {
+        self.syntax_overrides
+            .get(tab)
+            .and_then(|name| self.syntax_set.find_syntax_by_name(name))
+            .or_else(|| tab.path().and_then(|path| self.syntax_set.find_syntax_for_file(path).ok().flatten()))
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text())
     }
 
     pub fn update_syntax(&mut self) {
-        if let Some(idx) = self.active_tab_index {
-            if let Some(path) = self.open_tabs.get(idx) {
-                if let Some(syntax) = self.syntax_set.find_syntax_for_file(path).unwrap_or(None) {
-                    self.current_syntax_name = syntax.name.clone();
-                    return;
-                }
+        self.current_syntax_name = match self.active_tab() {
+            Some(tab) => self.syntax_for(&tab).name.clone(),
+            None => "Plain Text".to_string(),
+        };
+    }
+
+    /// Sets the syntax of the active tab by hand.
+    pub fn set_syntax(&mut self, name: String, cx: &mut Context<Self>) {
+        let Some(tab) = self.active_tab() else { return };
+        self.syntax_overrides.insert(tab, name);
+        self.update_syntax();
+        cx.notify();
+    }
+
+    /// Writes a file tab to its path and returns whether that worked. Tabs
+    /// without a path ask for one instead, and return false until they have it.
+    pub fn save_tab(&mut self, tab: TabId, cx: &mut Context<Self>) -> bool {
+        let Some(path) = tab.path().cloned() else {
+            self.prompt_save_as(tab, false, cx);
+            return false;
+        };
+        match self.write_buffer(&tab, &path) {
+            Ok(()) => {
+                self.dirty_tabs.remove(&tab);
+                self.notify_info(format!("Saved {}", tab.label()), cx);
+                true
+            }
+            Err(message) => {
+                self.notify_error(format!("Couldn't save {message}"), cx);
+                false
             }
         }
-        self.current_syntax_name = "Plain Text".to_string();
     }
 
-    pub fn save_path(&mut self, path: PathBuf, cx: &mut Context<Self>) {
-        if let Some(lines) = self.tab_contents.get(&path) {
-            let content = lines.join("
-");
-            if fs::write(&path, content).is_ok() {
-                self.dirty_tabs.remove(&path);
-                eprintln!("Saved: {:?}", path);
-                cx.notify();
-            }
+    /// Encodes a tab's buffer in its encoding and writes it to `path`,
+    /// marking it saved. Every save goes through here, and so through the
+    /// atomic writer. Errors are worded for a notification.
+    fn write_buffer(&mut self, tab: &TabId, path: &Path) -> Result<(), String> {
+        let options = &self.save_options;
+        let Some(buffer) = self.tab_contents.get_mut(tab) else {
+            return Err(format!("{}: the tab is gone", path.display()));
+        };
+        if buffer.read_only {
+            return Err(format!("{}: binary files are shown read-only", path.display()));
         }
+        let bytes = buffer.encoding.encode(&buffer.file_text()).map_err(|reason| format!("{}: {reason}", path.display()))?;
+        atomic_write::write(path, &bytes, options).map_err(|err| describe_io_error(&err, path))?;
+        buffer.mark_saved();
+        self.deleted_tabs.remove(tab);
+        Ok(())
     }
 
     pub fn save_active(&mut self, cx: &mut Context<Self>) {
-        if let Some(idx) = self.active_tab_index {
-            if let Some(path) = self.open_tabs.get(idx).cloned() {
-                self.save_path(path, cx);
-            }
+        if let Some(tab) = self.active_tab() {
+            self.save_tab(tab, cx);
         }
     }
 
+    /// Asks for a destination and saves the active tab there.
     pub fn save_as(&mut self, cx: &mut Context<Self>) {
-        if let Some(idx) = self.active_tab_index {
-            if let Some(path) = self.open_tabs.get(idx).cloned() {
-                if let Some(lines) = self.tab_contents.get(&path) {
-                    let mut new_path = path.clone();
-                    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("file");
-                    new_path.set_file_name(format!("{}_copy.txt", stem));
-                    let content = lines.join("
-");
-                    if fs::write(&new_path, content).is_ok() {
-                        eprintln!("Saved As: {:?}", new_path);
-                        cx.notify();
-                    }
+        if let Some(tab) = self.active_tab() {
+            self.prompt_save_as(tab, false, cx);
+        }
+    }
+
+    /// Asks where to save `tab`, then saves it there and, with `close_after`,
+    /// closes it. Cancelling the prompt leaves the tab as it was.
+    pub fn prompt_save_as(&mut self, tab: TabId, close_after: bool, cx: &mut Context<Self>) {
+        let (directory, file_name) = match tab.path() {
+            Some(path) => (
+                path.parent().map(PathBuf::from).unwrap_or_default(),
+                path.file_name().map(|name| name.to_string_lossy().to_string()),
+            ),
+            None => (self.project.roots().next().cloned().unwrap_or_default(), None),
+        };
+        let receiver = cx.prompt_for_new_path(&directory, file_name.as_deref());
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(new_path))) = receiver.await else { return };
+            this.update(cx, |this, cx| {
+                if this.save_tab_as(tab, new_path.clone(), cx) && close_after {
+                    this.close_tab(TabId::File(new_path), cx);
+                    this.advance_close(cx);
                 }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Writes `tab` to `new_path` and points the tab there. Returns whether
+    /// the write succeeded.
+    pub fn save_tab_as(&mut self, tab: TabId, new_path: PathBuf, cx: &mut Context<Self>) -> bool {
+        let new_tab = TabId::File(new_path.clone());
+        if new_tab != tab && self.dirty_tabs.contains(&new_tab) {
+            self.notify_error(
+                format!("Couldn't save {}: it is open in another tab with unsaved changes", new_path.display()),
+                cx,
+            );
+            return false;
+        }
+        if let Err(message) = self.write_buffer(&tab, &new_path) {
+            self.notify_error(format!("Couldn't save {message}"), cx);
+            return false;
+        }
+        self.notify_info(format!("Saved {}", new_tab.label()), cx);
+        self.retarget_tab(&tab, new_tab);
+        true
+    }
+
+    /// Moves a tab, its buffer and its dirty state from `old` to `new`. A
+    /// different tab already showing `new` is dropped with everything kept
+    /// for it, since it now holds stale contents. A syntax picked by hand only
+    /// carries over when the new file name doesn't suggest one.
+    pub fn retarget_tab(&mut self, old: &TabId, new: TabId) {
+        if old == &new {
+            self.sync_dirty(&new);
+            return;
+        }
+        let active = self.active_tab();
+        self.forget_tab(&new);
+        if let Some(idx) = self.open_tabs.iter().position(|t| t == old) {
+            self.open_tabs[idx] = new.clone();
+        }
+        if let Some(buffer) = self.tab_contents.remove(old) {
+            self.tab_contents.insert(new.clone(), buffer);
+        }
+        if let Some(view) = self.tab_views.remove(old) {
+            self.tab_views.insert(new.clone(), view);
+        }
+        if let Some(name) = self.syntax_overrides.remove(old) {
+            let detected = new.path().and_then(|path| self.syntax_set.find_syntax_for_file(path).ok().flatten());
+            if detected.is_none() {
+                self.syntax_overrides.insert(new.clone(), name);
             }
         }
+        self.dirty_tabs.remove(old);
+        if self.deleted_tabs.remove(old) {
+            self.deleted_tabs.insert(new.clone());
+        }
+        self.pending_reloads.retain(|t| t != old);
+        self.sync_dirty(&new);
+        let active = if active.as_ref() == Some(old) { Some(new) } else { active };
+        self.active_tab_index = active.and_then(|tab| self.open_tabs.iter().position(|t| t == &tab));
+        self.update_syntax();
+        self.update_watches();
+    }
+
+    /// Shows `tab`, adding it with `buffer` unless it is already open.
+    pub fn show_tab(&mut self, tab: TabId, buffer: impl FnOnce() -> Option<Buffer>, cx: &mut Context<Self>) {
+        if let Some(path) = tab.path() {
+            goto::remember_file(&mut self.recent_files, path);
+        }
+        if let Some(pos) = self.open_tabs.iter().position(|t| t == &tab) {
+            self.activate_tab(Some(pos));
+        } else if let Some(buffer) = buffer() {
+            self.tab_contents.insert(tab.clone(), buffer);
+            self.open_tabs.push(tab);
+            self.activate_tab(Some(self.open_tabs.len() - 1));
+            self.update_watches();
+        }
+        cx.notify();
+    }
+
+    /// Shows `path` in a tab, reading it from disk unless it is already open.
+    /// Files that can't be read are reported and no tab is opened.
+    pub fn open_file(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        let tab = TabId::File(path.clone());
+        let buffer = if self.open_tabs.contains(&tab) {
+            None
+        } else {
+            match fs::read(&path) {
+                Ok(bytes) => Some(Buffer::from_bytes(&bytes)),
+                Err(err) => {
+                    self.notify_error(format!("Couldn't open {}", describe_io_error(&err, &path)), cx);
+                    return;
+                }
+            }
+        };
+        self.show_tab(tab, || buffer, cx);
     }
 
+    /// Opens an empty scratch buffer that has no file until it is saved.
+    pub fn new_file(&mut self, cx: &mut Context<Self>) {
+        let tab = TabId::Untitled(self.next_untitled);
+        self.next_untitled += 1;
+        self.show_tab(tab, || Some(Buffer::from_text("")), cx);
+    }
+
+    /// Reads the active file again as `encoding`. Refused while the tab has
+    /// unsaved changes, since reopening would throw them away.
+    pub fn reopen_with_encoding(&mut self, encoding: TextEncoding, cx: &mut Context<Self>) {
+        let Some(tab) = self.active_tab() else { return };
+        let Some(path) = tab.path().cloned() else { return };
+        if self.dirty_tabs.contains(&tab) {
+            self.notify_error(format!("Save or undo the changes to {} before reopening it", tab.label()), cx);
+            return;
+        }
+        match fs::read(&path) {
+            Ok(bytes) => {
+                let mut buffer = Buffer::from_text(&encoding.decode(&bytes));
+                buffer.encoding = encoding;
+                self.tab_contents.insert(tab, buffer);
+                self.set_cursor(0);
+                cx.notify();
+            }
+            Err(err) => self.notify_error(format!("Couldn't reopen {}", describe_io_error(&err, &path)), cx),
+        }
+    }
+
+    /// Converts the active tab's line endings; the tab is modified until saved.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding, cx: &mut Context<Self>) {
+        let Some(tab) = self.active_tab() else { return };
+        let Some(buffer) = self.tab_contents.get_mut(&tab) else { return };
+        if buffer.read_only {
+            return;
+        }
+        buffer.line_ending = line_ending;
+        self.sync_dirty(&tab);
+        cx.notify();
+    }
+
+    /// Switches the active tab to `encoding` and saves it. If the file can't
+    /// be written, say because the text doesn't fit the encoding, the tab
+    /// keeps the encoding it had; an untitled one keeps the new encoding for
+    /// the Save As prompt.
+    pub fn save_with_encoding(&mut self, encoding: TextEncoding, cx: &mut Context<Self>) {
+        let Some(tab) = self.active_tab() else { return };
+        let Some(buffer) = self.tab_contents.get_mut(&tab) else { return };
+        let previous = std::mem::replace(&mut buffer.encoding, encoding);
+        if !self.save_tab(tab.clone(), cx) && tab.path().is_some() {
+            if let Some(buffer) = self.tab_contents.get_mut(&tab) {
+                buffer.encoding = previous;
+            }
+        }
+    }
+
+    pub fn prompt_open_files(&mut self, cx: &mut Context<Self>) {
+        let receiver = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: true,
+            prompt: None,
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = receiver.await else { return };
+            this.update(cx, |this, cx| {
+                for path in paths {
+                    this.open_file(path, cx);
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    pub fn prompt_open_folder(&mut self, cx: &mut Context<Self>) {
+        let receiver = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: false,
+            prompt: None,
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = receiver.await else { return };
+            let Some(folder) = paths.into_iter().next() else { return };
+            this.update(cx, |this, cx| this.open_folder(folder, cx)).ok();
+        })
+        .detach();
+    }
+
+    /// Replaces the project with one holding just `folder`.
+    pub fn open_folder(&mut self, folder: PathBuf, cx: &mut Context<Self>) {
+        self.set_project(Project::from_folder(folder), cx);
+    }
+
+    /// Saves every modified file tab. Tabs without a file are left for Save As.
     pub fn save_all(&mut self, cx: &mut Context<Self>) {
-        let paths: Vec<PathBuf> = self.dirty_tabs.iter().cloned().collect();
-        for path in paths {
-            if let Some(lines) = self.tab_contents.get(&path) {
-                let content = lines.join("
-");
-                if fs::write(&path, content).is_ok() {
-                    self.dirty_tabs.remove(&path);
-                    eprintln!("Saved (All): {:?}", path);
+        let tabs: Vec<TabId> = self.dirty_tabs.iter().cloned().collect();
+        let mut saved = 0;
+        for tab in tabs {
+            let Some(path) = tab.path().cloned() else { continue };
+            match self.write_buffer(&tab, &path) {
+                Ok(()) => {
+                    self.dirty_tabs.remove(&tab);
+                    saved += 1;
                 }
+                Err(message) => self.notify_error(format!("Couldn't save {message}"), cx),
             }
         }
+        if saved > 0 {
+            self.notify_info(format!("Saved {} file{}", saved, if saved == 1 { "" } else { "s" }), cx);
+        }
         cx.notify();
     }
 
+    /// Keeps `dirty_tabs` in line with the buffer's undo history, so undoing
+    /// back to the saved state clears the marker, unless the file is gone.
+    pub fn sync_dirty(&mut self, tab: &TabId) {
+        if self.deleted_tabs.contains(tab) || self.tab_contents.get(tab).is_some_and(|buffer| buffer.is_modified()) {
+            self.dirty_tabs.insert(tab.clone());
+        } else {
+            self.dirty_tabs.remove(tab);
+        }
+    }
+
     pub fn perform_search(&mut self) {
         self.find_matches.clear();
         if self.find_query.is_empty() {
@@ -174,17 +554,8 @@ impl ScrollDemo {
             return;
         }
 
-        if let Some(idx) = self.active_tab_index {
-            let path = &self.open_tabs[idx];
-            if let Some(lines) = self.tab_contents.get(path) {
-                for (row, line) in lines.iter().enumerate() {
-                    let mut start = 0;
-                    while let Some(pos) = line[start..].find(&self.find_query) {
-                        self.find_matches.push((row, start + pos));
-                        start += pos + self.find_query.len().max(1);
-                    }
-                }
-            }
+        if let Some(buffer) = self.active_buffer() {
+            self.find_matches = buffer.find_all(&self.find_query);
         }
 
         if !self.find_matches.is_empty() {
@@ -195,19 +566,29 @@ impl ScrollDemo {
         }
     }
 
+    /// Finds the query again after an edit moved the text, without moving the
+    /// selection; the active match stays if it still exists.
+    pub fn refresh_find_matches(&mut self) {
+        self.find_matches = match self.active_buffer() {
+            Some(buffer) => buffer.find_all(&self.find_query),
+            None => Vec::new(),
+        };
+        self.active_match_index = self.active_match_index.filter(|&i| i < self.find_matches.len());
+    }
+
     pub fn jump_to_active_match(&mut self) {
         if let Some(idx) = self.active_match_index {
             let (row, col) = self.find_matches[idx];
-            self.cursor_row = row;
-            self.cursor_col = col;
-
-            let line_height = 20.0;
-            let top_padding = 16.0;
-            let target_y = (row as f32 * line_height) + top_padding - 100.0;
-            self.right_handle.set_offset(Point::new(px(0.0), px(-target_y.max(0.0))));
+            if let Some(buffer) = self.active_buffer() {
+                let start = buffer.point_to_offset(row, col);
+                let end = buffer.clamp_offset(start + self.find_query.len());
+                self.selections = vec![Selection::new(start, end)];
+            }
+            self.scroll_to_row(row);
         }
     }
 
+
     pub fn find_next(&mut self, cx: &mut Context<Self>) {
         if !self.find_matches.is_empty() {
             let next = self.active_match_index.map(|i| (i + 1) % self.find_matches.len()).unwrap_or(0);
@@ -241,25 +622,24 @@ impl ScrollDemo {
             ignore::Walk::new(&search_path)
         };
 
-        for entry in walk {
-            if let Ok(entry) = entry {
-                if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
-                    if let Ok(content) = fs::read_to_string(entry.path()) {
-                        file_count += 1;
-                        let lines: Vec<_> = content.lines().collect();
-                        let mut file_matches = Vec::new();
-                        for (i, line) in lines.iter().enumerate() {
-                            if line.contains(&self.fif_query) {
-                                file_matches.push(format!("  {:>4}: {}", i + 1, line));
-                                match_count += 1;
-                            }
-                        }
-                        if !file_matches.is_empty() {
-                            results.push(format!("{}:", entry.path().display()));
-                            results.extend(file_matches);
-                            results.push(String::new());
+        for entry in walk.flatten() {
+            if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
+                let buffer = self.tab_contents.get(&TabId::File(entry.path().to_path_buf()));
+                if let Some(content) = searchable_text(entry.path(), buffer) {
+                    file_count += 1;
+                    let lines: Vec<_> = content.lines().collect();
+                    let mut file_matches = Vec::new();
+                    for (i, line) in lines.iter().enumerate() {
+                        if line.contains(&self.fif_query) {
+                            file_matches.push(format!("  {:>4}: {}", i + 1, line));
+                            match_count += 1;
                         }
                     }
+                    if !file_matches.is_empty() {
+                        results.push(format!("{}:", entry.path().display()));
+                        results.extend(file_matches);
+                        results.push(String::new());
+                    }
                 }
             }
         }
@@ -268,15 +648,15 @@ impl ScrollDemo {
             format!("Searching {} files for term \"{}\"", file_count, self.fif_query),
             String::new(),
         ];
-        final_content.extend(results.into_iter());
+        final_content.extend(results);
         final_content.push(format!("{} matches found in {} files", match_count, file_count));
 
-        let results_path = PathBuf::from("Find Results");
-        self.tab_contents.insert(results_path.clone(), final_content);
-        if !self.open_tabs.contains(&results_path) {
-            self.open_tabs.push(results_path.clone());
+        self.tab_contents.insert(TabId::FindResults, Buffer::from_text(&final_content.join("\n")));
+        if !self.open_tabs.contains(&TabId::FindResults) {
+            self.open_tabs.push(TabId::FindResults);
         }
-        self.active_tab_index = self.open_tabs.iter().position(|p| p == &results_path);
+        self.activate_tab(self.open_tabs.iter().position(|t| t == &TabId::FindResults));
+        self.set_cursor(0);
         self.fif_active = false;
         cx.notify();
     }
@@ -292,15 +672,19 @@ impl ScrollDemo {
             ignore::Walk::new(&search_path)
         };
 
-        for entry in walk {
-            if let Ok(entry) = entry {
-                if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
-                    if let Ok(content) = fs::read_to_string(entry.path()) {
-                        if content.contains(&self.fif_query) {
-                            let new_content = content.replace(&self.fif_query, &self.fif_replace);
-                            let _ = fs::write(entry.path(), new_content);
+        for entry in walk.flatten() {
+            if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
+                let tab = TabId::File(entry.path().to_path_buf());
+                let buffer = self.tab_contents.get_mut(&tab);
+                match replace_in_file(entry.path(), buffer, &self.fif_query, &self.fif_replace, &self.save_options) {
+                    Ok(count) => {
+                        if count > 0 && self.active_tab().as_ref() == Some(&tab) {
+                            self.clamp_selections();
+                            self.refresh_find_matches();
                         }
+                        self.sync_dirty(&tab);
                     }
+                    Err(err) => self.notify_error(format!("Couldn't replace in {}", describe_io_error(&err, entry.path())), cx),
                 }
             }
         }
@@ -308,27 +692,96 @@ impl ScrollDemo {
         cx.notify();
     }
 
-    pub fn close_tab(&mut self, path: PathBuf, cx: &mut Context<Self>) {
-        if let Some(idx) = self.open_tabs.iter().position(|p| p == &path) {
-            self.open_tabs.remove(idx);
-            self.tab_contents.remove(&path);
-            self.dirty_tabs.remove(&path);
-            if let Some(active_idx) = self.active_tab_index {
-                if active_idx >= self.open_tabs.len() {
-                    self.active_tab_index = if self.open_tabs.is_empty() { None } else { Some(self.open_tabs.len() - 1) };
-                }
+    /// Drops `tab` and everything kept for it, leaving the active tab index
+    /// for the caller to fix up.
+    fn forget_tab(&mut self, tab: &TabId) {
+        self.open_tabs.retain(|t| t != tab);
+        self.tab_contents.remove(tab);
+        self.tab_views.remove(tab);
+        self.dirty_tabs.remove(tab);
+        self.deleted_tabs.remove(tab);
+        self.syntax_overrides.remove(tab);
+        self.pending_reloads.retain(|t| t != tab);
+    }
+
+    pub fn close_tab(&mut self, tab: TabId, cx: &mut Context<Self>) {
+        if let Some(idx) = self.open_tabs.iter().position(|t| t == &tab) {
+            let active = self.active_tab();
+            self.forget_tab(&tab);
+            self.update_watches();
+            if active.as_ref() == Some(&tab) {
+                // Nothing to keep for the closed tab, so the switch starts
+                // from no active tab.
+                self.active_tab_index = None;
+                let next = (!self.open_tabs.is_empty()).then(|| idx.min(self.open_tabs.len() - 1));
+                self.activate_tab(next);
+            } else {
+                self.active_tab_index = active.and_then(|active| self.open_tabs.iter().position(|t| t == &active));
             }
-            self.update_syntax();
             cx.notify();
         }
     }
+}
 
-    pub fn request_close_tab(&mut self, path: PathBuf, cx: &mut Context<Self>) {
-        if self.dirty_tabs.contains(&path) {
-            self.pending_close_path = Some(path);
-        } else {
-            self.close_tab(path, cx);
-        }
-        cx.notify();
+#[cfg(test)]
+mod tests {
+    use super::{replace_in_file, searchable_text};
+    use crate::atomic_write::SaveOptions;
+    use crate::buffer::Buffer;
+    use encoding_rs::WINDOWS_1252;
+    use std::fs;
+
+    #[test]
+    fn open_files_are_replaced_in_their_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "foo foo").unwrap();
+        let mut buffer = Buffer::from_text("foo foo");
+        assert_eq!(replace_in_file(&path, Some(&mut buffer), "foo", "bar", &SaveOptions::default()).unwrap(), 2);
+        assert_eq!(buffer.text(), "bar bar");
+        assert!(buffer.is_modified());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "foo foo");
+    }
+
+    #[test]
+    fn closed_files_are_rewritten_and_hex_views_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "foo\nfoo\n").unwrap();
+        assert_eq!(replace_in_file(&path, None, "foo", "bar", &SaveOptions::default()).unwrap(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "bar\nbar\n");
+
+        let mut hex = Buffer::from_text("bar");
+        hex.read_only = true;
+        assert_eq!(replace_in_file(&path, Some(&mut hex), "bar", "baz", &SaveOptions::default()).unwrap(), 0);
+        assert_eq!(hex.text(), "bar");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "bar\nbar\n");
+    }
+
+    #[test]
+    fn find_searches_open_buffers_and_decoded_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, WINDOWS_1252.encode("caf\u{e9} cr\u{e8}me br\u{fb}l\u{e9}e").0).unwrap();
+        assert_eq!(searchable_text(&path, None).as_deref(), Some("caf\u{e9} cr\u{e8}me br\u{fb}l\u{e9}e"));
+        let buffer = Buffer::from_text("unsaved");
+        assert_eq!(searchable_text(&path, Some(&buffer)).as_deref(), Some("unsaved"));
+
+        let mut hex = Buffer::from_text("00 01");
+        hex.read_only = true;
+        assert_eq!(searchable_text(&path, Some(&hex)), None);
+        fs::write(&path, b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00").unwrap();
+        assert_eq!(searchable_text(&path, None), None);
+    }
+
+    #[test]
+    fn closed_files_keep_their_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        let latin1 = "caf\u{e9} foo, d\u{e9}j\u{e0} vu, na\u{ef}ve foo";
+        fs::write(&path, WINDOWS_1252.encode(latin1).0).unwrap();
+        assert_eq!(replace_in_file(&path, None, "foo", "bar", &SaveOptions::default()).unwrap(), 2);
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(WINDOWS_1252.decode(&bytes).0, latin1.replace("foo", "bar"));
     }
 }
@@ -0,0 +1,222 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::hash::fnv1a;
+
+/// Where, if anywhere, the previous contents of a file go before a save
+/// replaces it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum BackupMode {
+    #[default]
+    None,
+    /// `name.ext.bak` next to the file.
+    Sibling,
+    /// A copy under this directory, named after the file plus a hash of its
+    /// full path, so files of the same name don't overwrite each other's.
+    Directory(PathBuf),
+}
+
+impl BackupMode {
+    /// Reads a backup setting: `none`, `sibling`, or a directory to copy into.
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "" | "none" => BackupMode::None,
+            "sibling" => BackupMode::Sibling,
+            dir => BackupMode::Directory(PathBuf::from(dir)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SaveOptions {
+    pub backup: BackupMode,
+}
+
+/// Replaces `path` with `bytes` without ever leaving it half written. The bytes
+/// go to a temporary file in the same directory, which is synced and renamed
+/// over the target, so a crash or a full disk leaves either the old file or
+/// the new one. The target's permissions carry over, and a symlink is written
+/// through rather than replaced.
+pub fn write(path: &Path, bytes: &[u8], options: &SaveOptions) -> io::Result<()> {
+    write_with(path, bytes, options, |file, bytes| file.write_all(bytes))
+}
+
+/// `write` with the step that fills the temporary file passed in, so tests can
+/// make it fail.
+fn write_with(
+    path: &Path,
+    bytes: &[u8],
+    options: &SaveOptions,
+    fill: impl FnOnce(&mut File, &[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let existing = fs::metadata(&target).ok();
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = target.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+
+    let (temp_path, mut file) = create_temp(&dir, &file_name.to_string_lossy())?;
+    let result = (|| {
+        fill(&mut file, bytes)?;
+        if let Some(metadata) = &existing {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        drop(file);
+        if existing.is_some() {
+            backup(&target, &options.backup)?;
+        }
+        fs::rename(&temp_path, &target)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+    sync_dir(&dir);
+    Ok(())
+}
+
+fn create_temp(dir: &Path, file_name: &str) -> io::Result<(PathBuf, File)> {
+    let mut attempt = 0;
+    loop {
+        let temp_path = dir.join(format!(".{}.{}.{}.tmp", file_name, std::process::id(), attempt));
+        match OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn backup(target: &Path, mode: &BackupMode) -> io::Result<()> {
+    let backup_path = match mode {
+        BackupMode::None => return Ok(()),
+        BackupMode::Sibling => {
+            let mut name = target.as_os_str().to_owned();
+            name.push(".bak");
+            PathBuf::from(name)
+        }
+        BackupMode::Directory(dir) => {
+            fs::create_dir_all(dir)?;
+            let mut name = target.file_name().unwrap_or_default().to_owned();
+            name.push(format!(".{:016x}", fnv1a(target.as_os_str().as_encoded_bytes())));
+            dir.join(name)
+        }
+    };
+    fs::copy(target, backup_path).map(|_| ())
+}
+
+/// Makes the rename itself durable. Best effort: not every platform lets a
+/// directory be opened and synced.
+fn sync_dir(dir: &Path) {
+    if cfg!(unix) {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leftovers(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect()
+    }
+
+    #[test]
+    fn replaces_contents_and_cleans_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        write(&path, b"first", &SaveOptions::default()).unwrap();
+        write(&path, b"second", &SaveOptions::default()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn failed_write_leaves_the_original_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, b"original").unwrap();
+        let result = write_with(&path, b"replacement", &SaveOptions::default(), |file, bytes| {
+            file.write_all(&bytes[..4])?;
+            Err(io::Error::from(io::ErrorKind::StorageFull))
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::StorageFull);
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn missing_directory_fails_without_creating_anything() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gone/notes.txt");
+        assert_eq!(write(&path, b"x", &SaveOptions::default()).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn backup_setting_parses() {
+        assert_eq!(BackupMode::parse("none"), BackupMode::None);
+        assert_eq!(BackupMode::parse("sibling"), BackupMode::Sibling);
+        assert_eq!(BackupMode::parse("/var/bak"), BackupMode::Directory(PathBuf::from("/var/bak")));
+    }
+
+    #[test]
+    fn backups_hold_the_previous_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, b"v1").unwrap();
+        write(&path, b"v2", &SaveOptions { backup: BackupMode::Sibling }).unwrap();
+        assert_eq!(fs::read(dir.path().join("notes.txt.bak")).unwrap(), b"v1");
+
+        let backups = dir.path().join("backups");
+        write(&path, b"v3", &SaveOptions { backup: BackupMode::Directory(backups.clone()) }).unwrap();
+        let names: Vec<_> = fs::read_dir(&backups).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(names.len(), 1);
+        assert!(names[0].file_name().unwrap().to_string_lossy().starts_with("notes.txt."));
+        assert_eq!(fs::read(&names[0]).unwrap(), b"v2");
+        assert_eq!(fs::read(&path).unwrap(), b"v3");
+    }
+
+    #[test]
+    fn files_of_the_same_name_get_separate_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let backups = dir.path().join("backups");
+        let options = SaveOptions { backup: BackupMode::Directory(backups.clone()) };
+        for (folder, contents) in [("a", b"from a"), ("b", b"from b")] {
+            let path = dir.path().join(folder).join("mod.rs");
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            write(&path, b"new", &options).unwrap();
+        }
+        let mut saved: Vec<Vec<u8>> = fs::read_dir(&backups).unwrap().map(|entry| fs::read(entry.unwrap().path()).unwrap()).collect();
+        saved.sort();
+        assert_eq!(saved, [b"from a".to_vec(), b"from b".to_vec()]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_permissions_and_writes_through_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.sh");
+        fs::write(&path, b"echo old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+        let link = dir.path().join("link.sh");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        write(&link, b"echo new", &SaveOptions::default()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"echo new");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o750);
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    }
+}
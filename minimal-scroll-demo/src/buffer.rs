@@ -0,0 +1,860 @@
+use ropey::Rope;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::clipboard::ClipboardEntry;
+use crate::encoding::{self, Decoded, TextEncoding};
+use crate::history::{Edit, EditKind, History};
+use crate::line_ending::{self, LineEnding};
+use crate::selection::Selection;
+
+/// Text of an open tab, stored in a rope so edits stay cheap on large files.
+///
+/// Positions are either absolute byte offsets into the text, or `(row, col)`
+/// pairs where `col` is a byte offset into the line. Cursor movement steps
+/// over whole grapheme clusters, so positions always sit on char (and, when
+/// moved by the editor, grapheme) boundaries. Line endings are
+/// normalized to `\n` on the way in and `line_ending` puts them back on the
+/// way out; a final newline is ordinary text, so it survives either way. A
+/// file with mixed line endings keeps its own break for each line in
+/// `mixed_breaks`, which edits keep in step with the lines.
+/// Every edit is recorded in the buffer's undo history. `encoding` is what the
+/// file is written back in; `read_only` buffers (the hex view of a binary
+/// file) refuse edits and saves.
+#[derive(Clone, Default)]
+pub struct Buffer {
+    rope: Rope,
+    history: History,
+    pub encoding: TextEncoding,
+    pub line_ending: LineEnding,
+    saved_line_ending: LineEnding,
+    mixed_breaks: Vec<LineEnding>,
+    pub read_only: bool,
+}
+
+impl Buffer {
+    pub fn from_text(text: &str) -> Self {
+        let line_ending = LineEnding::detect(text);
+        Self {
+            rope: Rope::from_str(&normalize_newlines(text)),
+            line_ending,
+            saved_line_ending: line_ending,
+            mixed_breaks: if line_ending == LineEnding::Mixed { line_ending::breaks(text) } else { Vec::new() },
+            ..Self::default()
+        }
+    }
+
+    /// A buffer for a file's bytes, decoded in whatever encoding they appear
+    /// to be in, or shown as a read-only hex dump when they look binary.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        match encoding::decode(bytes) {
+            Decoded::Text(text, encoding) => Self { encoding, ..Self::from_text(&text) },
+            Decoded::Binary => Self { read_only: true, ..Self::from_text(&encoding::hex_dump(bytes)) },
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.rope.to_string()
+    }
+
+    /// The text as it goes to disk, with the buffer's line endings.
+    pub fn file_text(&self) -> String {
+        self.with_line_endings(&self.text())
+    }
+
+    /// Buffer text, such as an earlier state of this buffer, with the buffer's
+    /// line endings.
+    pub fn with_line_endings(&self, text: &str) -> String {
+        self.line_ending.apply(text, &self.mixed_breaks).into_owned()
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    /// Number of lines; a trailing newline starts an extra, empty line.
+    pub fn line_count(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    /// Contents of `row` without its line break.
+    pub fn line(&self, row: usize) -> String {
+        let start = self.rope.line_to_byte(row);
+        self.rope.byte_slice(start..start + self.line_len(row)).to_string()
+    }
+
+    /// Length of `row` in bytes, not counting the line break.
+    pub fn line_len(&self, row: usize) -> usize {
+        let start = self.rope.line_to_byte(row);
+        let end = if row + 1 < self.line_count() {
+            self.rope.line_to_byte(row + 1) - 1
+        } else {
+            self.rope.len_bytes()
+        };
+        end - start
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        (0..self.line_count()).map(|row| self.line(row))
+    }
+
+    /// The char index at byte `offset`; offsets past the end count as the end.
+    pub fn byte_to_char(&self, offset: usize) -> usize {
+        self.rope.byte_to_char(offset.min(self.len_bytes()))
+    }
+
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        self.rope.char_to_byte(char_idx)
+    }
+
+    /// Byte offset of `(row, col)`, clamped to the end of the line and snapped
+    /// back to a char boundary.
+    pub fn point_to_offset(&self, row: usize, col: usize) -> usize {
+        let row = row.min(self.line_count() - 1);
+        let offset = self.rope.line_to_byte(row) + col.min(self.line_len(row));
+        self.char_to_byte(self.byte_to_char(offset))
+    }
+
+    pub fn offset_to_point(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.len_bytes());
+        let row = self.rope.byte_to_line(offset);
+        (row, offset - self.rope.line_to_byte(row))
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> String {
+        self.rope.byte_slice(range).to_string()
+    }
+
+    /// Byte range of `row`, including its line break if it has one.
+    pub fn line_range(&self, row: usize) -> Range<usize> {
+        let start = self.rope.line_to_byte(row);
+        let end = if row + 1 < self.line_count() { self.rope.line_to_byte(row + 1) } else { self.len_bytes() };
+        start..end
+    }
+
+    /// Range of the word touching `offset`, or an empty range when there is none.
+    pub fn word_range_at(&self, offset: usize) -> Range<usize> {
+        let (row, col) = self.offset_to_point(offset);
+        let line = self.line(row);
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let start = line[..col].char_indices().rev().take_while(|&(_, c)| is_word(c)).last().map_or(col, |(i, _)| i);
+        let end = line[col..].char_indices().find(|&(_, c)| !is_word(c)).map_or(line.len(), |(i, _)| col + i);
+        let line_start = self.rope.line_to_byte(row);
+        line_start + start..line_start + end
+    }
+
+    /// Offset of the next occurrence of `query` at or after `from`, wrapping
+    /// around to the start of the buffer.
+    pub fn find_next(&self, query: &str, from: usize) -> Option<usize> {
+        if query.is_empty() {
+            return None;
+        }
+        let text = self.text();
+        let from = from.min(text.len());
+        text[from..].find(query).map(|i| from + i).or_else(|| text.find(query))
+    }
+
+    /// Start of the grapheme cluster before `offset`. A line break counts as
+    /// one cluster.
+    pub fn prev_grapheme(&self, offset: usize) -> usize {
+        if offset == 0 {
+            return 0;
+        }
+        let (row, col) = self.offset_to_point(offset);
+        if col == 0 {
+            return offset - 1;
+        }
+        let line = self.line(row);
+        let start = line[..col].grapheme_indices(true).next_back().map_or(0, |(i, _)| i);
+        offset - col + start
+    }
+
+    /// End of the grapheme cluster at `offset`. A line break counts as one
+    /// cluster.
+    pub fn next_grapheme(&self, offset: usize) -> usize {
+        if offset >= self.len_bytes() {
+            return self.len_bytes();
+        }
+        let (row, col) = self.offset_to_point(offset);
+        let line = self.line(row);
+        match line[col..].graphemes(true).next() {
+            Some(grapheme) => offset + grapheme.len(),
+            None => offset + 1,
+        }
+    }
+
+    /// On-screen column of byte column `col` of `row`: wide (CJK, emoji)
+    /// clusters take two cells, combining marks none, and tabs run to the next
+    /// stop of every `tab_size` cells.
+    pub fn display_column(&self, row: usize, col: usize, tab_size: usize) -> usize {
+        let line = self.line(row);
+        let col = col.min(line.len());
+        line[..col].graphemes(true).fold(0, |width, grapheme| width + grapheme_width(grapheme, width, tab_size))
+    }
+
+    /// Byte column of the last grapheme boundary of `row` whose display column
+    /// does not pass `target`.
+    pub fn col_for_display_column(&self, row: usize, target: usize, tab_size: usize) -> usize {
+        let line = self.line(row);
+        let mut width = 0;
+        for (col, grapheme) in line.grapheme_indices(true) {
+            let next = width + grapheme_width(grapheme, width, tab_size);
+            if next > target {
+                return col;
+            }
+            width = next;
+        }
+        line.len()
+    }
+
+    /// Moves `offset` one line up or down, aiming for display column `goal`
+    /// (or the current one). Returns the new offset and the goal to keep for
+    /// the next vertical move. Past the first or last line it goes to the
+    /// start or end of the buffer.
+    pub fn vertical_move(&self, offset: usize, down: bool, goal: Option<usize>, tab_size: usize) -> (usize, usize) {
+        let (row, col) = self.offset_to_point(offset);
+        let goal = goal.unwrap_or_else(|| self.display_column(row, col, tab_size));
+        let target_row = if down { row + 1 } else { row.wrapping_sub(1) };
+        if target_row >= self.line_count() {
+            return (if down { self.len_bytes() } else { 0 }, goal);
+        }
+        let col = self.col_for_display_column(target_row, goal, tab_size);
+        (self.line_to_byte(target_row) + col, goal)
+    }
+
+    /// Applies one edit per selection as a single undo step. `edit_for` gets
+    /// each selection against the unedited text and returns the range to
+    /// replace and its replacement. Returns a cursor after each replacement.
+    pub fn edit(
+        &mut self,
+        selections: &[Selection],
+        kind: EditKind,
+        mut edit_for: impl FnMut(&Self, &Selection) -> (Range<usize>, String),
+    ) -> Vec<Selection> {
+        let mut planned: Vec<(Range<usize>, String)> = selections.iter().map(|s| edit_for(self, s)).collect();
+        planned.sort_by_key(|(range, _)| range.start);
+
+        let mut edits = Vec::new();
+        let mut cursors = Vec::new();
+        let mut delta = 0isize;
+        let mut last_end = 0;
+        for (range, text) in planned {
+            let range = range.start.max(last_end)..range.end.max(last_end);
+            last_end = range.end;
+            let start = (range.start as isize + delta) as usize;
+            let edit = self.apply(start, range.end - range.start, &text);
+            delta += edit.inserted.len() as isize - edit.deleted.len() as isize;
+            cursors.push(Selection::cursor(start + edit.inserted.len()));
+            if !edit.deleted.is_empty() || !edit.inserted.is_empty() {
+                edits.push(edit);
+            }
+        }
+        self.history.record(edits, kind, selections.to_vec(), cursors.clone());
+        cursors
+    }
+
+    /// Replaces every selection with `text`. Runs of typing merge into one undo
+    /// step; anything with a newline starts its own.
+    pub fn insert(&mut self, selections: &[Selection], text: &str) -> Vec<Selection> {
+        let kind = if text.contains('\n') { EditKind::Other } else { EditKind::Typing };
+        self.edit(selections, kind, |_, s| (s.range(), text.to_string()))
+    }
+
+    /// Deletes each selection, or the char before each cursor.
+    pub fn backspace(&mut self, selections: &[Selection]) -> Vec<Selection> {
+        self.edit(selections, EditKind::Deletion, |buffer, s| {
+            if s.is_empty() {
+                (buffer.prev_grapheme(s.head)..s.head, String::new())
+            } else {
+                (s.range(), String::new())
+            }
+        })
+    }
+
+    /// What copying `selections` puts on the clipboard: the selected text, or
+    /// the whole line under each caret when nothing is selected.
+    pub fn copy(&self, selections: &[Selection]) -> ClipboardEntry {
+        let whole_lines = selections.iter().all(|s| s.is_empty());
+        if !whole_lines {
+            let pieces: Vec<String> = selections.iter().filter(|s| !s.is_empty()).map(|s| self.slice(s.range())).collect();
+            return ClipboardEntry { text: pieces.join("\n"), pieces, whole_lines };
+        }
+        let mut rows: Vec<usize> = selections.iter().map(|s| self.offset_to_point(s.head).0).collect();
+        rows.dedup();
+        let pieces: Vec<String> = rows
+            .into_iter()
+            .map(|row| {
+                let mut line = self.slice(self.line_range(row));
+                if !line.ends_with('\n') {
+                    line.push('\n');
+                }
+                line
+            })
+            .collect();
+        ClipboardEntry { text: pieces.concat(), pieces, whole_lines }
+    }
+
+    /// Copies `selections`, then deletes what was copied.
+    pub fn cut(&mut self, selections: &[Selection]) -> (ClipboardEntry, Vec<Selection>) {
+        let entry = self.copy(selections);
+        let cursors = self.edit(selections, EditKind::Other, |buffer, s| {
+            if entry.whole_lines {
+                (buffer.line_range(buffer.offset_to_point(s.head).0), String::new())
+            } else {
+                (s.range(), String::new())
+            }
+        });
+        (entry, cursors)
+    }
+
+    /// Pastes `entry` as one undo step. Each selection gets its own piece when
+    /// the counts match; lines copied without a selection go in above each
+    /// caret, which stays where it was in its line.
+    pub fn paste(&mut self, selections: &[Selection], entry: &ClipboardEntry) -> Vec<Selection> {
+        let distribute = entry.pieces.len() > 1 && entry.pieces.len() == selections.len();
+        let columns: Vec<usize> = selections
+            .iter()
+            .map(|s| s.head - self.line_range(self.offset_to_point(s.head).0).start)
+            .collect();
+        let mut index = 0;
+        let cursors = self.edit(selections, EditKind::Other, |buffer, s| {
+            let text = if distribute { entry.pieces[index].clone() } else { entry.text.clone() };
+            index += 1;
+            if entry.whole_lines && s.is_empty() {
+                let line_start = buffer.line_range(buffer.offset_to_point(s.head).0).start;
+                (line_start..line_start, text)
+            } else {
+                (s.range(), text)
+            }
+        });
+        if !entry.whole_lines {
+            return cursors;
+        }
+        cursors
+            .into_iter()
+            .zip(selections.iter().zip(columns))
+            .map(|(cursor, (s, column))| if s.is_empty() { Selection::cursor(cursor.head + column) } else { cursor })
+            .collect()
+    }
+
+    /// Reverts the last undo step and returns the selections from before it.
+    pub fn undo(&mut self) -> Option<Vec<Selection>> {
+        let transaction = self.history.undo()?;
+        for edit in transaction.edits.iter().rev() {
+            self.apply(edit.offset, edit.inserted.len(), &edit.deleted);
+        }
+        Some(transaction.selections_before)
+    }
+
+    /// Reapplies the last undone step and returns the selections after it.
+    pub fn redo(&mut self) -> Option<Vec<Selection>> {
+        let transaction = self.history.redo()?;
+        for edit in &transaction.edits {
+            self.apply(edit.offset, edit.deleted.len(), &edit.inserted);
+        }
+        Some(transaction.selections_after)
+    }
+
+    /// Ends the current run of typing so the next edit starts a new undo step.
+    pub fn break_undo_group(&mut self) {
+        self.history.seal();
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.history.mark_saved();
+        self.saved_line_ending = self.line_ending;
+    }
+
+    /// Whether saving would change the file: the text differs from the saved
+    /// state, or the line endings were converted since.
+    pub fn is_modified(&self) -> bool {
+        self.history.is_modified() || self.line_ending != self.saved_line_ending
+    }
+
+    /// Replaces the whole text as one undo step, if it differs.
+    pub fn set_text(&mut self, text: &str) {
+        if text != self.text() {
+            self.edit(&[Selection::new(0, self.len_bytes())], EditKind::Other, |_, s| (s.range(), text.to_string()));
+        }
+    }
+
+    /// `offset` moved back onto the text, for positions kept from before the
+    /// text changed underneath them.
+    pub fn clamp_offset(&self, offset: usize) -> usize {
+        self.char_to_byte(self.byte_to_char(offset))
+    }
+
+    /// The text as it was when last saved or loaded, found by walking the undo
+    /// history back (or forward) to that point. `None` when the saved state is
+    /// no longer in the history.
+    pub fn saved_text(&self) -> Option<String> {
+        for undo in [true, false] {
+            let mut buffer = self.clone();
+            while buffer.history.is_modified() {
+                let stepped = if undo { buffer.undo() } else { buffer.redo() };
+                if stepped.is_none() {
+                    break;
+                }
+            }
+            if !buffer.history.is_modified() {
+                return Some(buffer.text());
+            }
+        }
+        None
+    }
+
+    /// Replaces the contents with a fresh read of the file and marks them
+    /// saved. The replacement is one undo step, so the old text can be got
+    /// back. A binary file on either side replaces the buffer outright.
+    pub fn reload(&mut self, bytes: &[u8]) {
+        let fresh = Buffer::from_bytes(bytes);
+        if self.read_only || fresh.read_only {
+            *self = fresh;
+            return;
+        }
+        self.set_text(&fresh.text());
+        self.encoding = fresh.encoding;
+        self.line_ending = fresh.line_ending;
+        self.mixed_breaks = fresh.mixed_breaks;
+        self.mark_saved();
+    }
+
+    fn line_to_byte(&self, row: usize) -> usize {
+        self.rope.line_to_byte(row)
+    }
+
+    fn apply(&mut self, offset: usize, len: usize, text: &str) -> Edit {
+        let start = self.byte_to_char(offset);
+        let end = self.byte_to_char(offset + len);
+        let deleted = self.rope.slice(start..end).to_string();
+        let inserted = normalize_newlines(text);
+        if !self.mixed_breaks.is_empty() {
+            // Line breaks typed or pasted in take the file's first style.
+            let row = self.rope.char_to_line(start);
+            let removed = (row + deleted.matches('\n').count()).min(self.mixed_breaks.len());
+            let added = vec![self.mixed_breaks[0]; inserted.matches('\n').count()];
+            self.mixed_breaks.splice(row.min(removed)..removed, added);
+        }
+        self.rope.remove(start..end);
+        self.rope.insert(start, &inserted);
+        Edit { offset: self.char_to_byte(start), deleted, inserted }
+    }
+
+    /// Byte offsets of every occurrence of `query`, as `(row, col)` pairs.
+    pub fn find_all(&self, query: &str) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        if query.is_empty() {
+            return matches;
+        }
+        for (row, line) in self.lines().enumerate() {
+            let mut start = 0;
+            while let Some(pos) = line[start..].find(query) {
+                matches.push((row, start + pos));
+                start += pos + query.len();
+            }
+        }
+        matches
+    }
+
+    /// Replaces every occurrence of `query` and returns how many were replaced.
+    pub fn replace_all(&mut self, query: &str, replacement: &str) -> usize {
+        if query.is_empty() {
+            return 0;
+        }
+        let text = self.text();
+        let offsets: Vec<usize> = text.match_indices(query).map(|(i, _)| i).collect();
+        let Some(&first) = offsets.first() else { return 0 };
+        let edits: Vec<Edit> = offsets
+            .iter()
+            .rev()
+            .map(|&offset| self.apply(offset, query.len(), replacement))
+            .collect();
+        let cursor = vec![Selection::cursor(first)];
+        self.history.record(edits, EditKind::Other, cursor.clone(), cursor);
+        offsets.len()
+    }
+}
+
+fn grapheme_width(grapheme: &str, column: usize, tab_size: usize) -> usize {
+    if grapheme == "\t" {
+        tab_size - column % tab_size
+    } else {
+        grapheme.width()
+    }
+}
+
+fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursors(offsets: &[usize]) -> Vec<Selection> {
+        offsets.iter().map(|&o| Selection::cursor(o)).collect()
+    }
+
+    fn type_text(buffer: &mut Buffer, mut selections: Vec<Selection>, text: &str) -> Vec<Selection> {
+        for ch in text.chars() {
+            selections = buffer.insert(&selections, &ch.to_string());
+        }
+        selections
+    }
+
+    #[test]
+    fn lines_exclude_line_breaks() {
+        let buffer = Buffer::from_text("one\ntwo\r\nthree");
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(buffer.lines().collect::<Vec<_>>(), vec!["one", "two", "three"]);
+        assert_eq!(buffer.line_len(1), 3);
+        assert_eq!(buffer.line_range(1), 4..8);
+        assert_eq!(buffer.text(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn trailing_newline_starts_an_empty_line() {
+        let buffer = Buffer::from_text("a\n");
+        assert_eq!(buffer.line_count(), 2);
+        assert_eq!(buffer.line(1), "");
+    }
+
+    #[test]
+    fn offset_point_round_trip() {
+        let buffer = Buffer::from_text("ab\ncde\n\nf");
+        for offset in 0..=buffer.len_bytes() {
+            let (row, col) = buffer.offset_to_point(offset);
+            assert_eq!(buffer.point_to_offset(row, col), offset);
+        }
+        assert_eq!(buffer.offset_to_point(4), (1, 1));
+        assert_eq!(buffer.point_to_offset(1, 99), 6);
+    }
+
+    #[test]
+    fn byte_char_line_conversions() {
+        let buffer = Buffer::from_text("aé\nb");
+        assert_eq!(buffer.len_bytes(), 5);
+        assert_eq!(buffer.byte_to_char(3), 2);
+        assert_eq!(buffer.byte_to_char(100), 4);
+        assert_eq!(buffer.char_to_byte(3), 4);
+        assert_eq!(buffer.offset_to_point(4), (1, 0));
+        assert_eq!(buffer.point_to_offset(0, 2), 1);
+    }
+
+    #[test]
+    fn insert_replaces_selection_and_returns_cursor() {
+        let mut buffer = Buffer::from_text("hello world");
+        assert_eq!(buffer.insert(&[Selection::new(5, 11)], ",\nthere"), cursors(&[12]));
+        assert_eq!(buffer.text(), "hello,\nthere");
+    }
+
+    #[test]
+    fn insert_at_every_cursor() {
+        let mut buffer = Buffer::from_text("a\nb\nc");
+        let after = buffer.insert(&cursors(&[0, 2, 4]), "- ");
+        assert_eq!(buffer.text(), "- a\n- b\n- c");
+        assert_eq!(after, cursors(&[2, 6, 10]));
+    }
+
+    #[test]
+    fn backspace_joins_lines_and_deletes_selections() {
+        let mut buffer = Buffer::from_text("ab\ncd");
+        assert_eq!(buffer.backspace(&cursors(&[3])), cursors(&[2]));
+        assert_eq!(buffer.text(), "abcd");
+        assert_eq!(buffer.backspace(&[Selection::new(3, 1), Selection::cursor(4)]), cursors(&[1, 1]));
+        assert_eq!(buffer.text(), "a");
+        assert_eq!(buffer.backspace(&cursors(&[0])), cursors(&[0]));
+    }
+
+    #[test]
+    fn vertical_movement_keeps_goal_column() {
+        let buffer = Buffer::from_text("long line\nab\nlonger line");
+        assert_eq!(buffer.vertical_move(7, true, None, 4), (12, 7));
+        assert_eq!(buffer.vertical_move(12, true, Some(7), 4), (20, 7));
+        assert_eq!(buffer.vertical_move(12, false, None, 4), (2, 2));
+        assert_eq!(buffer.vertical_move(16, true, None, 4), (buffer.len_bytes(), 3));
+        assert_eq!(buffer.vertical_move(3, false, None, 4), (0, 3));
+    }
+
+    #[test]
+    fn cjk_moves_by_char_and_counts_double_width() {
+        let buffer = Buffer::from_text("日本語\nabcdef");
+        assert_eq!(buffer.next_grapheme(0), 3);
+        assert_eq!(buffer.prev_grapheme(6), 3);
+        assert_eq!(buffer.display_column(0, 6, 4), 4);
+        assert_eq!(buffer.col_for_display_column(0, 3, 4), 3);
+        assert_eq!(buffer.vertical_move(6, true, None, 4), (14, 4));
+        assert_eq!(buffer.vertical_move(14, false, None, 4), (6, 4));
+    }
+
+    #[test]
+    fn emoji_sequences_are_single_clusters() {
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let flag = "🇯🇵";
+        let text = format!("a{family}{flag}b");
+        let buffer = Buffer::from_text(&text);
+        let after_family = 1 + family.len();
+        assert_eq!(buffer.next_grapheme(1), after_family);
+        assert_eq!(buffer.next_grapheme(after_family), after_family + flag.len());
+        assert_eq!(buffer.prev_grapheme(after_family), 1);
+        assert_eq!(buffer.display_column(0, 1, 4), 1);
+        assert_eq!(buffer.display_column(0, after_family + flag.len(), 4), 5);
+    }
+
+    #[test]
+    fn combining_marks_stay_with_their_base() {
+        let buffer = Buffer::from_text("e\u{301}x");
+        assert_eq!(buffer.next_grapheme(0), 3);
+        assert_eq!(buffer.prev_grapheme(3), 0);
+        assert_eq!(buffer.display_column(0, 3, 4), 1);
+        assert_eq!(buffer.col_for_display_column(0, 1, 4), 3);
+    }
+
+    #[test]
+    fn backspace_removes_a_whole_cluster() {
+        let mut buffer = Buffer::from_text("ok👍🏽\ne\u{301}");
+        let line_end = buffer.line_len(0);
+        assert_eq!(buffer.backspace(&cursors(&[line_end])), cursors(&[2]));
+        assert_eq!(buffer.text(), "ok\ne\u{301}");
+        assert_eq!(buffer.backspace(&cursors(&[buffer.len_bytes()])), cursors(&[3]));
+        assert_eq!(buffer.text(), "ok\n");
+    }
+
+    #[test]
+    fn movement_never_splits_a_char() {
+        let buffer = Buffer::from_text("añ中😀\u{301}z\n\tä");
+        let mut offset = 0;
+        while offset < buffer.len_bytes() {
+            let next = buffer.next_grapheme(offset);
+            assert!(next > offset);
+            assert_eq!(buffer.prev_grapheme(next), offset);
+            assert_eq!(buffer.point_to_offset(buffer.offset_to_point(next).0, buffer.offset_to_point(next).1), next);
+            offset = next;
+        }
+        assert_eq!(buffer.display_column(1, 1, 4), 4);
+    }
+
+    #[test]
+    fn tabs_run_to_the_next_stop_of_the_tab_size() {
+        let buffer = Buffer::from_text("\tx\n12345678y");
+        assert_eq!(buffer.display_column(0, 1, 8), 8);
+        assert_eq!(buffer.display_column(0, 2, 2), 3);
+        assert_eq!(buffer.vertical_move(1, true, None, 8), (11, 8));
+        assert_eq!(buffer.vertical_move(1, true, None, 4), (7, 4));
+    }
+
+    #[test]
+    fn typing_multibyte_text_keeps_cursor_on_boundary() {
+        let mut buffer = Buffer::from_text("");
+        let selections = type_text(&mut buffer, cursors(&[0]), "héllo, 世界");
+        assert_eq!(selections, cursors(&["héllo, 世界".len()]));
+        assert_eq!(buffer.text(), "héllo, 世界");
+    }
+
+    #[test]
+    fn word_and_occurrence_lookup() {
+        let buffer = Buffer::from_text("let foo_bar = foo;");
+        assert_eq!(buffer.word_range_at(6), 4..11);
+        assert_eq!(buffer.word_range_at(12), 12..12);
+        assert_eq!(buffer.find_next("foo", 5), Some(14));
+        assert_eq!(buffer.find_next("foo", 15), Some(4));
+        assert_eq!(buffer.find_next("baz", 0), None);
+    }
+
+    #[test]
+    fn replace_all_counts_matches() {
+        let mut buffer = Buffer::from_text("foo bar\nfoo\n");
+        assert_eq!(buffer.find_all("foo"), vec![(0, 0), (1, 0)]);
+        assert_eq!(buffer.replace_all("foo", "quux"), 2);
+        assert_eq!(buffer.text(), "quux bar\nquux\n");
+        buffer.undo();
+        assert_eq!(buffer.text(), "foo bar\nfoo\n");
+    }
+
+    #[test]
+    fn typing_run_undoes_as_one_step() {
+        let mut buffer = Buffer::from_text("");
+        let selections = type_text(&mut buffer, cursors(&[0]), "abc");
+        let selections = buffer.insert(&selections, "\n");
+        type_text(&mut buffer, selections, "d");
+        assert_eq!(buffer.undo(), Some(cursors(&[4])));
+        assert_eq!(buffer.undo(), Some(cursors(&[3])));
+        assert_eq!(buffer.undo(), Some(cursors(&[0])));
+        assert_eq!(buffer.text(), "");
+        assert_eq!(buffer.undo(), None);
+        assert_eq!(buffer.redo(), Some(cursors(&[3])));
+        assert_eq!(buffer.text(), "abc");
+    }
+
+    #[test]
+    fn multi_cursor_typing_undoes_as_one_step() {
+        let mut buffer = Buffer::from_text("x\ny");
+        type_text(&mut buffer, cursors(&[1, 3]), "ab");
+        assert_eq!(buffer.text(), "xab\nyab");
+        assert_eq!(buffer.undo(), Some(cursors(&[1, 3])));
+        assert_eq!(buffer.text(), "x\ny");
+    }
+
+    #[test]
+    fn cursor_move_breaks_typing_run() {
+        let mut buffer = Buffer::from_text("");
+        let selections = buffer.insert(&cursors(&[0]), "a");
+        buffer.break_undo_group();
+        buffer.insert(&selections, "b");
+        buffer.undo();
+        assert_eq!(buffer.text(), "a");
+    }
+
+    #[test]
+    fn backspace_run_restores_cursor() {
+        let mut buffer = Buffer::from_text("hello");
+        let mut selections = cursors(&[5]);
+        for _ in 0..5 {
+            selections = buffer.backspace(&selections);
+        }
+        assert_eq!(buffer.text(), "");
+        assert_eq!(buffer.undo(), Some(cursors(&[5])));
+        assert_eq!(buffer.text(), "hello");
+    }
+
+    #[test]
+    fn undo_to_saved_state_is_unmodified() {
+        let mut buffer = Buffer::from_text("x");
+        assert!(!buffer.is_modified());
+        buffer.insert(&cursors(&[1]), "y");
+        buffer.mark_saved();
+        assert!(!buffer.is_modified());
+        buffer.insert(&cursors(&[2]), "z");
+        assert!(buffer.is_modified());
+        buffer.undo();
+        assert!(!buffer.is_modified());
+        buffer.undo();
+        assert!(buffer.is_modified());
+        buffer.redo();
+        assert!(!buffer.is_modified());
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut buffer = Buffer::from_text("");
+        buffer.insert(&cursors(&[0]), "a");
+        buffer.undo();
+        buffer.insert(&cursors(&[0]), "b");
+        assert_eq!(buffer.redo(), None);
+        assert_eq!(buffer.text(), "b");
+    }
+
+    #[test]
+    fn copy_without_selection_takes_whole_lines() {
+        let buffer = Buffer::from_text("one\ntwo");
+        let entry = buffer.copy(&cursors(&[1, 2, 5]));
+        assert!(entry.whole_lines);
+        assert_eq!(entry.text, "one\ntwo\n");
+        let entry = buffer.copy(&[Selection::new(0, 3), Selection::cursor(5), Selection::new(4, 7)]);
+        assert!(!entry.whole_lines);
+        assert_eq!(entry.text, "one\ntwo");
+    }
+
+    #[test]
+    fn cut_line_and_paste_it_above() {
+        let mut buffer = Buffer::from_text("one\ntwo\nthree");
+        let (entry, after) = buffer.cut(&cursors(&[1]));
+        assert_eq!(buffer.text(), "two\nthree");
+        assert_eq!(after, cursors(&[0]));
+        let after = buffer.paste(&cursors(&[6]), &entry);
+        assert_eq!(buffer.text(), "two\none\nthree");
+        assert_eq!(after, cursors(&[10]));
+    }
+
+    #[test]
+    fn paste_splits_pieces_across_matching_selections() {
+        let mut buffer = Buffer::from_text("a b");
+        let entry = ClipboardEntry { text: "x\ny".into(), pieces: vec!["x".into(), "y".into()], whole_lines: false };
+        buffer.paste(&cursors(&[1, 3]), &entry);
+        assert_eq!(buffer.text(), "ax by");
+        buffer.paste(&cursors(&[0]), &entry);
+        assert_eq!(buffer.text(), "x\nyax by");
+    }
+
+    #[test]
+    fn paste_normalizes_foreign_line_endings() {
+        let mut buffer = Buffer::from_text("ab");
+        let after = buffer.paste(&cursors(&[1]), &ClipboardEntry::plain("1\r\n2\r3".into()));
+        assert_eq!(buffer.text(), "a1\n2\n3b");
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(after, cursors(&[6]));
+        buffer.undo();
+        assert_eq!(buffer.text(), "ab");
+    }
+
+    #[test]
+    fn fixtures_round_trip_byte_for_byte() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/line_endings");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let bytes = std::fs::read(&path).unwrap();
+            let buffer = Buffer::from_bytes(&bytes);
+            assert!(!buffer.text().contains('\r'), "{}", path.display());
+            assert_eq!(buffer.encoding.encode(&buffer.file_text()).unwrap(), bytes, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn edits_are_saved_with_the_file_line_endings() {
+        let mut buffer = Buffer::from_text("one\r\ntwo\r\n");
+        assert_eq!(buffer.line_ending, LineEnding::CrLf);
+        buffer.insert(&cursors(&[3]), "\r\nnew");
+        assert_eq!(buffer.text(), "one\nnew\ntwo\n");
+        assert_eq!(buffer.file_text(), "one\r\nnew\r\ntwo\r\n");
+    }
+
+    #[test]
+    fn mixed_line_endings_are_kept_line_by_line() {
+        let mut buffer = Buffer::from_text("one\r\ntwo\nthree\r\n");
+        assert_eq!(buffer.line_ending, LineEnding::Mixed);
+        buffer.insert(&cursors(&[4]), "new\n");
+        assert_eq!(buffer.file_text(), "one\r\nnew\r\ntwo\nthree\r\n");
+        buffer.insert(&[Selection::new(0, 8)], "");
+        assert_eq!(buffer.file_text(), "two\nthree\r\n");
+        buffer.line_ending = LineEnding::Lf;
+        assert!(buffer.is_modified());
+        assert_eq!(buffer.file_text(), "two\nthree\n");
+    }
+
+    #[test]
+    fn converting_line_endings_marks_the_buffer_modified() {
+        let mut buffer = Buffer::from_text("a\nb");
+        buffer.line_ending = LineEnding::CrLf;
+        assert!(buffer.is_modified());
+        assert_eq!(buffer.file_text(), "a\r\nb");
+        buffer.line_ending = LineEnding::Lf;
+        assert!(!buffer.is_modified());
+        buffer.line_ending = LineEnding::CrLf;
+        buffer.mark_saved();
+        assert!(!buffer.is_modified());
+    }
+
+    #[test]
+    fn saved_text_walks_the_history_back() {
+        let mut buffer = Buffer::from_text("base\n");
+        buffer.insert(&cursors(&[0]), "new ");
+        assert_eq!(buffer.saved_text().as_deref(), Some("base\n"));
+        buffer.mark_saved();
+        buffer.undo();
+        assert_eq!(buffer.saved_text().as_deref(), Some("new base\n"));
+    }
+
+    #[test]
+    fn reload_is_one_undoable_step() {
+        let mut buffer = Buffer::from_text("old\n");
+        buffer.insert(&cursors(&[0]), "my ");
+        buffer.reload(b"theirs\r\n");
+        assert_eq!(buffer.text(), "theirs\n");
+        assert_eq!(buffer.line_ending, LineEnding::CrLf);
+        assert!(!buffer.is_modified());
+        buffer.undo();
+        assert_eq!(buffer.text(), "my old\n");
+        assert!(buffer.is_modified());
+    }
+}
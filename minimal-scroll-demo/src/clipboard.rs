@@ -0,0 +1,78 @@
+/// Text put on the clipboard by a copy or cut, plus what the system clipboard
+/// can't carry: the text of each selection, and whether whole lines were taken
+/// because nothing was selected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClipboardEntry {
+    pub text: String,
+    pub pieces: Vec<String>,
+    pub whole_lines: bool,
+}
+
+impl ClipboardEntry {
+    /// An entry for text that came from outside the editor.
+    pub fn plain(text: String) -> Self {
+        Self { pieces: vec![text.clone()], text, whole_lines: false }
+    }
+}
+
+const HISTORY_LIMIT: usize = 15;
+
+/// Recent clipboard entries, newest first, for Paste from History.
+#[derive(Default)]
+pub struct ClipboardHistory {
+    entries: Vec<ClipboardEntry>,
+}
+
+impl ClipboardHistory {
+    /// Adds an entry to the top, dropping an older copy of the same text.
+    pub fn push(&mut self, entry: ClipboardEntry) {
+        if entry.text.is_empty() {
+            return;
+        }
+        self.entries.retain(|e| e.text != entry.text);
+        self.entries.insert(0, entry);
+        self.entries.truncate(HISTORY_LIMIT);
+    }
+
+    pub fn entries(&self) -> &[ClipboardEntry] {
+        &self.entries
+    }
+
+    /// The entry for `text` if it was copied here, so a paste can use its
+    /// pieces and line mode; text from other programs pastes as-is.
+    pub fn resolve(&self, text: String) -> ClipboardEntry {
+        self.entries
+            .iter()
+            .find(|e| e.text == text)
+            .cloned()
+            .unwrap_or_else(|| ClipboardEntry::plain(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_moves_repeats_to_the_top_and_caps_the_list() {
+        let mut history = ClipboardHistory::default();
+        for i in 0..20 {
+            history.push(ClipboardEntry::plain(format!("{i}")));
+        }
+        history.push(ClipboardEntry::plain("10".into()));
+        history.push(ClipboardEntry::plain(String::new()));
+        let texts: Vec<&str> = history.entries().iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts.len(), HISTORY_LIMIT);
+        assert_eq!(&texts[..3], ["10", "19", "18"]);
+        assert_eq!(texts.iter().filter(|t| **t == "10").count(), 1);
+    }
+
+    #[test]
+    fn resolve_falls_back_to_plain_text() {
+        let mut history = ClipboardHistory::default();
+        let entry = ClipboardEntry { text: "a\nb\n".into(), pieces: vec!["a\n".into(), "b\n".into()], whole_lines: true };
+        history.push(entry.clone());
+        assert_eq!(history.resolve("a\nb\n".into()), entry);
+        assert_eq!(history.resolve("other".into()), ClipboardEntry::plain("other".into()));
+    }
+}
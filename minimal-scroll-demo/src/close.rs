@@ -0,0 +1,188 @@
+use gpui::*;
+use std::collections::HashSet;
+
+use crate::app_state::ScrollDemo;
+use crate::tab::TabId;
+
+/// What the unsaved-changes dialog is asking about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PendingClose {
+    /// Closing a single modified tab.
+    Tab(TabId),
+    /// Quitting or closing the window: the modified tabs still to decide on, in
+    /// tab order. The dialog shows the first.
+    Quit(Vec<TabId>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CloseChoice {
+    Save,
+    Discard,
+    SaveAll,
+    DiscardAll,
+    Cancel,
+}
+
+impl PendingClose {
+    /// What quitting has to ask about first: the modified tabs, in tab order.
+    /// `None` when there are none, or when hot exit keeps their changes in
+    /// the session instead.
+    pub fn for_quit(open_tabs: &[TabId], dirty: &HashSet<TabId>, hot_exit: bool) -> Option<Self> {
+        if hot_exit {
+            return None;
+        }
+        let tabs: Vec<TabId> = open_tabs.iter().filter(|tab| dirty.contains(tab)).cloned().collect();
+        (!tabs.is_empty()).then_some(PendingClose::Quit(tabs))
+    }
+
+    /// The tab the dialog is currently about.
+    pub fn tab(&self) -> Option<&TabId> {
+        match self {
+            PendingClose::Tab(tab) => Some(tab),
+            PendingClose::Quit(tabs) => tabs.first(),
+        }
+    }
+
+    /// How many more tabs are waiting after the current one.
+    pub fn others(&self) -> usize {
+        match self {
+            PendingClose::Tab(_) => 0,
+            PendingClose::Quit(tabs) => tabs.len().saturating_sub(1),
+        }
+    }
+
+    /// Drops the tabs that no longer need asking about, because they were
+    /// saved, discarded or closed some other way. Returns whether none are left.
+    pub fn advance(&mut self, dirty: &HashSet<TabId>) -> bool {
+        match self {
+            PendingClose::Tab(tab) => !dirty.contains(tab),
+            PendingClose::Quit(tabs) => {
+                tabs.retain(|tab| dirty.contains(tab));
+                tabs.is_empty()
+            }
+        }
+    }
+}
+
+impl ScrollDemo {
+    /// Closes `tab`, asking first when it has unsaved changes.
+    pub fn request_close_tab(&mut self, tab: TabId, cx: &mut Context<Self>) {
+        if self.dirty_tabs.contains(&tab) {
+            self.pending_close = Some(PendingClose::Tab(tab));
+        } else {
+            self.close_tab(tab, cx);
+        }
+        cx.notify();
+    }
+
+    pub fn close_active_tab(&mut self, cx: &mut Context<Self>) {
+        if let Some(tab) = self.active_tab() {
+            self.request_close_tab(tab, cx);
+        }
+    }
+
+    /// Returns whether the app can quit straight away. Any modified tabs are
+    /// asked about one by one first, and the app quits once the last is dealt
+    /// with; with hot exit turned on, the session keeps their changes instead.
+    pub fn request_quit(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(pending) = PendingClose::for_quit(&self.open_tabs, &self.dirty_tabs, self.settings.hot_exit) else {
+            return true;
+        };
+        self.pending_close = Some(pending);
+        cx.notify();
+        false
+    }
+
+    /// Acts on a button of the unsaved-changes dialog.
+    pub fn resolve_close(&mut self, choice: CloseChoice, cx: &mut Context<Self>) {
+        let Some(pending) = self.pending_close.clone() else { return };
+        let Some(tab) = pending.tab().cloned() else { return };
+        match choice {
+            CloseChoice::Save if tab.path().is_some() => {
+                if self.save_tab(tab.clone(), cx) {
+                    self.close_tab(tab, cx);
+                }
+            }
+            // Untitled: the dialog stays up behind the Save As prompt and
+            // moves on once the tab is saved and closed.
+            CloseChoice::Save => self.prompt_save_as(tab, true, cx),
+            CloseChoice::Discard => self.close_tab(tab, cx),
+            CloseChoice::SaveAll => {
+                let PendingClose::Quit(tabs) = pending else { return };
+                for tab in tabs.into_iter().filter(|tab| tab.path().is_some()) {
+                    if self.save_tab(tab.clone(), cx) {
+                        self.close_tab(tab, cx);
+                    }
+                }
+            }
+            CloseChoice::DiscardAll => {
+                let PendingClose::Quit(tabs) = pending else { return };
+                for tab in tabs {
+                    self.close_tab(tab, cx);
+                }
+            }
+            CloseChoice::Cancel => {
+                self.pending_close = None;
+                cx.notify();
+                return;
+            }
+        }
+        self.advance_close(cx);
+    }
+
+    /// Moves the dialog past whatever has been dealt with, quitting when a
+    /// quit was waiting on it.
+    pub fn advance_close(&mut self, cx: &mut Context<Self>) {
+        let Some(pending) = self.pending_close.as_mut() else { return };
+        if pending.advance(&self.dirty_tabs) {
+            let quitting = matches!(pending, PendingClose::Quit(_));
+            self.pending_close = None;
+            if quitting {
+                cx.quit();
+            }
+        }
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PendingClose;
+    use crate::tab::TabId;
+    use std::collections::HashSet;
+
+    #[test]
+    fn quit_walks_the_tabs_still_modified() {
+        let a = TabId::File("/tmp/a.txt".into());
+        let b = TabId::Untitled(1);
+        let c = TabId::File("/tmp/c.txt".into());
+        let mut pending = PendingClose::Quit(vec![a.clone(), b.clone(), c.clone()]);
+        assert_eq!(pending.tab(), Some(&a));
+        assert_eq!(pending.others(), 2);
+
+        let mut dirty: HashSet<TabId> = [b.clone(), c.clone()].into_iter().collect();
+        assert!(!pending.advance(&dirty));
+        assert_eq!(pending.tab(), Some(&b));
+
+        dirty.clear();
+        assert!(pending.advance(&dirty));
+        assert_eq!(pending.tab(), None);
+    }
+
+    #[test]
+    fn quitting_asks_about_modified_tabs_unless_hot_exit_is_on() {
+        let open = [TabId::File("/tmp/a.txt".into()), TabId::Untitled(1), TabId::File("/tmp/c.txt".into())];
+        let dirty: HashSet<TabId> = [open[2].clone(), open[1].clone()].into_iter().collect();
+        assert_eq!(PendingClose::for_quit(&open, &dirty, false), Some(PendingClose::Quit(vec![open[1].clone(), open[2].clone()])));
+        assert_eq!(PendingClose::for_quit(&open, &dirty, true), None);
+        assert_eq!(PendingClose::for_quit(&open, &HashSet::new(), false), None);
+    }
+
+    #[test]
+    fn a_single_tab_is_done_once_clean() {
+        let tab = TabId::Untitled(2);
+        let mut pending = PendingClose::Tab(tab.clone());
+        assert!(!pending.advance(&[tab].into_iter().collect()));
+        assert!(pending.advance(&HashSet::new()));
+    }
+}
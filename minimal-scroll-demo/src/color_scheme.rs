@@ -0,0 +1,373 @@
+use gpui::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use syntect::highlighting::{Color, FontStyle, ScopeSelectors, StyleModifier, Theme, ThemeItem, ThemeSet, ThemeSettings};
+
+use crate::app_state::ScrollDemo;
+use crate::json;
+use crate::picker::{Picker, PickerKind};
+use crate::settings::config_dir;
+
+/// The scheme used when the configured one can't be found.
+pub const DEFAULT_COLOR_SCHEME: &str = "base16-ocean.dark";
+
+/// How deep `var()` references may nest, so variables naming each other in a
+/// loop can't hang the loader.
+const MAX_VARIABLE_DEPTH: usize = 16;
+
+/// Whether `path` is a color scheme the editor can load.
+pub fn is_color_scheme(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "tmTheme" || extension == "sublime-color-scheme")
+}
+
+/// A `.sublime-color-scheme` file.
+#[derive(Deserialize)]
+struct SchemeFile {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    variables: HashMap<String, String>,
+    #[serde(default)]
+    globals: HashMap<String, String>,
+    #[serde(default)]
+    rules: Vec<SchemeRule>,
+}
+
+#[derive(Deserialize)]
+struct SchemeRule {
+    #[serde(default)]
+    scope: String,
+    foreground: Option<String>,
+    background: Option<String>,
+    font_style: Option<String>,
+}
+
+/// Parses a `.sublime-color-scheme` into the theme syntect highlights with,
+/// along with the colours and scopes it had to leave out.
+pub fn parse_sublime_color_scheme(text: &str) -> Result<(Theme, Vec<String>), String> {
+    let file: SchemeFile = json::from_str(text).map_err(|err| err.to_string())?;
+    let mut errors = Vec::new();
+    let mut color = |value: &str| {
+        let color = resolve_color(value, &file.variables, 0);
+        if color.is_none() {
+            errors.push(format!("unsupported color \"{value}\""));
+        }
+        color
+    };
+
+    let mut settings = ThemeSettings::default();
+    for (key, value) in &file.globals {
+        let slot = match key.as_str() {
+            "foreground" => &mut settings.foreground,
+            "background" => &mut settings.background,
+            "caret" => &mut settings.caret,
+            "line_highlight" => &mut settings.line_highlight,
+            "selection" => &mut settings.selection,
+            "selection_foreground" => &mut settings.selection_foreground,
+            "gutter" => &mut settings.gutter,
+            "gutter_foreground" => &mut settings.gutter_foreground,
+            "highlight" => &mut settings.highlight,
+            "find_highlight" => &mut settings.find_highlight,
+            "find_highlight_foreground" => &mut settings.find_highlight_foreground,
+            _ => continue,
+        };
+        *slot = color(value);
+    }
+
+    let mut scopes = Vec::new();
+    let mut bad_scopes = Vec::new();
+    for rule in &file.rules {
+        let style = StyleModifier {
+            foreground: rule.foreground.as_deref().and_then(&mut color),
+            background: rule.background.as_deref().and_then(&mut color),
+            font_style: rule.font_style.as_deref().map(font_style),
+        };
+        match ScopeSelectors::from_str(&rule.scope) {
+            Ok(scope) => scopes.push(ThemeItem { scope, style }),
+            Err(err) => bad_scopes.push(format!("bad scope \"{}\": {err}", rule.scope)),
+        }
+    }
+    errors.extend(bad_scopes);
+
+    Ok((Theme { name: file.name, author: file.author, settings, scopes }, errors))
+}
+
+/// A `font_style` like "bold italic". Sublime's underline variants all draw
+/// as a plain underline here, and glow isn't drawn at all.
+fn font_style(value: &str) -> FontStyle {
+    value.split_whitespace().fold(FontStyle::empty(), |style, word| match word {
+        "bold" => style | FontStyle::BOLD,
+        "italic" => style | FontStyle::ITALIC,
+        word if word.ends_with("underline") => style | FontStyle::UNDERLINE,
+        _ => style,
+    })
+}
+
+/// A colour as color schemes write it: `#rgb`, `#rgba`, `#rrggbb`,
+/// `#rrggbbaa`, `rgb()`, `rgba()`, `hsl()`, `hsla()`, or `var(name)` for one of
+/// the scheme's variables. Sublime's `color()` adjustments aren't supported.
+fn resolve_color(value: &str, variables: &HashMap<String, String>, depth: usize) -> Option<Color> {
+    let value = value.trim();
+    if let Some(name) = value.strip_prefix("var(").and_then(|rest| rest.strip_suffix(')')) {
+        let value = variables.get(name.trim()).filter(|_| depth < MAX_VARIABLE_DEPTH)?;
+        return resolve_color(value, variables, depth + 1);
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        return hex_color(hex);
+    }
+    let (function, args) = value.strip_suffix(')')?.split_once('(')?;
+    let args: Vec<&str> = args.split([',', ' ', '/']).filter(|arg| !arg.is_empty()).collect();
+    let alpha = match args.len() {
+        3 => 1.0,
+        4 => fraction(args[3])?,
+        _ => return None,
+    };
+    let [r, g, b] = match function.trim() {
+        "rgb" | "rgba" => {
+            let channel = |arg: &str| arg.parse::<f32>().ok().filter(|value| (0.0..=255.0).contains(value)).map(|value| value / 255.0);
+            [channel(args[0])?, channel(args[1])?, channel(args[2])?]
+        }
+        "hsl" | "hsla" => {
+            let hue = args[0].strip_suffix("deg").unwrap_or(args[0]).parse::<f32>().ok()?;
+            hsl_to_rgb(hue, fraction(args[1])?, fraction(args[2])?)
+        }
+        _ => return None,
+    };
+    let byte = |value: f32| (value * 255.0).round() as u8;
+    Some(Color { r: byte(r), g: byte(g), b: byte(b), a: byte(alpha) })
+}
+
+fn hex_color(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let width = match hex.len() {
+        3 | 4 => 1,
+        6 | 8 => 2,
+        _ => return None,
+    };
+    let channel = |i: usize| {
+        let value = u8::from_str_radix(hex.get(i * width..(i + 1) * width)?, 16).ok()?;
+        Some(if width == 1 { value * 17 } else { value })
+    };
+    let a = if hex.len() == 4 * width { channel(3)? } else { 255 };
+    Some(Color { r: channel(0)?, g: channel(1)?, b: channel(2)?, a })
+}
+
+/// "50%" or "0.5", as a fraction between 0 and 1.
+fn fraction(arg: &str) -> Option<f32> {
+    let value = match arg.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+        None => arg.parse::<f32>().ok()?,
+    };
+    (0.0..=1.0).contains(&value).then_some(value)
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> [f32; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    [r + m, g + m, b + m]
+}
+
+/// Reads a `.tmTheme` or `.sublime-color-scheme`, returning it with what had
+/// to be left out of it.
+fn read_color_scheme(path: &Path) -> Result<(Theme, Vec<String>), String> {
+    if path.extension().is_some_and(|extension| extension == "tmTheme") {
+        return ThemeSet::get_theme(path).map(|theme| (theme, Vec::new())).map_err(|err| err.to_string());
+    }
+    parse_sublime_color_scheme(&fs::read_to_string(path).map_err(|err| err.to_string())?)
+}
+
+/// The colours the editor draws itself rather than leaving to the
+/// highlighter, from the scheme's globals where it sets them.
+#[derive(Clone, Copy)]
+pub struct EditorColors {
+    pub background: Rgba,
+    pub gutter: Rgba,
+    pub gutter_foreground: Rgba,
+    pub caret: Rgba,
+    pub selection: Rgba,
+    pub selection_foreground: Option<Rgba>,
+    /// Behind the active find match.
+    pub find_highlight: Rgba,
+    pub find_highlight_foreground: Option<Rgba>,
+    /// Behind the other find matches.
+    pub highlight: Rgba,
+}
+
+impl EditorColors {
+    pub fn new(settings: &ThemeSettings) -> Self {
+        let background = settings.background.map_or(rgb(0x181818), to_rgba);
+        Self {
+            background,
+            gutter: settings.gutter.map_or(background, to_rgba),
+            gutter_foreground: settings.gutter_foreground.map_or(rgb(0x666666), to_rgba),
+            caret: settings.caret.or(settings.foreground).map_or(rgb(0xffffff), to_rgba),
+            selection: settings.selection.map_or(rgb(0x264f78), to_rgba),
+            selection_foreground: settings.selection_foreground.map(to_rgba),
+            find_highlight: settings.find_highlight.map_or(rgb(0xd18616), to_rgba),
+            find_highlight_foreground: settings.find_highlight_foreground.map(to_rgba),
+            // Sublime outlines the other matches in this colour; filling them
+            // with it instead, it is kept translucent so the text stays legible.
+            highlight: settings.highlight.map_or(rgba(0xffff0044), |color| Rgba { a: to_rgba(color).a.min(0.27), ..to_rgba(color) }),
+        }
+    }
+}
+
+pub fn to_rgba(color: Color) -> Rgba {
+    let channel = |value: u8| value as f32 / 255.0;
+    Rgba { r: channel(color.r), g: channel(color.g), b: channel(color.b), a: channel(color.a) }
+}
+
+impl ScrollDemo {
+    /// Loads syntect's color schemes plus the user's from the config
+    /// directory, each named after its file, reporting those that couldn't be
+    /// read or were only partly understood.
+    pub fn load_color_schemes(&mut self, cx: &mut Context<Self>) {
+        let mut theme_set = ThemeSet::load_defaults();
+        let mut errors = Vec::new();
+        let entries = config_dir().and_then(|dir| fs::read_dir(dir).ok());
+        for entry in entries.into_iter().flatten().filter_map(Result::ok) {
+            let path = entry.path();
+            if !is_color_scheme(&path) {
+                continue;
+            }
+            let file = entry.file_name().to_string_lossy().to_string();
+            match read_color_scheme(&path) {
+                Ok((theme, problems)) => {
+                    errors.extend(problems.into_iter().map(|problem| format!("{file}: {problem}")));
+                    let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                    theme_set.themes.insert(name, theme);
+                }
+                Err(err) => errors.push(format!("{file}: {err}")),
+            }
+        }
+        self.theme_set = theme_set;
+        if !errors.is_empty() {
+            self.notify_error(format!("Problems in your color schemes: {}", errors.join("; ")), cx);
+        }
+        cx.notify();
+    }
+
+    /// The scheme the editor is drawn in: the one being previewed, else the
+    /// active tab's.
+    pub fn color_scheme(&self) -> &Theme {
+        let name = self.color_scheme_preview.clone().unwrap_or_else(|| self.active_settings().color_scheme);
+        self.theme_set.themes.get(&name).unwrap_or(&self.theme_set.themes[DEFAULT_COLOR_SCHEME])
+    }
+
+    /// Lists every loaded color scheme, starting on the current one.
+    pub fn select_color_scheme(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let names: Vec<String> = self.theme_set.themes.keys().cloned().collect();
+        let mut picker = Picker::new(PickerKind::ColorScheme, names);
+        picker.selected = picker.items.iter().position(|name| *name == self.settings.color_scheme).unwrap_or(0);
+        self.open_picker(picker, window, cx);
+    }
+
+    /// Draws the editor in the scheme the picker is on, until it closes.
+    pub fn preview_color_scheme(&mut self) {
+        let Some(picker) = &self.picker else { return };
+        self.color_scheme_preview = picker.selected_item().map(|item| picker.items[item].clone());
+    }
+
+    /// Makes `name` the user's color scheme.
+    pub fn set_color_scheme(&mut self, name: String, cx: &mut Context<Self>) {
+        self.set_user_setting("color_scheme", name.into(), cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_sublime_color_scheme, resolve_color, EditorColors};
+    use std::collections::HashMap;
+    use syntect::highlighting::{Color, FontStyle, ThemeSettings};
+
+    fn color(value: &str) -> Option<Color> {
+        let variables = HashMap::from([
+            ("blue".to_string(), "#0000ff".to_string()),
+            ("accent".to_string(), "var(blue)".to_string()),
+            ("loop".to_string(), "var(loop)".to_string()),
+        ]);
+        resolve_color(value, &variables, 0)
+    }
+
+    #[test]
+    fn colors_are_read_in_every_supported_form() {
+        let red = Some(Color { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(color("#f00"), red);
+        assert_eq!(color("#ff0000"), red);
+        assert_eq!(color("rgb(255, 0, 0)"), red);
+        assert_eq!(color("hsl(0, 100%, 50%)"), red);
+        assert_eq!(color("#ff000080"), Some(Color { r: 255, g: 0, b: 0, a: 128 }));
+        assert_eq!(color("#f008"), Some(Color { r: 255, g: 0, b: 0, a: 136 }));
+        assert_eq!(color("rgba(0, 128, 255, 0.5)"), Some(Color { r: 0, g: 128, b: 255, a: 128 }));
+        assert_eq!(color("hsla(120deg, 100%, 25%, 50%)"), Some(Color { r: 0, g: 128, b: 0, a: 128 }));
+        assert_eq!(color("var(accent)"), Some(Color { r: 0, g: 0, b: 255, a: 255 }));
+    }
+
+    #[test]
+    fn unsupported_colors_are_rejected() {
+        for value in ["#ff000", "#gg0000", "rgb(300, 0, 0)", "rgb(1, 2)", "color(var(blue) alpha(0.5))", "var(missing)", "var(loop)", "blue"] {
+            assert_eq!(color(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn sublime_color_schemes_become_themes() {
+        let (theme, errors) = parse_sublime_color_scheme(
+            r##"{
+                // Comments and trailing commas are fine, as in Sublime.
+                "name": "Test",
+                "variables": { "fg": "#eeeeee" },
+                "globals": {
+                    "foreground": "var(fg)",
+                    "background": "#101010",
+                    "find_highlight": "#ffaa00",
+                    "gutter": "color(#101010 blend(#fff 90%))",
+                },
+                "rules": [
+                    { "scope": "comment", "foreground": "#888", "font_style": "italic bold" },
+                    { "scope": "string, constant.numeric", "background": "#222" },
+                ],
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(theme.name.as_deref(), Some("Test"));
+        assert_eq!(theme.settings.foreground, Some(Color { r: 238, g: 238, b: 238, a: 255 }));
+        assert_eq!(theme.settings.gutter, None);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("blend"));
+        assert_eq!(theme.scopes.len(), 2);
+        assert_eq!(theme.scopes[0].style.font_style, Some(FontStyle::ITALIC | FontStyle::BOLD));
+        assert_eq!(theme.scopes[1].scope.selectors.len(), 2);
+
+        let colors = EditorColors::new(&theme.settings);
+        assert_eq!(colors.find_highlight.r, 1.0);
+        assert_eq!(colors.gutter, colors.background);
+        assert!(parse_sublime_color_scheme("{ \"rules\": 1 }").is_err());
+    }
+
+    #[test]
+    fn missing_globals_keep_the_old_colours() {
+        let colors = EditorColors::new(&ThemeSettings::default());
+        assert_eq!(colors.find_highlight, gpui::rgb(0xd18616));
+        assert_eq!(colors.highlight, gpui::rgba(0xffff0044));
+        assert_eq!(colors.selection, gpui::rgb(0x264f78));
+        assert_eq!(colors.selection_foreground, None);
+    }
+}
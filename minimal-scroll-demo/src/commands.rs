@@ -0,0 +1,154 @@
+use gpui::*;
+
+use crate::actions::*;
+use crate::app_state::ScrollDemo;
+use crate::fuzzy;
+use crate::picker::{Picker, PickerKind};
+
+/// Something the user can run: from a key binding, a menu, or the Command
+/// Palette, which all find it in `COMMANDS` by its id so they agree.
+pub struct Command {
+    /// Stable name, for keymap files.
+    pub id: &'static str,
+    /// What the Command Palette lists it as.
+    pub name: &'static str,
+    /// What menus show it as, when that isn't `name` without its category.
+    menu_label: Option<&'static str>,
+    action: fn() -> Box<dyn Action>,
+}
+
+impl Command {
+    pub fn action(&self) -> Box<dyn Action> {
+        (self.action)()
+    }
+
+    /// The label of the menu items running the command.
+    pub fn menu_label(&self) -> &'static str {
+        self.menu_label.unwrap_or_else(|| self.name.split_once(": ").map_or(self.name, |(_, label)| label))
+    }
+}
+
+macro_rules! command {
+    ($id:literal, $name:literal, $action:expr) => {
+        Command { id: $id, name: $name, menu_label: None, action: || Box::new($action) }
+    };
+    ($id:literal, $name:literal, $action:expr, menu: $label:expr) => {
+        Command { id: $id, name: $name, menu_label: Some($label), action: || Box::new($action) }
+    };
+}
+
+pub static COMMANDS: &[Command] = &[
+    command!("new_file", "File: New File", NewFile),
+    command!("open_file", "File: Open File", OpenFile, menu: "Open File..."),
+    command!("open_folder", "File: Open Folder", OpenFolder, menu: "Open Folder..."),
+    command!("save", "File: Save", Save),
+    command!("save_as", "File: Save As", SaveAs, menu: "Save As..."),
+    command!("save_all", "File: Save All", SaveAll),
+    command!("reload_from_disk", "File: Reload from Disk", ReloadFromDisk),
+    command!("reopen_with_encoding", "File: Reopen with Encoding", ReopenWithEncoding, menu: "Reopen with Encoding..."),
+    command!("save_with_encoding", "File: Save with Encoding", SaveWithEncoding, menu: "Save with Encoding..."),
+    command!("close_file", "File: Close", CloseFile, menu: "Close File"),
+    command!("quit", "File: Quit", Quit, menu: if cfg!(target_os = "macos") { "Quit" } else { "Exit" }),
+    command!("undo", "Edit: Undo", Undo),
+    command!("redo", "Edit: Redo", Redo),
+    command!("copy", "Edit: Copy", Copy),
+    command!("cut", "Edit: Cut", Cut),
+    command!("paste", "Edit: Paste", Paste),
+    command!("paste_from_history", "Edit: Paste from History", PasteFromHistory),
+    command!("select_all", "Selection: Select All", SelectAll),
+    command!("expand_selection_to_line", "Selection: Expand to Line", ExpandSelectionToLine, menu: "Expand Selection to Line"),
+    command!("single_selection", "Selection: Single Selection", SingleSelection),
+    command!("add_cursor_below", "Selection: Add Next Line", AddCursorBelow),
+    command!("add_cursor_above", "Selection: Add Previous Line", AddCursorAbove),
+    command!("select_next_occurrence", "Selection: Quick Add Next", SelectNextOccurrence),
+    command!("find", "Find: Find", FindAction, menu: "Find..."),
+    command!("find_next", "Find: Find Next", FindNext),
+    command!("find_previous", "Find: Find Previous", FindPrevious),
+    command!("find_in_files", "Find: Find in Files", FindInFilesAction, menu: "Find in Files..."),
+    command!("select_syntax", "View: Set Syntax", SelectSyntax, menu: "Syntax..."),
+    command!("line_endings_unix", "View: Line Endings: Unix (LF)", LineEndingsUnix),
+    command!("line_endings_windows", "View: Line Endings: Windows (CRLF)", LineEndingsWindows),
+    command!("reveal_in_side_bar", "View: Reveal Active File in Side Bar", RevealInSideBar),
+    command!("toggle_ignored_files", "View: Toggle Ignored Files", ToggleIgnoredFiles, menu: "Hide Ignored Files"),
+    command!("goto_anything", "Goto: Anything", GotoAnything, menu: "Goto Anything..."),
+    command!("goto_symbol", "Goto: Symbol", GotoSymbol, menu: "Goto Symbol..."),
+    command!("goto_line", "Goto: Line", GotoLine, menu: "Goto Line..."),
+    command!("command_palette", "Tools: Command Palette", CommandPalette, menu: "Command Palette..."),
+    command!("edit_settings", "Preferences: Settings", EditSettings),
+    command!("edit_syntax_settings", "Preferences: Settings – Syntax Specific", EditSyntaxSettings),
+    command!("edit_key_bindings", "Preferences: Key Bindings", EditKeyBindings),
+    command!("select_color_scheme", "UI: Select Color Scheme", SelectColorScheme, menu: "Color Scheme..."),
+    command!("open_project", "Project: Open Project", OpenProject, menu: "Open Project..."),
+    command!("recent_projects", "Project: Recent Projects", RecentProjects, menu: "Recent Projects..."),
+    command!("save_project_as", "Project: Save As", SaveProjectAs, menu: "Save Project As..."),
+    command!("add_folder_to_project", "Project: Add Folder", AddFolderToProject, menu: "Add Folder to Project..."),
+    command!("remove_folder_from_project", "Project: Remove Folder", RemoveFolderFromProject, menu: "Remove Folder from Project"),
+    command!("explorer_new_file", "Side Bar: New File", ExplorerNewFile, menu: "New File..."),
+    command!("explorer_new_folder", "Side Bar: New Folder", ExplorerNewFolder, menu: "New Folder..."),
+    command!("explorer_rename", "Side Bar: Rename", ExplorerRename, menu: "Rename..."),
+    command!("explorer_duplicate", "Side Bar: Duplicate", ExplorerDuplicate, menu: "Duplicate..."),
+    command!("explorer_delete", "Side Bar: Delete", ExplorerDelete),
+    command!("explorer_copy_path", "Side Bar: Copy Path", ExplorerCopyPath),
+    command!("explorer_reveal", "Side Bar: Reveal", ExplorerReveal),
+];
+
+pub fn command(id: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.id == id)
+}
+
+/// The command dispatching `action`.
+pub fn for_action(action: &dyn Action) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.action().partial_eq(action))
+}
+
+/// The palette's matches: everything in order until something is typed, then
+/// fuzzily ranked.
+pub fn palette_matches(query: &str, names: &[String]) -> Vec<usize> {
+    if query.trim().is_empty() {
+        return (0..names.len()).collect();
+    }
+    fuzzy::rank(query, names.iter().map(String::as_str), 0)
+}
+
+impl ScrollDemo {
+    /// Lists every command with its shortcut, to be filtered and run.
+    pub fn show_command_palette(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let mut commands: Vec<&Command> = COMMANDS.iter().collect();
+        commands.sort_by_key(|command| command.name);
+        let mut picker = Picker::new(PickerKind::CommandPalette, commands.iter().map(|command| command.name.to_string()).collect());
+        picker.hints = commands.iter().map(|command| self.keymap.shortcut(command.id).unwrap_or_default()).collect();
+        self.open_picker(picker, window, cx);
+    }
+
+    /// Runs the command the palette lists as `name`, dispatching its action
+    /// from the editor as if its keys had been pressed there.
+    pub fn run_command(&mut self, name: &str, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(command) = COMMANDS.iter().find(|command| command.name == name) {
+            window.dispatch_action(command.action(), cx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{command, palette_matches, COMMANDS};
+    use std::collections::HashSet;
+
+    #[test]
+    fn ids_and_names_are_unique() {
+        let ids: HashSet<&str> = COMMANDS.iter().map(|command| command.id).collect();
+        let names: HashSet<&str> = COMMANDS.iter().map(|command| command.name).collect();
+        assert_eq!(ids.len(), COMMANDS.len());
+        assert_eq!(names.len(), COMMANDS.len());
+        assert_eq!(command("save").map(|command| command.name), Some("File: Save"));
+        assert!(command("missing").is_none());
+    }
+
+    #[test]
+    fn palette_ranks_only_once_something_is_typed() {
+        let names: Vec<String> = ["File: Save", "File: Save As", "Goto: Line"].map(String::from).to_vec();
+        assert_eq!(palette_matches("", &names), [0, 1, 2]);
+        assert_eq!(palette_matches("gl", &names), [2]);
+        assert_eq!(palette_matches("save", &names)[0], 0);
+    }
+}
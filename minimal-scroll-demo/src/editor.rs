@@ -0,0 +1,306 @@
+use gpui::*;
+
+use crate::app_state::ScrollDemo;
+use crate::buffer::Buffer;
+use crate::clipboard::ClipboardEntry;
+use crate::picker::{Picker, PickerKind};
+use crate::selection::{normalize, Selection};
+use crate::tab::TabId;
+
+/// The space around the text inside the editor's scroll area.
+pub const TEXT_PADDING: f32 = 16.0;
+
+/// How far down the editor scrolls for `row` to sit in the middle of a
+/// viewport `viewport` pixels tall, with lines `line_height` pixels tall.
+/// Past the end, layout clamps it.
+pub fn row_scroll_offset(row: usize, viewport: f32, line_height: f32) -> f32 {
+    let top = TEXT_PADDING + row as f32 * line_height;
+    (top + line_height / 2.0 - viewport / 2.0).max(0.0)
+}
+
+/// Where a tab's caret and scroll position were when another tab replaced it,
+/// to put back when it is shown again.
+#[derive(Clone, Debug)]
+pub struct TabView {
+    pub selections: Vec<Selection>,
+    pub scroll: Point<Pixels>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    LineStart,
+    LineEnd,
+}
+
+impl ScrollDemo {
+    pub fn active_tab(&self) -> Option<TabId> {
+        self.active_tab_index.and_then(|idx| self.open_tabs.get(idx)).cloned()
+    }
+
+    pub fn active_buffer(&self) -> Option<&Buffer> {
+        self.active_tab_index
+            .and_then(|idx| self.open_tabs.get(idx))
+            .and_then(|tab| self.tab_contents.get(tab))
+    }
+
+    /// Row and display column of the caret of the last selection.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        match (self.active_buffer(), self.selections.last()) {
+            (Some(buffer), Some(selection)) => {
+                let (row, col) = buffer.offset_to_point(selection.head);
+                (row, buffer.display_column(row, col, self.active_settings().tab_size))
+            }
+            _ => (0, 0),
+        }
+    }
+
+    pub fn set_cursor(&mut self, offset: usize) {
+        self.selections = vec![Selection::cursor(offset)];
+    }
+
+    /// Shows the tab at `index`, putting back the selections and scroll
+    /// position it had when it was last shown and keeping those of the tab it
+    /// replaces.
+    pub fn activate_tab(&mut self, index: Option<usize>) {
+        if let Some(tab) = self.active_tab() {
+            let view = TabView { selections: std::mem::take(&mut self.selections), scroll: self.right_handle.offset() };
+            self.tab_views.insert(tab, view);
+        }
+        self.active_tab_index = index;
+        let view = self.active_tab().and_then(|tab| self.tab_views.remove(&tab));
+        self.selections = view.as_ref().map_or_else(|| vec![Selection::cursor(0)], |view| view.selections.clone());
+        self.clamp_selections();
+        self.update_syntax();
+        match view {
+            Some(view) => self.right_handle.set_offset(view.scroll),
+            None => self.scroll_to_row(0),
+        }
+    }
+
+    /// Moves the selections back onto the active buffer, for when its text
+    /// changed underneath them.
+    pub fn clamp_selections(&mut self) {
+        let Some(buffer) = self.active_tab_index.and_then(|idx| self.open_tabs.get(idx)).and_then(|tab| self.tab_contents.get(tab)) else {
+            return;
+        };
+        for selection in &mut self.selections {
+            selection.anchor = buffer.clamp_offset(selection.anchor);
+            selection.head = buffer.clamp_offset(selection.head);
+        }
+    }
+
+    /// Scrolls the editor to put `row` in the middle of the view.
+    pub fn scroll_to_row(&mut self, row: usize) {
+        let viewport = self.right_handle.bounds().size.height;
+        let offset = row_scroll_offset(row, viewport.into(), self.active_settings().line_height);
+        self.right_handle.set_offset(point(px(0.0), px(-offset)));
+    }
+
+    /// Runs an edit against the active buffer and every selection, then
+    /// replaces the selections with the ones it returns.
+    fn edit_active(&mut self, cx: &mut Context<Self>, edit: impl FnOnce(&mut Buffer, &[Selection]) -> Vec<Selection>) {
+        let Some(tab) = self.active_tab() else { return };
+        let Some(buffer) = self.tab_contents.get_mut(&tab) else { return };
+        if buffer.read_only {
+            return;
+        }
+        self.selections = normalize(edit(buffer, &self.selections));
+        self.sync_dirty(&tab);
+        self.refresh_find_matches();
+        cx.notify();
+    }
+
+    pub fn insert_text(&mut self, text: &str, cx: &mut Context<Self>) {
+        self.edit_active(cx, |buffer, selections| buffer.insert(selections, text));
+    }
+
+    pub fn backspace(&mut self, cx: &mut Context<Self>) {
+        self.edit_active(cx, |buffer, selections| buffer.backspace(selections));
+    }
+
+    pub fn undo(&mut self, cx: &mut Context<Self>) {
+        self.edit_active(cx, |buffer, selections| buffer.undo().unwrap_or_else(|| selections.to_vec()));
+    }
+
+    pub fn redo(&mut self, cx: &mut Context<Self>) {
+        self.edit_active(cx, |buffer, selections| buffer.redo().unwrap_or_else(|| selections.to_vec()));
+    }
+
+    pub fn copy(&mut self, cx: &mut Context<Self>) {
+        let Some(buffer) = self.active_buffer() else { return };
+        let entry = buffer.copy(&self.selections);
+        self.put_on_clipboard(entry, cx);
+    }
+
+    pub fn cut(&mut self, cx: &mut Context<Self>) {
+        let mut cut = None;
+        self.edit_active(cx, |buffer, selections| {
+            let (entry, cursors) = buffer.cut(selections);
+            cut = Some(entry);
+            cursors
+        });
+        if let Some(entry) = cut {
+            self.put_on_clipboard(entry, cx);
+        }
+    }
+
+    /// Pastes the system clipboard, using what was recorded when the text was
+    /// copied here to split it across selections or paste it as whole lines.
+    pub fn paste(&mut self, cx: &mut Context<Self>) {
+        let Some(text) = cx.read_from_clipboard().and_then(|item| item.text()) else { return };
+        let entry = self.clipboard_history.resolve(text);
+        self.edit_active(cx, |buffer, selections| buffer.paste(selections, &entry));
+    }
+
+    pub fn paste_from_history(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let items = self.clipboard_history.entries().iter().map(|entry| history_label(&entry.text)).collect();
+        self.open_picker(Picker::new(PickerKind::ClipboardHistory, items), window, cx);
+    }
+
+    /// Puts a history entry back on top of the clipboard and pastes it.
+    pub fn paste_history_entry(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(entry) = self.clipboard_history.entries().get(index).cloned() else { return };
+        self.put_on_clipboard(entry.clone(), cx);
+        self.edit_active(cx, |buffer, selections| buffer.paste(selections, &entry));
+    }
+
+    fn put_on_clipboard(&mut self, entry: ClipboardEntry, cx: &mut Context<Self>) {
+        cx.write_to_clipboard(ClipboardItem::new_string(entry.text.clone()));
+        self.clipboard_history.push(entry);
+    }
+
+    /// Moves every caret; with `extend` the anchors stay put and the
+    /// selections grow instead.
+    pub fn move_selections(&mut self, motion: Motion, extend: bool, cx: &mut Context<Self>) {
+        let tab_size = self.active_settings().tab_size;
+        let Some(tab) = self.active_tab() else { return };
+        let Some(buffer) = self.tab_contents.get_mut(&tab) else { return };
+        let moved = self
+            .selections
+            .iter()
+            .map(|s| {
+                let target = match motion {
+                    Motion::Up | Motion::Down => {
+                        let (offset, goal) = buffer.vertical_move(s.head, motion == Motion::Down, s.goal, tab_size);
+                        return s.moved_to(offset, extend).with_goal(goal);
+                    }
+                    Motion::Left if !extend && !s.is_empty() => s.start(),
+                    Motion::Right if !extend && !s.is_empty() => s.end(),
+                    Motion::Left => buffer.prev_grapheme(s.head),
+                    Motion::Right => buffer.next_grapheme(s.head),
+                    Motion::LineStart => buffer.line_range(buffer.offset_to_point(s.head).0).start,
+                    Motion::LineEnd => {
+                        let row = buffer.offset_to_point(s.head).0;
+                        buffer.point_to_offset(row, buffer.line_len(row))
+                    }
+                };
+                s.moved_to(target, extend)
+            })
+            .collect();
+        buffer.break_undo_group();
+        self.selections = normalize(moved);
+        cx.notify();
+    }
+
+    /// Adds a caret on the line above the first selection or below the last
+    /// one, at the same display column.
+    pub fn add_cursor(&mut self, above: bool, cx: &mut Context<Self>) {
+        let tab_size = self.active_settings().tab_size;
+        let Some(buffer) = self.active_buffer() else { return };
+        let edge = if above { self.selections.first() } else { self.selections.last() };
+        let Some(edge) = edge.copied() else { return };
+        let row = buffer.offset_to_point(edge.head).0;
+        if (above && row == 0) || (!above && row + 1 >= buffer.line_count()) {
+            return;
+        }
+        let (offset, goal) = buffer.vertical_move(edge.head, !above, edge.goal, tab_size);
+        self.selections.push(Selection::cursor(offset).with_goal(goal));
+        self.selections = normalize(std::mem::take(&mut self.selections));
+        cx.notify();
+    }
+
+    /// Sublime's Ctrl+D: a lone caret selects the word under it; otherwise the
+    /// next occurrence of the last selection's text is added.
+    pub fn select_next_occurrence(&mut self, cx: &mut Context<Self>) {
+        let Some(buffer) = self.active_buffer() else { return };
+        let Some(last) = self.selections.last().copied() else { return };
+        if last.is_empty() {
+            if self.selections.len() == 1 {
+                let word = buffer.word_range_at(last.head);
+                if !word.is_empty() {
+                    self.selections = vec![Selection::new(word.start, word.end)];
+                }
+            }
+        } else {
+            let query = buffer.slice(last.range());
+            if let Some(start) = buffer.find_next(&query, last.end()) {
+                let next = Selection::new(start, start + query.len());
+                if !self.selections.iter().any(|s| s.range() == next.range()) {
+                    self.selections.push(next);
+                    self.selections = normalize(std::mem::take(&mut self.selections));
+                }
+            }
+        }
+        cx.notify();
+    }
+
+    /// Grows each selection to whole lines. Repeating it takes in the next line.
+    pub fn expand_selection_to_line(&mut self, cx: &mut Context<Self>) {
+        let Some(buffer) = self.active_buffer() else { return };
+        let expanded = self
+            .selections
+            .iter()
+            .map(|s| {
+                let start_row = buffer.offset_to_point(s.start()).0;
+                let end_row = buffer.offset_to_point(s.end()).0;
+                Selection::new(buffer.line_range(start_row).start, buffer.line_range(end_row).end)
+            })
+            .collect();
+        self.selections = normalize(expanded);
+        cx.notify();
+    }
+
+    pub fn select_all(&mut self, cx: &mut Context<Self>) {
+        let Some(buffer) = self.active_buffer() else { return };
+        self.selections = vec![Selection::new(0, buffer.len_bytes())];
+        cx.notify();
+    }
+
+    /// Drops every selection but the first, collapsing it to its caret.
+    pub fn single_selection(&mut self, cx: &mut Context<Self>) {
+        if let Some(first) = self.selections.first().copied() {
+            self.set_cursor(first.head);
+            cx.notify();
+        }
+    }
+}
+
+/// One line of a clipboard entry for the history picker.
+fn history_label(text: &str) -> String {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines.next().unwrap_or_default();
+    match lines.count() {
+        0 => first.to_string(),
+        more => format!("{first} (+{more} lines)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{row_scroll_offset, TEXT_PADDING};
+
+    #[test]
+    fn rows_scroll_to_the_middle_of_the_view() {
+        assert_eq!(row_scroll_offset(0, 400.0, 20.0), 0.0);
+        assert_eq!(row_scroll_offset(3, 400.0, 20.0), 0.0);
+        let row = 100;
+        for line_height in [20.0, 31.0] {
+            let offset = row_scroll_offset(row, 400.0, line_height);
+            assert_eq!(TEXT_PADDING + row as f32 * line_height - offset + line_height / 2.0, 200.0);
+        }
+    }
+}
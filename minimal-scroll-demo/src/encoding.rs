@@ -0,0 +1,261 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{
+    Encoding, BIG5, EUC_JP, EUC_KR, GB18030, GBK, ISO_8859_2, ISO_8859_7, KOI8_R, SHIFT_JIS, UTF_16BE,
+    UTF_16LE, UTF_8, WINDOWS_1250, WINDOWS_1251, WINDOWS_1252,
+};
+
+/// How much of a file is searched for NUL bytes when deciding it is binary.
+const BINARY_SNIFF_LEN: usize = 8192;
+/// The hex view stops after this many bytes; beyond that it is too slow to lay out.
+const HEX_VIEW_LIMIT: usize = 1 << 20;
+
+/// The encoding a file is read and written in. `bom` records whether it began
+/// with a byte order mark, so saving can put one back.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextEncoding {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self::new(UTF_8, false)
+    }
+}
+
+impl TextEncoding {
+    pub const fn new(encoding: &'static Encoding, bom: bool) -> Self {
+        Self { encoding, bom }
+    }
+
+    pub fn name(&self) -> String {
+        let name = match self.encoding.name() {
+            "UTF-16LE" => "UTF-16 LE",
+            "UTF-16BE" => "UTF-16 BE",
+            "windows-1252" => "Western (Windows 1252)",
+            "windows-1251" => "Cyrillic (Windows 1251)",
+            "windows-1250" => "Central European (Windows 1250)",
+            other => other,
+        };
+        if self.bom {
+            format!("{name} with BOM")
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Decodes `bytes`, skipping a leading BOM that belongs to this encoding.
+    /// Malformed sequences become U+FFFD.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let bytes = match Encoding::for_bom(bytes) {
+            Some((encoding, len)) if encoding == self.encoding => &bytes[len..],
+            _ => bytes,
+        };
+        self.encoding.decode_without_bom_handling(bytes).0.into_owned()
+    }
+
+    /// Encodes `text`, failing when it has characters this encoding can't hold
+    /// rather than writing substitutes.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::with_capacity(text.len() + 3);
+        if self.bom {
+            bytes.extend_from_slice(bom(self.encoding));
+        }
+        if self.encoding == UTF_16LE {
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        } else if self.encoding == UTF_16BE {
+            bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        } else {
+            let (encoded, _, had_errors) = self.encoding.encode(text);
+            if had_errors {
+                return Err(format!("it contains characters that {} can't represent", self.name()));
+            }
+            bytes.extend_from_slice(&encoded);
+        }
+        Ok(bytes)
+    }
+}
+
+fn bom(encoding: &'static Encoding) -> &'static [u8] {
+    if encoding == UTF_8 {
+        b"\xEF\xBB\xBF"
+    } else if encoding == UTF_16LE {
+        b"\xFF\xFE"
+    } else if encoding == UTF_16BE {
+        b"\xFE\xFF"
+    } else {
+        b""
+    }
+}
+
+/// The encodings offered by Reopen with Encoding and Save with Encoding.
+pub fn choices() -> Vec<TextEncoding> {
+    vec![
+        TextEncoding::new(UTF_8, false),
+        TextEncoding::new(UTF_8, true),
+        TextEncoding::new(UTF_16LE, true),
+        TextEncoding::new(UTF_16BE, true),
+        TextEncoding::new(WINDOWS_1252, false),
+        TextEncoding::new(WINDOWS_1250, false),
+        TextEncoding::new(WINDOWS_1251, false),
+        TextEncoding::new(ISO_8859_2, false),
+        TextEncoding::new(ISO_8859_7, false),
+        TextEncoding::new(KOI8_R, false),
+        TextEncoding::new(SHIFT_JIS, false),
+        TextEncoding::new(EUC_JP, false),
+        TextEncoding::new(GBK, false),
+        TextEncoding::new(GB18030, false),
+        TextEncoding::new(BIG5, false),
+        TextEncoding::new(EUC_KR, false),
+    ]
+}
+
+pub enum Decoded {
+    Text(String, TextEncoding),
+    Binary,
+}
+
+/// Works out how to show a file's bytes: a BOM decides the encoding outright;
+/// otherwise NUL bytes near the start mean UTF-16 when they fall in every
+/// other byte and binary when they don't, valid UTF-8 is taken as is, and
+/// anything else goes by chardetng's guess.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        let encoding = TextEncoding::new(encoding, true);
+        return Decoded::Text(encoding.decode(bytes), encoding);
+    }
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+    if let Some(encoding) = utf16_without_bom(bytes, sample) {
+        let encoding = TextEncoding::new(encoding, false);
+        return Decoded::Text(encoding.decode(bytes), encoding);
+    }
+    if sample.contains(&0) {
+        return Decoded::Binary;
+    }
+    let encoding = if std::str::from_utf8(bytes).is_ok() {
+        UTF_8
+    } else {
+        let mut detector = EncodingDetector::new();
+        detector.feed(bytes, true);
+        detector.guess(None, true)
+    };
+    let encoding = TextEncoding::new(encoding, false);
+    Decoded::Text(encoding.decode(bytes), encoding)
+}
+
+/// UTF-16 without a BOM, told apart from binary by where the NUL bytes in
+/// `sample` fall: text that is mostly ASCII has one in every other byte, at
+/// odd offsets for little endian and even ones for big endian, and none on
+/// the other side. The whole of `bytes` must then decode cleanly.
+fn utf16_without_bom(bytes: &[u8], sample: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let nuls_at = |parity: usize| sample.iter().skip(parity).step_by(2).filter(|&&byte| byte == 0).count();
+    let (even, odd) = (nuls_at(0), nuls_at(1));
+    let units = sample.len() / 2;
+    let encoding = if odd * 3 >= units && even == 0 {
+        UTF_16LE
+    } else if even * 3 >= units && odd == 0 {
+        UTF_16BE
+    } else {
+        return None;
+    };
+    encoding.decode_without_bom_handling_and_without_replacement(bytes).map(|_| encoding)
+}
+
+/// A hex dump of `bytes`: an offset, sixteen bytes in hex and their printable
+/// ASCII on each line.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (row, chunk) in bytes[..bytes.len().min(HEX_VIEW_LIMIT)].chunks(16).enumerate() {
+        out.push_str(&format!("{:08x}  ", row * 16));
+        for i in 0..16 {
+            match chunk.get(i) {
+                Some(byte) => out.push_str(&format!("{byte:02x} ")),
+                None => out.push_str("   "),
+            }
+            if i == 7 {
+                out.push(' ');
+            }
+        }
+        out.push_str(" |");
+        out.extend(chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }));
+        out.push_str("|\n");
+    }
+    if bytes.len() > HEX_VIEW_LIMIT {
+        out.push_str(&format!("... {} more bytes not shown\n", bytes.len() - HEX_VIEW_LIMIT));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(bytes: &[u8]) -> (String, TextEncoding) {
+        match decode(bytes) {
+            Decoded::Text(text, encoding) => (text, encoding),
+            Decoded::Binary => panic!("decoded as binary"),
+        }
+    }
+
+    #[test]
+    fn bom_decides_the_encoding() {
+        assert_eq!(decoded(b"\xEF\xBB\xBFhi"), ("hi".into(), TextEncoding::new(UTF_8, true)));
+        assert_eq!(decoded(b"\xFF\xFEh\0i\0"), ("hi".into(), TextEncoding::new(UTF_16LE, true)));
+        assert_eq!(decoded(b"\xFE\xFF\0h\0i"), ("hi".into(), TextEncoding::new(UTF_16BE, true)));
+    }
+
+    #[test]
+    fn legacy_encodings_are_guessed() {
+        assert_eq!(decoded("naïve café".as_bytes()).1, TextEncoding::default());
+        let latin1 = b"Der Stra\xDFenverkehr ist heute sehr ruhig, sch\xF6n und gr\xFCn.";
+        let (text, encoding) = decoded(latin1);
+        assert_eq!(encoding.encoding, WINDOWS_1252);
+        assert!(text.contains("Straßenverkehr") && text.contains("schön"));
+        let sjis = SHIFT_JIS.encode("これは日本語のテキストです。文字コードの判定を確認します。").0;
+        assert_eq!(decoded(&sjis).1.encoding, SHIFT_JIS);
+    }
+
+    #[test]
+    fn nul_bytes_mean_binary() {
+        assert!(matches!(decode(b"\x7FELF\x02\x01\x01\0\0\0"), Decoded::Binary));
+        assert!(matches!(decode(b"\0\0\0\x01\0\0\0\x02"), Decoded::Binary));
+    }
+
+    #[test]
+    fn utf16_without_a_bom_is_recognised_by_its_nul_bytes() {
+        let text = "fn main() {}\n// café\n";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decoded(&le), (text.into(), TextEncoding::new(UTF_16LE, false)));
+        assert_eq!(decoded(&be), (text.into(), TextEncoding::new(UTF_16BE, false)));
+        assert_eq!(TextEncoding::new(UTF_16LE, false).encode(text).unwrap(), le);
+    }
+
+    #[test]
+    fn encoding_round_trips_and_keeps_the_bom() {
+        for encoding in choices() {
+            let text = "plain ascii\n";
+            assert_eq!(decoded(&encoding.encode(text).unwrap()).0, text, "{}", encoding.name());
+        }
+        let utf16 = TextEncoding::new(UTF_16LE, true);
+        assert_eq!(utf16.encode("é").unwrap(), b"\xFF\xFE\xE9\x00");
+    }
+
+    #[test]
+    fn unrepresentable_text_is_refused() {
+        assert!(TextEncoding::new(WINDOWS_1252, false).encode("日本").is_err());
+    }
+
+    #[test]
+    fn hex_dump_shows_offsets_and_ascii() {
+        let dump = hex_dump(b"Hello\0world, hex view!");
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("00000000  48 65 6c 6c 6f 00 77 6f  72 6c 64 2c"));
+        assert!(lines[0].ends_with("|Hello.world, hex|"));
+        assert!(lines[1].starts_with("00000010  "));
+    }
+}
@@ -0,0 +1,249 @@
+use gpui::*;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::app_state::ScrollDemo;
+use crate::project::Project;
+use crate::project_tree::ProjectTree;
+
+/// The height of every explorer row, fixed so a row's position follows from
+/// its index.
+pub const ROW_HEIGHT: f32 = 22.0;
+/// The padding above the first row.
+const TOP_PADDING: f32 = 8.0;
+/// How long type-ahead waits for the next key before starting a new name.
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The name typed so far while the explorer has focus.
+#[derive(Default)]
+pub struct TypeAhead {
+    query: String,
+    last_key: Option<Instant>,
+}
+
+impl TypeAhead {
+    /// Adds typed text to the query, starting a new one if the last key was
+    /// pressed too long ago, and returns it.
+    pub fn push(&mut self, text: &str, now: Instant) -> &str {
+        if self.last_key.is_none_or(|last| now.duration_since(last) > TYPE_AHEAD_TIMEOUT) {
+            self.query.clear();
+        }
+        self.query.push_str(text);
+        self.last_key = Some(now);
+        &self.query
+    }
+}
+
+/// The row type-ahead jumps to: the first, going down from `current` and
+/// wrapping round, whose name starts with `query` ignoring case. A fresh
+/// one-letter query starts below `current`, so pressing the same letter again
+/// steps through the names starting with it.
+pub fn type_ahead_match(rows: &[(PathBuf, bool)], current: Option<usize>, query: &str) -> Option<usize> {
+    let query = query.to_lowercase();
+    let from = match current {
+        Some(current) if query.chars().count() == 1 => current + 1,
+        Some(current) => current,
+        None => 0,
+    };
+    (0..rows.len()).map(|i| (from + i) % rows.len()).find(|&i| {
+        rows[i].0.file_name().is_some_and(|name| name.to_string_lossy().to_lowercase().starts_with(&query))
+    })
+}
+
+/// The explorer's rows, each project folder's after the one before.
+fn explorer_rows(project: &Project, tree: &ProjectTree, expanded: &HashSet<PathBuf>) -> Vec<(PathBuf, bool)> {
+    project.roots().flat_map(|root| tree.visible_rows(root, expanded)).collect()
+}
+
+/// The row Up, Down, Home or End moves the selection to from `current` among
+/// `count` rows; `None` for other keys or when there are no rows.
+fn moved_row(key: &str, count: usize, current: Option<usize>) -> Option<usize> {
+    let last = count.checked_sub(1)?;
+    match key {
+        "up" => Some(current.map_or(0, |i| i.saturating_sub(1))),
+        "down" => Some(current.map_or(0, |i| (i + 1).min(last))),
+        "home" => Some(0),
+        "end" => Some(last),
+        _ => None,
+    }
+}
+
+impl ScrollDemo {
+    fn explorer_rows(&self) -> Vec<(PathBuf, bool)> {
+        explorer_rows(&self.project, &self.project_tree, &self.expanded_dirs)
+    }
+
+    /// Keyboard handling while the explorer has focus: arrows move the
+    /// selection, Left and Right collapse and expand, Enter opens, Escape
+    /// returns to the editor and anything printable jumps to a name.
+    pub fn explorer_key_down(&mut self, event: &KeyDownEvent, window: &mut Window, cx: &mut Context<Self>) {
+        let modifiers = &event.keystroke.modifiers;
+        if modifiers.platform || modifiers.control || modifiers.alt {
+            return;
+        }
+        let key = event.keystroke.key.as_str();
+        if key == "escape" {
+            window.focus(&self.focus_handle);
+            cx.notify();
+            return;
+        }
+        let rows = self.explorer_rows();
+        if rows.is_empty() {
+            return;
+        }
+        let current = self.explorer_selected.as_ref().and_then(|selected| rows.iter().position(|(path, _)| path == selected));
+        if let Some(i) = moved_row(key, rows.len(), current) {
+            return self.select_explorer_row(&rows, i, cx);
+        }
+        match key {
+            "left" => {
+                let Some(i) = current else { return };
+                let (path, is_dir) = &rows[i];
+                if *is_dir && self.expanded_dirs.contains(path) {
+                    self.toggle_dir(path.clone(), cx);
+                } else if let Some(parent) = rows.iter().position(|(row, _)| Some(row.as_path()) == path.parent()) {
+                    self.select_explorer_row(&rows, parent, cx);
+                }
+            }
+            "right" => {
+                let Some(i) = current else { return };
+                let (path, is_dir) = &rows[i];
+                if !*is_dir {
+                    return;
+                }
+                if !self.expanded_dirs.contains(path) {
+                    self.toggle_dir(path.clone(), cx);
+                } else if rows.get(i + 1).is_some_and(|(child, _)| child.parent() == Some(path.as_path())) {
+                    self.select_explorer_row(&rows, i + 1, cx);
+                }
+            }
+            "enter" => {
+                let Some(i) = current else { return };
+                let (path, is_dir) = rows[i].clone();
+                if is_dir {
+                    self.toggle_dir(path, cx);
+                } else {
+                    self.open_file(path, cx);
+                    window.focus(&self.focus_handle);
+                }
+            }
+            _ => {
+                let Some(text) = &event.keystroke.key_char else { return };
+                let query = self.explorer_type_ahead.push(text, Instant::now());
+                if let Some(i) = type_ahead_match(&rows, current, query) {
+                    self.select_explorer_row(&rows, i, cx);
+                }
+            }
+        }
+    }
+
+    fn select_explorer_row(&mut self, rows: &[(PathBuf, bool)], index: usize, cx: &mut Context<Self>) {
+        self.explorer_selected = Some(rows[index].0.clone());
+        self.explorer_reveal_pending = false;
+        self.scroll_explorer_to(index);
+        cx.notify();
+    }
+
+    /// Scrolls the explorer the least amount that brings row `index` into view.
+    fn scroll_explorer_to(&self, index: usize) {
+        let top = px(TOP_PADDING + index as f32 * ROW_HEIGHT);
+        let bottom = top + px(ROW_HEIGHT);
+        let height = self.left_handle.bounds().size.height;
+        let mut offset = self.left_handle.offset();
+        if top < -offset.y {
+            offset.y = -top;
+        } else if bottom > height - offset.y {
+            offset.y = height - bottom;
+        }
+        self.left_handle.set_offset(offset);
+    }
+
+    /// Reveal Active File in Side Bar: expands the folders above the active
+    /// file, selects it and scrolls to it once their listings are read.
+    pub fn reveal_in_side_bar(&mut self, cx: &mut Context<Self>) {
+        let Some(path) = self.active_tab().and_then(|tab| tab.path().cloned()) else { return };
+        let Some(root) = self.project.folder_for(&path).map(|folder| folder.path.clone()) else {
+            return self.notify_info(format!("{} isn't in any of the project's folders", path.display()), cx);
+        };
+        let Some(parent) = path.parent() else { return };
+        for dir in parent.ancestors().take_while(|dir| dir.starts_with(&root)) {
+            self.expanded_dirs.insert(dir.to_path_buf());
+            self.load_dir(dir.to_path_buf(), false, cx);
+        }
+        self.explorer_selected = Some(path);
+        self.explorer_reveal_pending = true;
+        self.continue_reveal();
+        cx.notify();
+    }
+
+    /// Finishes a reveal once the selected row has appeared in the explorer.
+    pub fn continue_reveal(&mut self) {
+        if !self.explorer_reveal_pending {
+            return;
+        }
+        let rows = self.explorer_rows();
+        if let Some(index) = self.explorer_selected.as_ref().and_then(|selected| rows.iter().position(|(path, _)| path == selected)) {
+            self.explorer_reveal_pending = false;
+            self.scroll_explorer_to(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explorer_rows, moved_row, type_ahead_match, TypeAhead, TYPE_AHEAD_TIMEOUT};
+    use crate::project::Project;
+    use crate::project_tree::ProjectTree;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    fn rows(names: &[&str]) -> Vec<(PathBuf, bool)> {
+        names.iter().map(|name| (PathBuf::from("/p").join(name), false)).collect()
+    }
+
+    #[test]
+    fn type_ahead_matches_name_prefixes_ignoring_case() {
+        let rows = rows(&["Cargo.toml", "build.rs", "src", "README.md", "scripts"]);
+        assert_eq!(type_ahead_match(&rows, None, "re"), Some(3));
+        assert_eq!(type_ahead_match(&rows, Some(2), "sc"), Some(4));
+        assert_eq!(type_ahead_match(&rows, Some(2), "sr"), Some(2));
+        assert_eq!(type_ahead_match(&rows, Some(0), "x"), None);
+    }
+
+    #[test]
+    fn keys_move_within_the_rows() {
+        assert_eq!(moved_row("down", 3, None), Some(0));
+        assert_eq!(moved_row("down", 3, Some(2)), Some(2));
+        assert_eq!(moved_row("up", 3, Some(0)), Some(0));
+        assert_eq!(moved_row("end", 3, Some(0)), Some(2));
+        assert_eq!(moved_row("left", 3, Some(1)), None);
+    }
+
+    #[test]
+    fn a_project_without_folders_has_nowhere_to_move() {
+        let rows = explorer_rows(&Project::default(), &ProjectTree::default(), &HashSet::new());
+        assert!(rows.is_empty());
+        for key in ["up", "down", "home", "end"] {
+            assert_eq!(moved_row(key, rows.len(), None), None);
+        }
+        assert_eq!(type_ahead_match(&rows, None, "s"), None);
+    }
+
+    #[test]
+    fn repeating_a_letter_steps_through_the_matches() {
+        let rows = rows(&["src", "build.rs", "scripts"]);
+        assert_eq!(type_ahead_match(&rows, Some(0), "s"), Some(2));
+        assert_eq!(type_ahead_match(&rows, Some(2), "s"), Some(0));
+    }
+
+    #[test]
+    fn type_ahead_starts_over_after_a_pause() {
+        let mut type_ahead = TypeAhead::default();
+        let start = Instant::now();
+        type_ahead.push("s", start);
+        assert_eq!(type_ahead.push("r", start + Duration::from_millis(200)), "sr");
+        assert_eq!(type_ahead.push("b", start + TYPE_AHEAD_TIMEOUT * 2), "b");
+    }
+}
@@ -0,0 +1,291 @@
+use gpui::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::app_state::ScrollDemo;
+use crate::notifications::describe_io_error;
+use crate::picker::{Picker, PickerKind};
+use crate::tab::TabId;
+
+/// An explorer entry being dragged onto a folder.
+#[derive(Clone)]
+pub struct DraggedEntry(pub PathBuf);
+
+impl Render for DraggedEntry {
+    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+        let name = self.0.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        div().px_2().py_1().bg(rgb(0x094771)).text_size(px(12.0)).text_color(rgb(0xffffff)).child(name)
+    }
+}
+
+/// Where `path` ends up when `from` is moved to `to`: itself if it is `from`,
+/// the same place under `to` if it is inside it, else `None`.
+pub fn moved_path(path: &Path, from: &Path, to: &Path) -> Option<PathBuf> {
+    let rest = path.strip_prefix(from).ok()?;
+    Some(if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) })
+}
+
+/// The name Duplicate suggests: `notes copy.txt` for `notes.txt`.
+pub fn copy_name(path: &Path) -> String {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    match path.extension() {
+        Some(ext) if !path.is_dir() => format!("{stem} copy.{}", ext.to_string_lossy()),
+        _ => format!("{} copy", path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or(stem)),
+    }
+}
+
+/// Checks a name typed into a prompt is a single path component.
+pub fn validate_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() || name == "." || name == ".." {
+        return Err("a name is needed".to_string());
+    }
+    if name.contains(['/', '\\']) {
+        return Err(format!("\"{name}\" can't contain a path separator"));
+    }
+    Ok(name)
+}
+
+/// Copies a file, or a directory and everything in it, to `to`, which must
+/// not exist yet.
+pub fn copy_recursively(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+    }
+    if !from.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+    for entry in WalkDir::new(from) {
+        let entry = entry.map_err(io::Error::other)?;
+        let target = to.join(entry.path().strip_prefix(from).map_err(io::Error::other)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Renames `from` to `to`, refusing to replace something already there.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+    }
+    fs::rename(from, to)
+}
+
+impl ScrollDemo {
+    /// The folder new entries go in: the selected folder itself, the folder
+    /// of a selected file, or the first project folder.
+    fn target_dir(&self) -> PathBuf {
+        match &self.explorer_selected {
+            Some(path) if path.is_dir() => path.clone(),
+            Some(path) => path.parent().map(PathBuf::from).unwrap_or_default(),
+            None => self.project.roots().next().cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Selects an explorer entry and opens the context menu at `position`.
+    pub fn open_explorer_menu(&mut self, path: PathBuf, position: Point<Pixels>, cx: &mut Context<Self>) {
+        self.explorer_selected = Some(path);
+        self.explorer_menu = Some(position);
+        cx.stop_propagation();
+        cx.notify();
+    }
+
+    pub fn prompt_new_entry(&mut self, kind: PickerKind, window: &mut Window, cx: &mut Context<Self>) {
+        let dir = self.target_dir();
+        self.open_picker(Picker::name_prompt(kind, dir, String::new()), window, cx);
+    }
+
+    pub fn prompt_rename(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(path) = self.explorer_selected.clone() else { return };
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        self.open_picker(Picker::name_prompt(PickerKind::Rename, path, name), window, cx);
+    }
+
+    pub fn prompt_duplicate(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(path) = self.explorer_selected.clone() else { return };
+        let name = copy_name(&path);
+        self.open_picker(Picker::name_prompt(PickerKind::Duplicate, path, name), window, cx);
+    }
+
+    /// Carries out a name prompt once a name is entered. `target` is the
+    /// folder for new entries and the entry itself for Rename and Duplicate.
+    pub fn confirm_name_prompt(&mut self, kind: PickerKind, target: PathBuf, name: &str, cx: &mut Context<Self>) {
+        let name = match validate_name(name) {
+            Ok(name) => name,
+            Err(message) => return self.notify_error(format!("Couldn't name the entry: {message}"), cx),
+        };
+        let sibling = |path: &Path| path.parent().unwrap_or(path).join(name);
+        let (result, path) = match kind {
+            PickerKind::NewFile => {
+                let path = target.join(name);
+                (fs::OpenOptions::new().write(true).create_new(true).open(&path).map(|_| ()), path)
+            }
+            PickerKind::NewFolder => {
+                let path = target.join(name);
+                (fs::create_dir(&path), path)
+            }
+            PickerKind::Rename => {
+                let path = sibling(&target);
+                (move_path(&target, &path), path)
+            }
+            PickerKind::Duplicate => {
+                let path = sibling(&target);
+                (copy_recursively(&target, &path), path)
+            }
+            _ => return,
+        };
+        if let Err(err) = result {
+            let verb = match kind {
+                PickerKind::Rename => "rename to",
+                PickerKind::Duplicate => "copy to",
+                _ => "create",
+            };
+            return self.notify_error(format!("Couldn't {verb} {}", describe_io_error(&err, &path)), cx);
+        }
+        match kind {
+            PickerKind::NewFile => self.open_file(path.clone(), cx),
+            PickerKind::NewFolder => {
+                self.expanded_dirs.insert(path.clone());
+            }
+            PickerKind::Rename => self.path_moved(&target, &path, cx),
+            _ => {}
+        }
+        self.refresh_parent(&path, cx);
+        if kind == PickerKind::Rename {
+            self.refresh_parent(&target, cx);
+        }
+        self.explorer_selected = Some(path);
+        cx.notify();
+    }
+
+    /// Moves a dragged entry into `dir`.
+    pub fn move_into(&mut self, from: PathBuf, dir: PathBuf, cx: &mut Context<Self>) {
+        let Some(name) = from.file_name() else { return };
+        let to = dir.join(name);
+        if to == from || dir.starts_with(&from) {
+            return;
+        }
+        if let Err(err) = move_path(&from, &to) {
+            return self.notify_error(format!("Couldn't move {}", describe_io_error(&err, &from)), cx);
+        }
+        self.path_moved(&from, &to, cx);
+        self.refresh_parent(&from, cx);
+        self.refresh_parent(&to, cx);
+        self.explorer_selected = Some(to);
+        cx.notify();
+    }
+
+    /// Points open tabs, expanded folders and project roots at or under `from`
+    /// to their new place under `to`, keeping their buffers and unsaved
+    /// changes.
+    pub fn path_moved(&mut self, from: &Path, to: &Path, cx: &mut Context<Self>) {
+        let moved: Vec<(TabId, PathBuf)> = self
+            .open_tabs
+            .iter()
+            .filter_map(|tab| Some((tab.clone(), moved_path(tab.path()?, from, to)?)))
+            .collect();
+        for (tab, new_path) in moved {
+            self.retarget_tab(&tab, TabId::File(new_path));
+        }
+        self.expanded_dirs = self
+            .expanded_dirs
+            .drain()
+            .map(|dir| moved_path(&dir, from, to).unwrap_or(dir))
+            .collect();
+        if self.project.folders_moved(from, to) {
+            self.write_project(cx);
+            self.load_expanded_dirs(cx);
+        }
+    }
+
+    /// Moves the selected entry to the trash. Clean tabs showing files in it
+    /// are closed; modified ones stay open, marked deleted, so nothing is
+    /// lost. Project folders have to be removed from the project first.
+    pub fn trash_selected(&mut self, cx: &mut Context<Self>) {
+        let Some(path) = self.explorer_selected.clone() else { return };
+        if self.project.roots().any(|root| root.starts_with(&path)) {
+            return self.notify_error(format!("{} is a project folder; remove it from the project first", path.display()), cx);
+        }
+        self.explorer_selected = None;
+        if let Err(err) = trash::delete(&path) {
+            return self.notify_error(format!("Couldn't move {} to the trash: {err}", path.display()), cx);
+        }
+        let trashed: Vec<TabId> = self.open_tabs.iter().filter(|tab| tab.path().is_some_and(|p| p.starts_with(&path))).cloned().collect();
+        for tab in trashed {
+            if self.dirty_tabs.contains(&tab) {
+                self.deleted_tabs.insert(tab);
+            } else {
+                self.close_tab(tab, cx);
+            }
+        }
+        self.expanded_dirs.retain(|dir| !dir.starts_with(&path));
+        self.refresh_parent(&path, cx);
+        cx.notify();
+    }
+
+    pub fn copy_selected_path(&mut self, cx: &mut Context<Self>) {
+        if let Some(path) = &self.explorer_selected {
+            cx.write_to_clipboard(ClipboardItem::new_string(path.to_string_lossy().to_string()));
+        }
+    }
+
+    pub fn reveal_selected(&mut self, cx: &mut Context<Self>) {
+        if let Some(path) = &self.explorer_selected {
+            cx.reveal_path(path);
+        }
+    }
+
+    /// Rereads the folder `path` is listed in, without waiting for the watcher.
+    fn refresh_parent(&mut self, path: &Path, cx: &mut Context<Self>) {
+        if let Some(parent) = path.parent() {
+            self.load_dir(parent.to_path_buf(), true, cx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{copy_name, copy_recursively, moved_path, validate_name};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn moves_carry_paths_inside_along() {
+        let (from, to) = (Path::new("/p/src"), Path::new("/p/lib"));
+        assert_eq!(moved_path(Path::new("/p/src"), from, to), Some(PathBuf::from("/p/lib")));
+        assert_eq!(moved_path(Path::new("/p/src/a/b.rs"), from, to), Some(PathBuf::from("/p/lib/a/b.rs")));
+        assert_eq!(moved_path(Path::new("/p/srcs/b.rs"), from, to), None);
+    }
+
+    #[test]
+    fn duplicate_names_keep_the_extension() {
+        assert_eq!(copy_name(Path::new("/nowhere/notes.txt")), "notes copy.txt");
+        assert_eq!(copy_name(Path::new("/nowhere/Makefile")), "Makefile copy");
+    }
+
+    #[test]
+    fn names_must_be_one_component() {
+        assert_eq!(validate_name("  a.rs "), Ok("a.rs"));
+        assert!(validate_name("").is_err());
+        assert!(validate_name("..").is_err());
+        assert!(validate_name("a/b").is_err());
+    }
+
+    #[test]
+    fn directories_copy_with_their_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("src");
+        fs::create_dir_all(from.join("nested")).unwrap();
+        fs::write(from.join("nested/a.txt"), "a").unwrap();
+        let to = dir.path().join("src copy");
+        copy_recursively(&from, &to).unwrap();
+        assert_eq!(fs::read_to_string(to.join("nested/a.txt")).unwrap(), "a");
+        assert!(copy_recursively(&from, &to).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+/// Points for every matched character.
+const MATCH: i64 = 1;
+/// Extra for a character that follows the previous match directly.
+const CONSECUTIVE: i64 = 8;
+/// Extra for a character that starts a word: the first character, one after
+/// a separator, or an upper-case letter after a lower-case one.
+const WORD_START: i64 = 10;
+/// Extra for a character in the file name rather than the directories.
+const FILE_NAME: i64 = 4;
+/// What a gap between two matches costs, plus `GAP` for each character in it.
+const GAP_START: i64 = 3;
+const GAP: i64 = 1;
+/// The most a recently used item can gain, for the most recent one.
+const RECENT: i64 = 12;
+
+/// How well `candidate` matches `query`, higher being better, or `None` when
+/// the query's characters don't all appear in it in order. Case is ignored.
+/// Of all the ways the characters can line up, the best scoring one counts.
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let name_start = chars.iter().rposition(|&c| c == '/' || c == '\\').map_or(0, |i| i + 1);
+    let bonus = |j: usize| {
+        let word_start = match j.checked_sub(1).map(|i| chars[i]) {
+            None => true,
+            Some(prev) => matches!(prev, '/' | '\\' | '_' | '-' | '.' | ' ') || (prev.is_lowercase() && chars[j].is_uppercase()),
+        };
+        MATCH + if word_start { WORD_START } else { 0 } + if j >= name_start { FILE_NAME } else { 0 }
+    };
+
+    // best[j]: the best score with the current query character matched at j.
+    let mut best: Vec<Option<i64>> = (0..chars.len()).map(|j| (lower[j] == query[0]).then(|| bonus(j))).collect();
+    for &wanted in &query[1..] {
+        let mut next = vec![None; chars.len()];
+        // The best of best[k] + k * GAP over k < j - 1, for gapped matches.
+        let mut gapped: Option<i64> = None;
+        for j in 1..chars.len() {
+            if j >= 2 {
+                if let Some(score) = best[j - 2] {
+                    let score = score + (j - 2) as i64 * GAP;
+                    gapped = Some(gapped.map_or(score, |g| g.max(score)));
+                }
+            }
+            if lower[j] != wanted {
+                continue;
+            }
+            let consecutive = best[j - 1].map(|score| score + CONSECUTIVE);
+            let skipped = gapped.map(|g| g - (j - 1) as i64 * GAP - GAP_START);
+            next[j] = consecutive.max(skipped).map(|score| score + bonus(j));
+        }
+        best = next;
+    }
+    best.into_iter().flatten().max()
+}
+
+/// The indices of `candidates` matching `query`, best first. The first
+/// `recent` candidates are taken to be recently used, most recent first, and
+/// get a bonus that shrinks with age; ties go to the shorter candidate, then
+/// to the earlier one.
+pub fn rank<'a>(query: &str, candidates: impl IntoIterator<Item = &'a str>, recent: usize) -> Vec<usize> {
+    let candidates: Vec<&str> = candidates.into_iter().collect();
+    let mut scored: Vec<(i64, usize)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, candidate)| {
+            let bonus = if i < recent { RECENT * (recent - i) as i64 / recent as i64 } else { 0 };
+            score(query, candidate).map(|score| (score + bonus, i))
+        })
+        .collect();
+    scored.sort_by(|(a, i), (b, j)| b.cmp(a).then(candidates[*i].len().cmp(&candidates[*j].len())).then(i.cmp(j)));
+    scored.into_iter().map(|(_, i)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rank, score};
+
+    #[test]
+    fn characters_must_appear_in_order() {
+        assert!(score("amn", "src/app_main.rs").is_some());
+        assert!(score("AMN", "src/app_main.rs").is_some());
+        assert!(score("nma", "src/app_main.rs").is_none());
+        assert_eq!(score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn word_starts_and_runs_beat_scattered_matches() {
+        assert!(score("fb", "src/foo_bar.rs") > score("fb", "src/fabric.rs"));
+        assert!(score("main", "src/main.rs") > score("main", "src/my_animation.rs"));
+        assert!(score("pt", "src/ProjectTree.rs") > score("pt", "src/output.rs"));
+    }
+
+    #[test]
+    fn file_names_beat_directories() {
+        assert!(score("ui", "src/ui.rs") > score("ui", "ui/mod.rs"));
+    }
+
+    #[test]
+    fn the_best_alignment_is_found() {
+        // A greedy match would take the `a` in `data` and miss `app_state`.
+        assert!(score("as", "data/app_state.rs") > score("as", "data/alias.rs"));
+    }
+
+    #[test]
+    fn ranking_orders_by_score_then_recency_then_length() {
+        let items = ["src/editor.rs", "src/app_state.rs", "README.md", "src/explorer.rs"];
+        assert_eq!(rank("state", items, 0), [1]);
+        assert_eq!(rank("ed", items, 0)[0], 0);
+        assert_eq!(rank("rs", items, 0), [0, 3, 1]);
+        assert_eq!(rank("rs", ["src/explorer.rs", "src/editor.rs"], 1), [0, 1]);
+    }
+}
@@ -0,0 +1,414 @@
+use gpui::*;
+use ignore::WalkBuilder;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+
+use crate::app_state::ScrollDemo;
+use crate::fuzzy;
+use crate::picker::{Picker, PickerKind};
+use crate::project::{Excludes, Project, ProjectFolder};
+use crate::selection::Selection;
+
+/// How many files or symbols Goto Anything lists at once.
+const MAX_RESULTS: usize = 50;
+/// How many recently opened files are remembered for ranking.
+const MAX_RECENT_FILES: usize = 50;
+
+/// Where Goto Anything goes once a file is picked, from what follows the file
+/// part of the query.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GotoTarget {
+    /// `:line` or `:line:col`, both counted from 1.
+    Line(usize, Option<usize>),
+    /// `@name`: the best matching symbol.
+    Symbol(String),
+    /// `#term`: the first occurrence of the text.
+    Term(String),
+}
+
+/// A Goto Anything query split into the fuzzy file part and the target. With
+/// no file part, the target applies to the active file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GotoQuery {
+    pub file: String,
+    pub target: Option<GotoTarget>,
+}
+
+impl GotoQuery {
+    pub fn parse(query: &str) -> Self {
+        let Some(at) = query.find([':', '@', '#']) else {
+            return Self { file: query.to_string(), target: None };
+        };
+        let (file, rest) = (query[..at].to_string(), &query[at + 1..]);
+        let target = match &query[at..=at] {
+            "@" => Some(GotoTarget::Symbol(rest.to_string())),
+            "#" => Some(GotoTarget::Term(rest.to_string())).filter(|_| !rest.is_empty()),
+            _ => {
+                let mut numbers = rest.splitn(2, ':').map(|n| n.trim().parse::<usize>().ok());
+                match (numbers.next().flatten(), numbers.next()) {
+                    (Some(line), None) => Some(GotoTarget::Line(line, None)),
+                    (Some(line), Some(Some(col))) => Some(GotoTarget::Line(line, Some(col))),
+                    _ => None,
+                }
+            }
+        };
+        Self { file, target }
+    }
+
+    /// Whether the query lists the active file's symbols rather than files.
+    pub fn lists_symbols(&self) -> bool {
+        self.file.is_empty() && matches!(self.target, Some(GotoTarget::Symbol(_)))
+    }
+}
+
+/// A definition the syntax marks with an `entity.name` scope, such as a
+/// function, type or heading. `column` is a byte offset into the line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The symbols defined in `text`, in order.
+pub fn symbols(text: &str, syntax: &SyntaxReference, syntax_set: &SyntaxSet) -> Vec<Symbol> {
+    let entity_name = Scope::new("entity.name").expect("valid scope");
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut symbols = Vec::new();
+    let is_name = |stack: &ScopeStack| stack.as_slice().iter().any(|scope| entity_name.is_prefix_of(*scope));
+    for (line_index, line) in text.split_inclusive('\n').enumerate() {
+        let Ok(ops) = state.parse_line(line, syntax_set) else { break };
+        // The stretches of the line between scope changes, and whether each
+        // is inside a name.
+        let mut spans = Vec::new();
+        let mut from = 0;
+        for (offset, op) in ops {
+            spans.push((from, offset, is_name(&stack)));
+            from = offset;
+            if stack.apply(&op).is_err() {
+                return symbols;
+            }
+        }
+        spans.push((from, line.trim_end_matches(['\r', '\n']).len().max(from), is_name(&stack)));
+        let mut open: Option<Symbol> = None;
+        for (from, to, inside) in spans {
+            if inside {
+                open.get_or_insert(Symbol { name: String::new(), line: line_index, column: from }).name.push_str(&line[from..to]);
+            } else if let Some(symbol) = open.take() {
+                symbols.push(symbol);
+            }
+        }
+        symbols.extend(open);
+    }
+    symbols.retain(|symbol| !symbol.name.trim().is_empty());
+    symbols
+}
+
+/// Every file under the project's folders that neither the ignore files nor
+/// the project's exclude patterns leave out.
+pub fn project_files(folders: &[ProjectFolder]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for folder in folders {
+        let excludes = Excludes::new(folder);
+        let walk = WalkBuilder::new(&folder.path)
+            .require_git(false)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+                !excludes.is_excluded(entry.path(), is_dir)
+            })
+            .build();
+        files.extend(
+            walk.filter_map(Result::ok)
+                .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+                .map(|entry| entry.into_path()),
+        );
+    }
+    files
+}
+
+/// How a file is listed: relative to its project folder, under the folder's
+/// name when the project has more than one.
+pub fn file_label(project: &Project, path: &Path) -> String {
+    let Some(folder) = project.folder_for(path) else { return path.to_string_lossy().to_string() };
+    let relative = path.strip_prefix(&folder.path).unwrap_or(path).to_string_lossy().to_string();
+    if project.folders.len() < 2 {
+        return relative;
+    }
+    let name = folder
+        .name
+        .clone()
+        .or_else(|| folder.path.file_name().map(|name| name.to_string_lossy().to_string()))
+        .unwrap_or_default();
+    format!("{name}/{relative}")
+}
+
+/// What Goto Anything lists: the project's files, recently opened ones
+/// first, and the active file's symbols for `@` queries.
+pub struct GotoItems {
+    pub paths: Vec<PathBuf>,
+    pub recent: usize,
+    pub symbols: Vec<Symbol>,
+}
+
+impl GotoItems {
+    /// Indices into the files, or into the symbols for `@` queries, best
+    /// match first. A target with no file part lists nothing; it acts on the
+    /// active file.
+    pub fn matches(&self, query: &str, labels: &[String]) -> Vec<usize> {
+        let query = GotoQuery::parse(query);
+        let matches = match &query.target {
+            Some(GotoTarget::Symbol(name)) if query.lists_symbols() => {
+                fuzzy::rank(name, self.symbols.iter().map(|symbol| symbol.name.as_str()), 0)
+            }
+            Some(_) if query.file.is_empty() => Vec::new(),
+            _ => fuzzy::rank(&query.file, labels.iter().map(String::as_str), self.recent),
+        };
+        matches.into_iter().take(MAX_RESULTS).collect()
+    }
+
+    pub fn label<'a>(&'a self, query: &str, item: usize, labels: &'a [String]) -> &'a str {
+        if GotoQuery::parse(query).lists_symbols() {
+            &self.symbols[item].name
+        } else {
+            &labels[item]
+        }
+    }
+}
+
+/// Moves `file` to the front of the recently opened files.
+pub fn remember_file(recent: &mut Vec<PathBuf>, file: &Path) {
+    recent.retain(|known| known != file);
+    recent.insert(0, file.to_path_buf());
+    recent.truncate(MAX_RECENT_FILES);
+}
+
+/// Where a Goto Line entry points, as a row and column counted from 0:
+/// `line`, `line:col`, or `+N` / `-N` lines from `current`. The row is
+/// clamped to the `line_count` lines there are.
+pub fn parse_line_target(input: &str, current: usize, line_count: usize) -> Option<(usize, usize)> {
+    let input = input.trim();
+    let (line, col) = match input.split_once(':') {
+        Some((line, col)) => (line, Some(col.trim().parse::<usize>().ok()?)),
+        None => (input, None),
+    };
+    let row = if let Some(down) = line.strip_prefix('+') {
+        current.checked_add(down.parse().ok()?)?
+    } else if let Some(up) = line.strip_prefix('-') {
+        current.saturating_sub(up.parse().ok()?)
+    } else {
+        line.parse::<usize>().ok()?.saturating_sub(1)
+    };
+    Some((row.min(line_count.saturating_sub(1)), col.unwrap_or(1).saturating_sub(1)))
+}
+
+/// Where the caret and scroll position were when Goto Line opened, to go back
+/// to if it is cancelled.
+pub struct GotoLineOrigin {
+    selections: Vec<Selection>,
+    scroll: Point<Pixels>,
+}
+
+impl ScrollDemo {
+    /// Opens Goto Anything with the recent files, then fills in the rest of
+    /// the project once it has been walked in the background.
+    pub fn goto_anything(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let symbols = self.active_symbols();
+        let picker = Picker::goto_anything(GotoItems { paths: Vec::new(), recent: 0, symbols });
+        self.open_picker(picker, window, cx);
+        self.set_goto_files(Vec::new());
+
+        let folders = self.project.folders.clone();
+        cx.spawn(async move |this, cx| {
+            let files = cx.background_executor().spawn(async move { project_files(&folders) }).await;
+            this.update(cx, |this, cx| {
+                this.set_goto_files(files);
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Goto Anything already listing the active file's symbols.
+    pub fn goto_symbol(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.goto_anything(window, cx);
+        if let Some(picker) = self.picker.as_mut() {
+            picker.set_query("@".into());
+        }
+    }
+
+    /// Lists `files` in an open Goto Anything, after the recent files.
+    fn set_goto_files(&mut self, files: Vec<PathBuf>) {
+        let mut paths: Vec<PathBuf> = self.recent_files.iter().filter(|path| path.is_file()).cloned().collect();
+        let recent = paths.len();
+        let known: HashSet<PathBuf> = paths.iter().cloned().collect();
+        paths.extend(files.into_iter().filter(|path| !known.contains(path)));
+        let labels = paths.iter().map(|path| file_label(&self.project, path)).collect();
+        let Some(picker) = self.picker.as_mut().filter(|picker| picker.kind == PickerKind::GotoAnything) else { return };
+        let Some(goto) = picker.goto.as_mut() else { return };
+        goto.paths = paths;
+        goto.recent = recent;
+        picker.set_items(labels);
+    }
+
+    fn active_symbols(&self) -> Vec<Symbol> {
+        let Some(tab) = self.active_tab() else { return Vec::new() };
+        let Some(buffer) = self.active_buffer() else { return Vec::new() };
+        symbols(&buffer.text(), self.syntax_for(&tab), &self.syntax_set)
+    }
+
+    /// Carries out a confirmed Goto Anything query.
+    pub fn confirm_goto(&mut self, picker: &Picker, cx: &mut Context<Self>) {
+        let Some(goto) = &picker.goto else { return };
+        let query = GotoQuery::parse(&picker.query);
+        let item = picker.selected_item();
+        if query.lists_symbols() {
+            if let Some(symbol) = item.map(|i| &goto.symbols[i]) {
+                self.go_to_offset_in_line(symbol.line, symbol.column);
+            }
+            return cx.notify();
+        }
+        if !query.file.is_empty() {
+            let Some(i) = item else { return };
+            self.open_file(goto.paths[i].clone(), cx);
+        }
+        match query.target {
+            Some(GotoTarget::Line(line, col)) => self.go_to_line(line.saturating_sub(1), col.unwrap_or(1).saturating_sub(1)),
+            Some(GotoTarget::Symbol(name)) => {
+                let symbols = self.active_symbols();
+                if let Some(&best) = fuzzy::rank(&name, symbols.iter().map(|symbol| symbol.name.as_str()), 0).first() {
+                    self.go_to_offset_in_line(symbols[best].line, symbols[best].column);
+                }
+            }
+            Some(GotoTarget::Term(term)) => {
+                self.find_query = term;
+                self.perform_search();
+            }
+            None => {}
+        }
+        cx.notify();
+    }
+
+    /// Puts a single caret at character `col` of `row`, both from 0, clamped
+    /// to the buffer, and scrolls to it.
+    pub fn go_to_line(&mut self, row: usize, col: usize) {
+        let Some(buffer) = self.active_buffer() else { return };
+        let row = row.min(buffer.line_count() - 1);
+        let line = buffer.line(row);
+        let column = line.char_indices().nth(col).map_or(buffer.line_len(row), |(i, _)| i);
+        self.go_to_offset_in_line(row, column);
+    }
+
+    fn go_to_offset_in_line(&mut self, row: usize, column: usize) {
+        let Some(buffer) = self.active_buffer() else { return };
+        let offset = buffer.point_to_offset(row, column);
+        self.set_cursor(offset);
+        self.scroll_to_row(row);
+    }
+
+    pub fn goto_line(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.active_buffer().is_none() {
+            return;
+        }
+        self.open_picker(Picker::new(PickerKind::GotoLine, Vec::new()), window, cx);
+        self.goto_line_origin = Some(GotoLineOrigin { selections: self.selections.clone(), scroll: self.right_handle.offset() });
+    }
+
+    /// The row and column the open Goto Line points at, if it is valid.
+    fn goto_line_target(&self) -> Option<(usize, usize)> {
+        let origin = self.goto_line_origin.as_ref()?;
+        let query = &self.picker.as_ref()?.query;
+        let buffer = self.active_buffer()?;
+        let current = origin.selections.last().map_or(0, |selection| buffer.offset_to_point(selection.head).0);
+        parse_line_target(query, current, buffer.line_count())
+    }
+
+    /// Shows where Goto Line points as it is typed, or the starting place
+    /// while it points nowhere.
+    pub fn preview_goto_line(&mut self) {
+        match self.goto_line_target() {
+            Some((row, col)) => self.go_to_line(row, col),
+            None => {
+                if let Some(origin) = &self.goto_line_origin {
+                    self.selections = origin.selections.clone();
+                    self.right_handle.set_offset(origin.scroll);
+                }
+            }
+        }
+    }
+
+    /// Keeps the previewed place when Goto Line is confirmed with a valid
+    /// line; otherwise closing it goes back to where it started.
+    pub fn confirm_goto_line(&mut self) {
+        if let Some((row, col)) = self.goto_line_target() {
+            self.goto_line_origin = None;
+            self.go_to_line(row, col);
+        }
+    }
+
+    /// Puts the caret and scroll position back after Goto Line is cancelled.
+    pub fn cancel_goto_line(&mut self) {
+        if let Some(origin) = self.goto_line_origin.take() {
+            self.selections = origin.selections;
+            self.right_handle.set_offset(origin.scroll);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_label, parse_line_target, symbols, GotoQuery, GotoTarget};
+    use crate::project::Project;
+    use std::path::Path;
+    use syntect::parsing::SyntaxSet;
+
+    fn parse(query: &str) -> (String, Option<GotoTarget>) {
+        let query = GotoQuery::parse(query);
+        (query.file, query.target)
+    }
+
+    #[test]
+    fn queries_split_into_file_and_target() {
+        assert_eq!(parse("main"), ("main".into(), None));
+        assert_eq!(parse("main:12"), ("main".into(), Some(GotoTarget::Line(12, None))));
+        assert_eq!(parse("main:12:5"), ("main".into(), Some(GotoTarget::Line(12, Some(5)))));
+        assert_eq!(parse("main@new"), ("main".into(), Some(GotoTarget::Symbol("new".into()))));
+        assert_eq!(parse("#todo"), ("".into(), Some(GotoTarget::Term("todo".into()))));
+        assert_eq!(parse("main:"), ("main".into(), None));
+        assert!(GotoQuery::parse("@").lists_symbols());
+        assert!(!GotoQuery::parse("main@x").lists_symbols());
+    }
+
+    #[test]
+    fn symbols_come_from_entity_name_scopes() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let rust = syntax_set.find_syntax_by_extension("rs").unwrap();
+        let found = symbols("struct Point;\n\nfn main() {\n    let x = 1;\n}\n", rust, &syntax_set);
+        let found: Vec<(&str, usize, usize)> = found.iter().map(|s| (s.name.as_str(), s.line, s.column)).collect();
+        assert_eq!(found, [("Point", 0, 7), ("main", 2, 3)]);
+    }
+
+    #[test]
+    fn labels_name_the_folder_only_with_several() {
+        let mut project = Project::from_folder("/p/app".into());
+        assert_eq!(file_label(&project, Path::new("/p/app/src/main.rs")), "src/main.rs");
+        project.add_folder("/p/lib".into());
+        assert_eq!(file_label(&project, Path::new("/p/lib/a.rs")), "lib/a.rs");
+        assert_eq!(file_label(&project, Path::new("/elsewhere/a.rs")), "/elsewhere/a.rs");
+    }
+
+    #[test]
+    fn line_targets_are_absolute_or_relative() {
+        assert_eq!(parse_line_target("12", 0, 100), Some((11, 0)));
+        assert_eq!(parse_line_target("12:5", 0, 100), Some((11, 4)));
+        assert_eq!(parse_line_target("+3", 10, 100), Some((13, 0)));
+        assert_eq!(parse_line_target("-30", 10, 100), Some((0, 0)));
+        assert_eq!(parse_line_target("500", 0, 100), Some((99, 0)));
+        assert_eq!(parse_line_target("0", 5, 100), Some((0, 0)));
+        assert_eq!(parse_line_target("", 0, 100), None);
+        assert_eq!(parse_line_target("12:", 0, 100), None);
+        assert_eq!(parse_line_target("x", 0, 100), None);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::selection::Selection;
+
+/// A single replacement of `deleted` by `inserted` at byte `offset`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit {
+    pub offset: usize,
+    pub deleted: String,
+    pub inserted: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditKind {
+    Typing,
+    Deletion,
+    Other,
+}
+
+/// One undo step: the edits it made, in the order they were applied, and the
+/// selections on either side of it.
+#[derive(Clone, Debug)]
+pub struct Transaction {
+    id: usize,
+    kind: EditKind,
+    pub edits: Vec<Edit>,
+    pub selections_before: Vec<Selection>,
+    pub selections_after: Vec<Selection>,
+}
+
+/// Undo and redo stacks for a buffer. Runs of typing or deleting merge into the
+/// transaction on top of the stack as long as they pick up where it left the
+/// selections, until something seals it (a cursor move, a save, an undo).
+#[derive(Clone)]
+pub struct History {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+    next_id: usize,
+    saved_id: usize,
+    sealed: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self { undo: Vec::new(), redo: Vec::new(), next_id: 1, saved_id: 0, sealed: true }
+    }
+}
+
+impl History {
+    pub fn record(
+        &mut self,
+        edits: Vec<Edit>,
+        kind: EditKind,
+        selections_before: Vec<Selection>,
+        selections_after: Vec<Selection>,
+    ) {
+        if edits.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        if !self.sealed {
+            if let Some(top) = self.undo.last_mut() {
+                if top.kind == kind && kind != EditKind::Other && top.selections_after == selections_before {
+                    top.edits.extend(edits);
+                    top.selections_after = selections_after;
+                    return;
+                }
+            }
+        }
+        self.undo.push(Transaction { id: self.next_id, kind, edits, selections_before, selections_after });
+        self.next_id += 1;
+        self.sealed = false;
+    }
+
+    /// Stops the next edit from merging into the current transaction.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    pub fn undo(&mut self) -> Option<Transaction> {
+        let transaction = self.undo.pop()?;
+        self.redo.push(transaction.clone());
+        self.sealed = true;
+        Some(transaction)
+    }
+
+    pub fn redo(&mut self) -> Option<Transaction> {
+        let transaction = self.redo.pop()?;
+        self.undo.push(transaction.clone());
+        self.sealed = true;
+        Some(transaction)
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_id = self.current_id();
+        self.sealed = true;
+    }
+
+    /// Whether the buffer differs from the last saved (or loaded) state.
+    pub fn is_modified(&self) -> bool {
+        self.saved_id != self.current_id()
+    }
+
+    fn current_id(&self) -> usize {
+        self.undo.last().map(|t| t.id).unwrap_or(0)
+    }
+}
@@ -0,0 +1,209 @@
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+/// Parses the relaxed JSON Sublime Text writes its project, settings and
+/// keymap files in: plain JSON plus `//` and `/* */` comments and trailing
+/// commas.
+pub fn from_str<T: DeserializeOwned>(text: &str) -> serde_json::Result<T> {
+    serde_json::from_str(&strict(text))
+}
+
+/// `text` with the top-level object's `key` set to `value`, or with `key`
+/// added at the end, changing nothing else so comments and layout survive.
+pub fn set_key(text: &str, key: &str, value: &Value) -> serde_json::Result<String> {
+    from_str::<Map<String, Value>>(text)?;
+    let value = serde_json::to_string(value)?;
+    let blank = blank_comments(text);
+    let mut depth = 0;
+    let mut string_start = None;
+    let mut escaped = false;
+    let mut value_start = None;
+    let mut span = None;
+    let mut close = blank.len();
+    for (i, c) in blank.char_indices() {
+        if let Some(start) = string_start {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                string_start = None;
+                let after = blank[i + 1..].trim_start();
+                if depth == 1 && after.starts_with(':') && serde_json::from_str::<String>(&blank[start..=i])? == key {
+                    value_start = Some(blank.len() - after[1..].trim_start().len());
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' => string_start = Some(i),
+            '{' | '[' => depth += 1,
+            ',' | '}' | ']' => {
+                if depth == 1 {
+                    if let Some(start) = value_start.take() {
+                        span = Some(start..blank[..i].trim_end().len());
+                    }
+                }
+                if c == ',' {
+                    continue;
+                }
+                depth -= 1;
+                if depth == 0 {
+                    close = i;
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut text = text.to_string();
+    if let Some(span) = span {
+        text.replace_range(span, &value);
+        return Ok(text);
+    }
+    let entry = format!("{}: {value}", serde_json::to_string(key)?);
+    let last = blank[..close].trim_end();
+    let line_start = blank[..close].rfind('\n').map_or(0, |i| i + 1);
+    if line_start >= last.len() {
+        text.insert_str(line_start, &format!("    {entry}\n"));
+    } else {
+        text.insert_str(close, &format!("{entry} "));
+    }
+    if !last.ends_with(['{', ',']) {
+        text.insert(last.len(), ',');
+    }
+    Ok(text)
+}
+
+/// `text` with comments blanked out and trailing commas dropped, keeping
+/// everything else where it was so parse errors still point at the right
+/// place.
+fn strict(text: &str) -> String {
+    let blank = blank_comments(text);
+    let mut out = String::with_capacity(blank.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in blank.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else {
+                escaped = c == '\\';
+                in_string = c != '"';
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if matches!(c, ']' | '}') {
+            let kept = out.trim_end_matches(char::is_whitespace).len();
+            if out[..kept].ends_with(',') {
+                out.replace_range(kept - 1..kept, " ");
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// `text` with its comments turned into spaces, keeping their line breaks,
+/// so everything else stays at the same offset.
+fn blank_comments(text: &str) -> String {
+    let blank = |out: &mut String, c: char| {
+        if c == '\n' {
+            out.push('\n');
+        } else {
+            out.extend(std::iter::repeat_n(' ', c.len_utf8()));
+        }
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                blank(&mut out, c);
+                while let Some(c) = chars.next_if(|&c| c != '\n') {
+                    blank(&mut out, c);
+                }
+            }
+            ('/', Some('*')) => {
+                blank(&mut out, c);
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    blank(&mut out, c);
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_str, set_key};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn comments_and_trailing_commas_are_accepted() {
+        let text = r#"{
+            // the folders
+            "folders": [{ "path": "src", }, ],
+            /* a block
+               comment */
+            "url": "http://example.com//not-a-comment",
+        }"#;
+        let value: Value = from_str(text).unwrap();
+        assert_eq!(value, json!({ "folders": [{ "path": "src" }], "url": "http://example.com//not-a-comment" }));
+    }
+
+    #[test]
+    fn escaped_quotes_stay_inside_strings() {
+        let value: Value = from_str(r#"["a \" // b", ]"#).unwrap();
+        assert_eq!(value, json!(["a \" // b"]));
+    }
+
+    #[test]
+    fn errors_keep_their_line_numbers() {
+        let err = from_str::<Value>("{\n/* one\ntwo */\n\"a\": }").unwrap_err();
+        assert_eq!(err.line(), 4);
+    }
+
+    #[test]
+    fn setting_a_key_keeps_the_rest_of_the_text() {
+        let text = "// mine\n{\n    \"a\": [1, \"}\"], // why\n    /* \"b\": 0 */\n    \"b\": { \"c\": 2 },\n}\n";
+        assert_eq!(
+            set_key(text, "b", &json!("x")).unwrap(),
+            "// mine\n{\n    \"a\": [1, \"}\"], // why\n    /* \"b\": 0 */\n    \"b\": \"x\",\n}\n"
+        );
+        assert_eq!(
+            set_key(text, "a", &json!(3)).unwrap(),
+            "// mine\n{\n    \"a\": 3, // why\n    /* \"b\": 0 */\n    \"b\": { \"c\": 2 },\n}\n"
+        );
+    }
+
+    #[test]
+    fn a_missing_key_is_added_at_the_end() {
+        let text = "{\n    \"a\": 1 // why\n    // \"b\": 2,\n}\n";
+        assert_eq!(set_key(text, "b", &json!(3)).unwrap(), "{\n    \"a\": 1, // why\n    // \"b\": 2,\n    \"b\": 3\n}\n");
+        assert_eq!(set_key("{\n    // \"b\": 2,\n}", "b", &json!(3)).unwrap(), "{\n    // \"b\": 2,\n    \"b\": 3\n}");
+        assert_eq!(set_key("{ \"a\": 1, }", "b", &json!(3)).unwrap(), "{ \"a\": 1, \"b\": 3 }");
+        assert!(set_key("[1]", "b", &json!(3)).is_err());
+    }
+}
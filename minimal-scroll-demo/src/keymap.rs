@@ -0,0 +1,323 @@
+use gpui::*;
+use serde::Deserialize;
+use std::fs;
+use std::mem;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::app_state::ScrollDemo;
+use crate::commands;
+use crate::json;
+use crate::settings::config_dir;
+
+const DEFAULT_KEYMAP: &str = include_str!("../assets/Default.sublime-keymap");
+
+/// The user keymap file, in the config directory.
+const USER_KEYMAP: &str = "Default.sublime-keymap";
+
+/// What a new user keymap starts out as.
+const USER_KEYMAP_TEMPLATE: &str = "\
+// Your key bindings, read after the default ones and winning over them. Each
+// is written like { \"keys\": [\"primary+shift+d\"], \"command\": \"save_all\" };
+// the default keymap explains the rest. Changes apply as soon as you save.
+[
+]
+";
+
+/// The key contexts a binding's context can name, each set on the part of the
+/// window it stands for.
+const CONTEXT_KEYS: &[&str] = &["editor", "find_panel", "find_in_files_panel", "overlay", "side_bar"];
+
+/// One binding from a `.sublime-keymap` file.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct KeymapEntry {
+    pub keys: Vec<String>,
+    pub command: String,
+    #[serde(default)]
+    pub context: Vec<ContextRule>,
+}
+
+/// A condition on where the keyboard focus is for a binding to apply.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ContextRule {
+    pub key: String,
+    #[serde(default)]
+    pub operator: Operator,
+    #[serde(default = "operand_default")]
+    pub operand: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    #[default]
+    Equal,
+    NotEqual,
+}
+
+fn operand_default() -> bool {
+    true
+}
+
+impl KeymapEntry {
+    /// The keys as GPUI writes them, like "secondary-k secondary-v".
+    fn keystrokes(&self) -> String {
+        self.keys.iter().map(|key| keystroke(key)).collect::<Vec<_>>().join(" ")
+    }
+
+    /// The context as a GPUI predicate, like "editor && !overlay".
+    fn predicate(&self) -> Result<Option<String>, String> {
+        let mut terms = Vec::new();
+        for rule in &self.context {
+            if !CONTEXT_KEYS.contains(&rule.key.as_str()) {
+                return Err(format!("unknown context key \"{}\"", rule.key));
+            }
+            let wanted = rule.operand == (rule.operator == Operator::Equal);
+            terms.push(if wanted { rule.key.clone() } else { format!("!{}", rule.key) });
+        }
+        Ok((!terms.is_empty()).then(|| terms.join(" && ")))
+    }
+
+    fn binding(&self) -> Result<KeyBinding, String> {
+        if self.keys.is_empty() {
+            return Err(format!("a binding for \"{}\" has no keys", self.command));
+        }
+        let action = match self.command.as_str() {
+            "noop" => NoAction.boxed_clone(),
+            id => commands::command(id).ok_or_else(|| format!("there is no command \"{id}\""))?.action(),
+        };
+        let context = match self.predicate()? {
+            Some(predicate) => Some(Rc::new(KeyBindingContextPredicate::parse(&predicate).map_err(|err| err.to_string())?)),
+            None => None,
+        };
+        KeyBinding::load(&self.keystrokes(), action, context, false, None, &DummyKeyboardMapper)
+            .map_err(|err| format!("{} in {:?}", err, self.keys))
+    }
+}
+
+/// A Sublime keystroke, "ctrl+shift+p", as a GPUI one, "ctrl-shift-p".
+/// "primary" becomes GPUI's "secondary": Cmd on macOS and Ctrl elsewhere.
+fn keystroke(key: &str) -> String {
+    let (modifiers, key) = match key.strip_suffix("++") {
+        Some(modifiers) => (modifiers, "+"),
+        None => key.rsplit_once('+').unwrap_or(("", key)),
+    };
+    modifiers
+        .split('+')
+        .filter(|modifier| !modifier.is_empty())
+        .map(|modifier| match modifier {
+            "primary" => "secondary",
+            "super" => "cmd",
+            modifier => modifier,
+        })
+        .chain([key])
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Whether GPUI keystrokes use Ctrl on macOS (`mac`) or Cmd elsewhere, as
+/// the default keymap's bindings for the other platform do.
+fn for_other_platform(keystrokes: &str, mac: bool) -> bool {
+    let other = if mac { "ctrl" } else { "cmd" };
+    keystrokes.split([' ', '-']).any(|part| part == other)
+}
+
+/// GPUI keystrokes as menus write them on macOS (`mac`) or elsewhere, like
+/// "Ctrl+Shift+S" or "Ctrl+K, Ctrl+V".
+fn shortcut_label(keystrokes: &str, mac: bool) -> String {
+    let keystroke = |keystroke: &str| {
+        keystroke
+            .split('-')
+            .map(|part| match part {
+                "secondary" if mac => "Cmd".to_string(),
+                "secondary" => "Ctrl".to_string(),
+                "cmd" if !mac => "Super".to_string(),
+                part => {
+                    let mut chars = part.chars();
+                    chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("+")
+    };
+    keystrokes.split_whitespace().map(keystroke).collect::<Vec<_>>().join(", ")
+}
+
+/// Parses the text of a keymap file.
+pub fn parse(text: &str) -> serde_json::Result<Vec<KeymapEntry>> {
+    json::from_str(text)
+}
+
+/// The active key bindings: the default keymap followed by the user's, so
+/// that GPUI lets the user's win where both bind the same keys.
+pub struct Keymap {
+    entries: Vec<KeymapEntry>,
+    user_start: usize,
+    /// Bindings made before ours, such as the components' own, which are
+    /// bound again whenever ours are replaced.
+    base: Vec<KeyBinding>,
+}
+
+impl Keymap {
+    pub fn user_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(USER_KEYMAP))
+    }
+
+    /// `base` are the bindings made before ours, to keep.
+    pub fn new(base: Vec<KeyBinding>, user: Vec<KeymapEntry>) -> Self {
+        let mut entries = parse(DEFAULT_KEYMAP).expect("the default keymap is valid");
+        let user_start = entries.len();
+        entries.extend(user);
+        Self { entries, user_start, base }
+    }
+
+    /// A GPUI binding for every entry, and what was wrong with those that
+    /// couldn't have one.
+    pub fn bindings(&self) -> (Vec<KeyBinding>, Vec<String>) {
+        let mut bindings = Vec::new();
+        let mut errors = Vec::new();
+        for entry in &self.entries {
+            match entry.binding() {
+                Ok(binding) => bindings.push(binding),
+                Err(err) => errors.push(err),
+            }
+        }
+        (bindings, errors)
+    }
+
+    /// The keys to show next to the command with `id` on this platform.
+    pub fn shortcut(&self, id: &str) -> Option<String> {
+        self.shortcut_for(id, cfg!(target_os = "macos"))
+    }
+
+    /// The user's latest binding for the command, else its first default
+    /// one, leaving out any a later entry takes the keys back from and the
+    /// defaults meant for the other platform.
+    fn shortcut_for(&self, id: &str, mac: bool) -> Option<String> {
+        let candidates: Vec<(usize, String)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.command == id)
+            .filter(|(i, entry)| {
+                !self.entries[i + 1..].iter().any(|later| {
+                    later.command != id && later.keystrokes() == entry.keystrokes() && later.context == entry.context
+                })
+            })
+            .filter(|(i, entry)| *i >= self.user_start || !for_other_platform(&entry.keystrokes(), mac))
+            .map(|(i, entry)| (i, shortcut_label(&entry.keystrokes(), mac)))
+            .collect();
+        let user = candidates.iter().rev().find(|(i, _)| *i >= self.user_start);
+        user.or(candidates.first()).map(|(_, label)| label.clone())
+    }
+}
+
+impl ScrollDemo {
+    /// Reads the default and user keymaps and binds them in place of the
+    /// current ones, reporting whatever is wrong with the user's.
+    pub fn load_keymap(&mut self, cx: &mut Context<Self>) {
+        let path = Keymap::user_path();
+        let mut errors = Vec::new();
+        let user = match path.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(text) => parse(&text).unwrap_or_else(|err| {
+                errors.push(err.to_string());
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        let keymap = Keymap::new(mem::take(&mut self.keymap.base), user);
+        let (bindings, binding_errors) = keymap.bindings();
+        errors.extend(binding_errors);
+        cx.clear_key_bindings();
+        cx.bind_keys(keymap.base.iter().cloned().chain(bindings));
+        self.keymap = keymap;
+        if let (Some(path), false) = (path, errors.is_empty()) {
+            self.notify_error(format!("Problems in {}: {}", path.display(), errors.join("; ")), cx);
+        }
+        cx.notify();
+    }
+
+    /// Opens the user keymap, creating it first if there is none yet.
+    pub fn edit_key_bindings(&mut self, cx: &mut Context<Self>) {
+        self.open_config_file(USER_KEYMAP, USER_KEYMAP_TEMPLATE, cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_other_platform, keystroke, parse, shortcut_label, Keymap, DEFAULT_KEYMAP, USER_KEYMAP_TEMPLATE};
+
+    #[test]
+    fn keystrokes_are_translated_for_gpui() {
+        assert_eq!(keystroke("primary+shift+p"), "secondary-shift-p");
+        assert_eq!(keystroke("super+alt+up"), "cmd-alt-up");
+        assert_eq!(keystroke("f3"), "f3");
+        assert_eq!(keystroke("ctrl++"), "ctrl-+");
+        assert_eq!(keystroke("ctrl+-"), "ctrl--");
+    }
+
+    #[test]
+    fn shortcuts_are_shown_for_the_platform() {
+        assert_eq!(shortcut_label("secondary-shift-s", false), "Ctrl+Shift+S");
+        assert_eq!(shortcut_label("secondary-shift-s", true), "Cmd+Shift+S");
+        assert_eq!(shortcut_label("secondary-k secondary-v", false), "Ctrl+K, Ctrl+V");
+        assert_eq!(shortcut_label("shift-f3", true), "Shift+F3");
+        assert_eq!(shortcut_label("ctrl-y", true), "Ctrl+Y");
+        assert_eq!(shortcut_label("cmd-s", false), "Super+S");
+        assert!(for_other_platform("ctrl-y", true) && for_other_platform("cmd-s", false));
+        assert!(!for_other_platform("secondary-k ctrl-v", false) && !for_other_platform("shift-f3", true));
+    }
+
+    #[test]
+    fn the_default_keymap_binds_registered_commands() {
+        assert!(parse(USER_KEYMAP_TEMPLATE).unwrap().is_empty());
+        let keymap = Keymap::new(Vec::new(), Vec::new());
+        assert_eq!(keymap.entries.len(), parse(DEFAULT_KEYMAP).unwrap().len());
+        assert!(keymap.entries.iter().all(|entry| entry.binding().is_ok()));
+        assert_eq!(keymap.shortcut_for("redo", false).as_deref(), Some("Ctrl+Y"));
+        assert_eq!(keymap.shortcut_for("redo", true).as_deref(), Some("Cmd+Shift+Z"));
+        assert_eq!(keymap.shortcut_for("single_selection", false).as_deref(), Some("Escape"));
+        assert_eq!(keymap.shortcut_for("open_folder", false), None);
+        // Text inputs in panels and pickers keep their own clipboard and undo.
+        for command in ["undo", "redo", "copy", "cut", "paste", "select_all"] {
+            let entries = keymap.entries.iter().filter(|entry| entry.command == command);
+            assert!(entries.clone().count() > 0 && entries.clone().all(|entry| entry.context.iter().any(|rule| rule.key == "editor")));
+        }
+    }
+
+    #[test]
+    fn user_bindings_take_over_the_menus() {
+        let user = parse(
+            r#"[
+                { "keys": ["primary+shift+d"], "command": "save_all" },
+                { "keys": ["primary+s"], "command": "noop" },
+                { "keys": ["ctrl+k", "ctrl+s"], "command": "save", "context": [{ "key": "editor" }] },
+            ]"#,
+        )
+        .unwrap();
+        let keymap = Keymap::new(Vec::new(), user);
+        assert_eq!(keymap.shortcut_for("save_all", false).as_deref(), Some("Ctrl+Shift+D"));
+        assert_eq!(keymap.shortcut_for("save", false).as_deref(), Some("Ctrl+K, Ctrl+S"));
+        assert_eq!(keymap.shortcut_for("save", true).as_deref(), Some("Ctrl+K, Ctrl+S"));
+        let (bindings, errors) = keymap.bindings();
+        assert_eq!(bindings.len(), keymap.entries.len());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn bad_entries_are_reported() {
+        let user = parse(
+            r#"[
+                { "keys": ["ctrl+m"], "command": "make_coffee" },
+                { "keys": ["ctrl+m"], "command": "save", "context": [{ "key": "minimap" }] },
+                { "keys": [], "command": "save" },
+                { "keys": ["ctrl+m"], "command": "save", "context": [{ "key": "overlay", "operator": "not_equal" }] },
+            ]"#,
+        )
+        .unwrap();
+        let (_, errors) = Keymap::new(Vec::new(), user).bindings();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("make_coffee"));
+        assert!(errors[1].contains("minimap"));
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// The line break a file uses on disk. Buffers hold `\n` only; this is what
+/// they are written back with. A `Mixed` file keeps each of its own breaks
+/// until the user picks one style for all of them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    Cr,
+    Mixed,
+}
+
+impl LineEnding {
+    /// The style every line break in `text` shares, `Mixed` if they differ,
+    /// or LF if there are none.
+    pub fn detect(text: &str) -> Self {
+        let mut breaks = breaks(text).into_iter();
+        let first = breaks.next().unwrap_or_default();
+        if breaks.all(|line_ending| line_ending == first) {
+            first
+        } else {
+            LineEnding::Mixed
+        }
+    }
+
+    /// The break itself; `Mixed` has no single one, so it's the buffer's `\n`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf | LineEnding::Mixed => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "Unix (LF)",
+            LineEnding::CrLf => "Windows (CRLF)",
+            LineEnding::Cr => "Mac OS 9 (CR)",
+            LineEnding::Mixed => "Mixed",
+        }
+    }
+
+    /// Turns the `\n`s of buffer text into this line ending. `Mixed` puts
+    /// back `breaks`, the file's own breaks in order; any past the end of
+    /// them get the first.
+    pub fn apply<'a>(&self, text: &'a str, breaks: &[LineEnding]) -> Cow<'a, str> {
+        match self {
+            LineEnding::Lf => Cow::Borrowed(text),
+            LineEnding::Mixed => {
+                let fallback = breaks.first().copied().unwrap_or_default();
+                let mut out = String::with_capacity(text.len() + breaks.len());
+                for (i, line) in text.split('\n').enumerate() {
+                    if i > 0 {
+                        out.push_str(breaks.get(i - 1).unwrap_or(&fallback).as_str());
+                    }
+                    out.push_str(line);
+                }
+                Cow::Owned(out)
+            }
+            _ => Cow::Owned(text.replace('\n', self.as_str())),
+        }
+    }
+}
+
+/// The style of each line break in `text`, in order.
+pub fn breaks(text: &str) -> Vec<LineEnding> {
+    let bytes = text.as_bytes();
+    let mut breaks = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match &bytes[i..] {
+            [b'\r', b'\n', ..] => {
+                breaks.push(LineEnding::CrLf);
+                i += 1;
+            }
+            [b'\r', ..] => breaks.push(LineEnding::Cr),
+            [b'\n', ..] => breaks.push(LineEnding::Lf),
+            _ => {}
+        }
+        i += 1;
+    }
+    breaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_finds_the_style_every_break_shares() {
+        assert_eq!(LineEnding::detect("no breaks"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\r\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\rb"), LineEnding::Cr);
+        assert_eq!(LineEnding::detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\nc"), LineEnding::Mixed);
+        assert_eq!(LineEnding::detect("a\nb\r\n"), LineEnding::Mixed);
+    }
+
+    #[test]
+    fn apply_rewrites_every_break() {
+        assert_eq!(LineEnding::CrLf.apply("a\nb\n", &[]), "a\r\nb\r\n");
+        assert_eq!(LineEnding::Lf.apply("a\nb", &[]), "a\nb");
+    }
+
+    #[test]
+    fn mixed_puts_back_each_break() {
+        let text = "a\r\nb\nc\rd";
+        assert_eq!(breaks(text), [LineEnding::CrLf, LineEnding::Lf, LineEnding::Cr]);
+        assert_eq!(LineEnding::Mixed.apply("a\nb\nc\nd", &breaks(text)), text);
+        assert_eq!(LineEnding::Mixed.apply("a\nb\nc\nd\ne", &breaks(text)), "a\r\nb\nc\rd\r\ne");
+    }
+}
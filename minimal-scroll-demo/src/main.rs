@@ -1,35 +1,45 @@
 mod actions;
+mod atomic_write;
+mod buffer;
+mod clipboard;
+mod close;
+mod color_scheme;
+mod commands;
+mod editor;
+mod encoding;
+mod explorer;
+mod file_ops;
+mod fuzzy;
+mod goto;
+mod hash;
+mod history;
+mod json;
+mod keymap;
+mod line_ending;
 mod menu;
+mod notifications;
 mod app_state;
+mod picker;
+mod project;
+mod project_tree;
+mod selection;
+mod session;
+mod settings;
+mod syntaxes;
+mod tab;
 mod ui;
+mod watcher;
 
-use gpui::prelude::FluentBuilder;
 use gpui::*;
 use gpui_component::scroll::ScrollbarShow;
 use gpui_component::theme::{Theme, ThemeMode};
 use gpui_component::{init, Root};
 
-use crate::actions::*;
 use crate::app_state::ScrollDemo;
 
 fn main() {
     Application::new().run(|cx: &mut App| {
         init(cx);
-        
-        cx.bind_keys([
-            KeyBinding::new("cmd-s", Save, None),
-            KeyBinding::new("ctrl-s", Save, None),
-            KeyBinding::new("cmd-shift-s", SaveAs, None),
-            KeyBinding::new("ctrl-shift-s", SaveAs, None),
-            KeyBinding::new("cmd-alt-s", SaveAll, None),
-            KeyBinding::new("ctrl-alt-s", SaveAll, None),
-            KeyBinding::new("cmd-f", FindAction, None),
-            KeyBinding::new("ctrl-f", FindAction, None),
-            KeyBinding::new("cmd-shift-f", FindInFilesAction, None),
-            KeyBinding::new("ctrl-shift-f", FindInFilesAction, None),
-            KeyBinding::new("cmd-q", Quit, None),
-            KeyBinding::new("ctrl-q", Quit, None),
-        ]);
 
         Theme::change(ThemeMode::Dark, None, cx);
         let theme = cx.global_mut::<Theme>();
@@ -43,8 +53,18 @@ fn main() {
             window_bounds: Some(WindowBounds::Windowed(bounds)), 
             ..Default::default() 
         }, |window, cx| {
-            let view = cx.new(|cx| ScrollDemo::new(cx));
+            let view = cx.new(ScrollDemo::new);
+            window.on_window_should_close(cx, {
+                let view = view.clone();
+                move |_window, cx| view.update(cx, |this, cx| this.request_quit(cx))
+            });
             cx.new(|cx| Root::new(view, window, cx))
         }).expect("failed to open window");
+        cx.on_window_closed(|cx| {
+            if cx.windows().is_empty() {
+                cx.quit();
+            }
+        })
+        .detach();
     });
 }
\ No newline at end of file
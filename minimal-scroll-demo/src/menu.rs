@@ -1,5 +1,7 @@
-use gpui::*;
+use gpui::Action;
+
 use crate::actions::*;
+use crate::commands::{self, Command};
 
 #[derive(Clone, PartialEq)]
 pub enum OpenMenu {
@@ -16,92 +18,103 @@ pub enum OpenMenu {
     Help,
 }
 
+/// The menus of the menu bar, left to right.
+pub const MENU_BAR: [(&str, OpenMenu); 10] = [
+    ("File", OpenMenu::File),
+    ("Edit", OpenMenu::Edit),
+    ("Selection", OpenMenu::Selection),
+    ("Find", OpenMenu::Find),
+    ("View", OpenMenu::View),
+    ("Goto", OpenMenu::Goto),
+    ("Tools", OpenMenu::Tools),
+    ("Project", OpenMenu::Project),
+    ("Preferences", OpenMenu::Preferences),
+    ("Help", OpenMenu::Help),
+];
+
+impl OpenMenu {
+    /// The items the menu drops down with.
+    pub fn items(&self) -> Vec<MenuItem> {
+        match self {
+            OpenMenu::None => Vec::new(),
+            OpenMenu::File => file_menu_items(),
+            OpenMenu::Edit => edit_menu_items(),
+            OpenMenu::Selection => selection_menu_items(),
+            OpenMenu::Find => find_menu_items(),
+            OpenMenu::View => view_menu_items(),
+            OpenMenu::Goto => goto_menu_items(),
+            OpenMenu::Tools => tools_menu_items(),
+            OpenMenu::Project => project_menu_items(),
+            OpenMenu::Preferences => preferences_menu_items(),
+            OpenMenu::Help => help_menu_items(),
+        }
+    }
+}
+
+/// A row of a menu. Items run a registered command and show its shortcut;
+/// ones without a command are shown greyed out.
+#[derive(Clone)]
 pub struct MenuItem {
     pub label: &'static str,
-    pub shortcut: Option<&'static str>,
-    pub action: Box<dyn Action>,
+    pub command: Option<&'static Command>,
     pub is_separator: bool,
     pub has_arrow: bool,
 }
 
-impl Clone for MenuItem {
-    fn clone(&self) -> Self {
-        Self {
-            label: self.label,
-            shortcut: self.shortcut,
-            action: self.action.boxed_clone(),
-            is_separator: self.is_separator,
-            has_arrow: self.has_arrow,
-        }
-    }
-}
-
 impl MenuItem {
-    pub fn item(label: &'static str, shortcut: Option<&'static str>, action: impl Action) -> Self {
-        Self {
-            label,
-            shortcut,
-            action: action.boxed_clone(),
-            is_separator: false,
-            has_arrow: false,
-        }
+    /// An item running the command registered for `action`, labelled as the
+    /// command says.
+    pub fn item(action: &dyn Action) -> Self {
+        let command = commands::for_action(action).unwrap_or_else(|| panic!("no command for {}", action.name()));
+        Self { command: Some(command), ..Self::unavailable(command.menu_label()) }
+    }
+    /// An item for something not implemented yet.
+    pub fn unavailable(label: &'static str) -> Self {
+        Self { label, command: None, is_separator: false, has_arrow: false }
     }
     pub fn sep() -> Self {
-        Self {
-            label: "",
-            shortcut: None,
-            action: Quit.boxed_clone(),
-            is_separator: true,
-            has_arrow: false,
-        }
+        Self { is_separator: true, ..Self::unavailable("") }
     }
     pub fn submenu(label: &'static str) -> Self {
-        Self {
-            label,
-            shortcut: None,
-            action: Quit.boxed_clone(),
-            is_separator: false,
-            has_arrow: true,
-        }
+        Self { has_arrow: true, ..Self::unavailable(label) }
     }
 }
 
 pub fn file_menu_items() -> Vec<MenuItem> {
     vec![
-        MenuItem::item("New File", Some("Ctrl+N"), Save),
+        MenuItem::item(&NewFile),
         MenuItem::sep(),
-        MenuItem::item("Open File...", Some("Ctrl+O"), Save),
-        MenuItem::item("Open Folder...", None, Save),
+        MenuItem::item(&OpenFile),
+        MenuItem::item(&OpenFolder),
         MenuItem::submenu("Open Recent"),
         MenuItem::sep(),
-        MenuItem::item("Reopen Closed File", None, Save),
-        MenuItem::item("New View into File", None, Save),
+        MenuItem::unavailable("Reopen Closed File"),
+        MenuItem::unavailable("New View into File"),
         MenuItem::sep(),
-        MenuItem::item("Save", Some("Ctrl+S"), Save),
-        MenuItem::item("Save As...", Some("Ctrl+Shift+S"), SaveAs),
-        MenuItem::item("Save All", Some("Ctrl+Alt+S"), SaveAll),
+        MenuItem::item(&Save),
+        MenuItem::item(&SaveAs),
+        MenuItem::item(&SaveAll),
         MenuItem::sep(),
-        MenuItem::item("Reload from Disk", None, Save),
+        MenuItem::item(&ReloadFromDisk),
+        MenuItem::item(&ReopenWithEncoding),
+        MenuItem::item(&SaveWithEncoding),
         MenuItem::sep(),
-        MenuItem::item("Close View", Some("Ctrl+W"), Quit),
-        MenuItem::item("Close File", None, Quit),
+        MenuItem::unavailable("Close View"),
+        MenuItem::item(&CloseFile),
         MenuItem::sep(),
-        if cfg!(target_os = "macos") {
-            MenuItem::item("Quit", Some("Cmd+Q"), Quit)
-        } else {
-            MenuItem::item("Exit", Some("Alt+F4"), Quit)
-        },
+        MenuItem::item(&Quit),
     ]
 }
 
 pub fn edit_menu_items() -> Vec<MenuItem> {
     vec![
-        MenuItem::item("Undo", Some("Ctrl+Z"), Save),
-        MenuItem::item("Redo", Some("Ctrl+Y"), Save),
+        MenuItem::item(&Undo),
+        MenuItem::item(&Redo),
         MenuItem::sep(),
-        MenuItem::item("Copy", Some("Ctrl+C"), Save),
-        MenuItem::item("Cut", Some("Ctrl+X"), Save),
-        MenuItem::item("Paste", Some("Ctrl+V"), Save),
+        MenuItem::item(&Copy),
+        MenuItem::item(&Cut),
+        MenuItem::item(&Paste),
+        MenuItem::item(&PasteFromHistory),
         MenuItem::sep(),
         MenuItem::submenu("Line"),
         MenuItem::submenu("Comment"),
@@ -112,77 +125,141 @@ pub fn edit_menu_items() -> Vec<MenuItem> {
 
 pub fn selection_menu_items() -> Vec<MenuItem> {
     vec![
-        MenuItem::item("Select All", Some("Ctrl+A"), Save),
-        MenuItem::item("Expand Selection", Some("Ctrl+L"), Save),
+        MenuItem::item(&SelectAll),
+        MenuItem::item(&ExpandSelectionToLine),
         MenuItem::sep(),
-        MenuItem::item("Add Next Line", Some("Ctrl+Alt+Down"), Save),
-        MenuItem::item("Add Previous Line", Some("Ctrl+Alt+Up"), Save),
+        MenuItem::item(&SingleSelection),
+        MenuItem::item(&AddCursorBelow),
+        MenuItem::item(&AddCursorAbove),
     ]
 }
 
 pub fn find_menu_items() -> Vec<MenuItem> {
     vec![
-        MenuItem::item("Find...", Some("Ctrl+F"), FindAction),
-        MenuItem::item("Find Next", Some("F3"), Save),
-        MenuItem::item("Find Previous", Some("Shift+F3"), Save),
-        MenuItem::item("Replace...", Some("Ctrl+H"), Save),
+        MenuItem::item(&FindAction),
+        MenuItem::item(&FindNext),
+        MenuItem::item(&FindPrevious),
+        MenuItem::unavailable("Replace..."),
+        MenuItem::item(&SelectNextOccurrence),
         MenuItem::sep(),
-        MenuItem::item("Find in Files...", Some("Ctrl+Shift+F"), FindInFilesAction),
+        MenuItem::item(&FindInFilesAction),
     ]
 }
 
 pub fn view_menu_items() -> Vec<MenuItem> {
     vec![
         MenuItem::submenu("Side Bar"),
+        MenuItem::item(&RevealInSideBar),
+        MenuItem::item(&ToggleIgnoredFiles),
         MenuItem::submenu("Show Console"),
         MenuItem::sep(),
         MenuItem::submenu("Layout"),
         MenuItem::submenu("Groups"),
+        MenuItem::sep(),
+        MenuItem::item(&SelectSyntax),
+        MenuItem::item(&LineEndingsUnix),
+        MenuItem::item(&LineEndingsWindows),
+    ]
+}
+
+/// The explorer's right-click menu; the items act on `explorer_selected`.
+pub fn explorer_menu_items() -> Vec<MenuItem> {
+    vec![
+        MenuItem::item(&ExplorerNewFile),
+        MenuItem::item(&ExplorerNewFolder),
+        MenuItem::sep(),
+        MenuItem::item(&ExplorerRename),
+        MenuItem::item(&ExplorerDuplicate),
+        MenuItem::item(&ExplorerDelete),
+        MenuItem::sep(),
+        MenuItem::item(&ExplorerCopyPath),
+        MenuItem::item(&ExplorerReveal),
+        MenuItem::sep(),
+        MenuItem::item(&RemoveFolderFromProject),
     ]
 }
 
 pub fn goto_menu_items() -> Vec<MenuItem> {
     vec![
-        MenuItem::item("Goto Anything...", Some("Ctrl+P"), Save),
+        MenuItem::item(&GotoAnything),
         MenuItem::sep(),
-        MenuItem::item("Goto Symbol...", Some("Ctrl+R"), Save),
-        MenuItem::item("Goto Line...", Some("Ctrl+G"), Save),
+        MenuItem::item(&GotoSymbol),
+        MenuItem::item(&GotoLine),
     ]
 }
 
 pub fn tools_menu_items() -> Vec<MenuItem> {
     vec![
-        MenuItem::item("Command Palette...", Some("Ctrl+Shift+P"), Save),
+        MenuItem::item(&CommandPalette),
         MenuItem::sep(),
         MenuItem::submenu("Build System"),
-        MenuItem::item("Build", Some("Ctrl+B"), Save),
+        MenuItem::unavailable("Build"),
     ]
 }
 
 pub fn project_menu_items() -> Vec<MenuItem> {
     vec![
-        MenuItem::item("Open Project...", None, Save),
-        MenuItem::submenu("Recent Projects"),
+        MenuItem::item(&OpenProject),
+        MenuItem::item(&RecentProjects),
         MenuItem::sep(),
-        MenuItem::item("Save Project As...", None, Save),
+        MenuItem::item(&SaveProjectAs),
+        MenuItem::sep(),
+        MenuItem::item(&AddFolderToProject),
     ]
 }
 
 pub fn preferences_menu_items() -> Vec<MenuItem> {
     vec![
-        MenuItem::item("Settings", None, Save),
-        MenuItem::item("Key Bindings", None, Save),
+        MenuItem::item(&EditSettings),
+        MenuItem::item(&EditSyntaxSettings),
+        MenuItem::item(&EditKeyBindings),
         MenuItem::sep(),
-        MenuItem::submenu("Color Scheme"),
+        MenuItem::item(&SelectColorScheme),
         MenuItem::submenu("Theme"),
     ]
 }
 
 pub fn help_menu_items() -> Vec<MenuItem> {
     vec![
-        MenuItem::item("Documentation", None, Save),
-        MenuItem::item("Twitter", None, Save),
+        MenuItem::unavailable("Documentation"),
+        MenuItem::unavailable("Twitter"),
         MenuItem::sep(),
-        MenuItem::item("About Sublime Text", None, Save),
+        MenuItem::unavailable("About Sublime Text"),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The item labelled `label` in `items`.
+    fn labelled(items: Vec<MenuItem>, label: &str) -> MenuItem {
+        items.into_iter().find(|item| item.label == label).unwrap_or_else(|| panic!("no item {label:?}"))
+    }
+
+    #[test]
+    fn every_menu_item_names_a_registered_command() {
+        let items: Vec<MenuItem> =
+            MENU_BAR.iter().flat_map(|(_, menu)| menu.items()).chain(explorer_menu_items()).collect();
+        for item in &items {
+            if let Some(command) = item.command {
+                let registered = commands::command(command.id);
+                assert!(registered.is_some_and(|registered| std::ptr::eq(registered, command)), "{}", item.label);
+                assert_eq!(item.label, command.menu_label());
+                assert!(!item.has_arrow && !item.is_separator, "{}", item.label);
+            }
+        }
+    }
+
+    #[test]
+    fn labels_come_from_the_commands() {
+        let id = |item: MenuItem| item.command.map(|command| command.id);
+        assert_eq!(id(labelled(file_menu_items(), "Save As...")), Some("save_as"));
+        assert_eq!(id(labelled(edit_menu_items(), "Undo")), Some("undo"));
+        assert_eq!(id(labelled(goto_menu_items(), "Goto Line...")), Some("goto_line"));
+        assert_eq!(id(labelled(project_menu_items(), "Save Project As...")), Some("save_project_as"));
+        assert_eq!(id(labelled(explorer_menu_items(), "Delete")), Some("explorer_delete"));
+        assert_eq!(id(labelled(find_menu_items(), "Replace...")), None);
+        assert_eq!(id(labelled(tools_menu_items(), "Build")), None);
+    }
+}
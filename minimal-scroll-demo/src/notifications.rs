@@ -0,0 +1,118 @@
+use gpui::*;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::app_state::ScrollDemo;
+
+/// How long a success message stays up. Errors stay until dismissed.
+const INFO_TIMEOUT: Duration = Duration::from_secs(4);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Info,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub id: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Messages shown in the corner of the window, oldest first.
+#[derive(Default)]
+pub struct Notifications {
+    items: Vec<Notification>,
+    next_id: usize,
+}
+
+impl Notifications {
+    pub fn push(&mut self, severity: Severity, message: String) -> usize {
+        self.next_id += 1;
+        self.items.push(Notification { id: self.next_id, severity, message });
+        self.next_id
+    }
+
+    pub fn dismiss(&mut self, id: usize) {
+        self.items.retain(|n| n.id != id);
+    }
+
+    pub fn items(&self) -> &[Notification] {
+        &self.items
+    }
+}
+
+/// Why an IO operation on `path` failed, in words for the user.
+pub fn describe_io_error(err: &io::Error, path: &Path) -> String {
+    let reason = match err.kind() {
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        io::ErrorKind::NotFound => match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !parent.exists() => {
+                format!("{} no longer exists", parent.display())
+            }
+            _ => "the file no longer exists".to_string(),
+        },
+        io::ErrorKind::StorageFull => "the disk is full".to_string(),
+        io::ErrorKind::ReadOnlyFilesystem => "the file system is read-only".to_string(),
+        io::ErrorKind::IsADirectory => "it is a directory".to_string(),
+        io::ErrorKind::AlreadyExists => "something with that name already exists".to_string(),
+        _ => err.to_string(),
+    };
+    format!("{}: {}", path.display(), reason)
+}
+
+impl ScrollDemo {
+    pub fn notify_info(&mut self, message: String, cx: &mut Context<Self>) {
+        let id = self.notifications.push(Severity::Info, message);
+        cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(INFO_TIMEOUT).await;
+            this.update(cx, |this, cx| this.dismiss_notification(id, cx)).ok();
+        })
+        .detach();
+        cx.notify();
+    }
+
+    pub fn notify_error(&mut self, message: String, cx: &mut Context<Self>) {
+        self.notifications.push(Severity::Error, message);
+        cx.notify();
+    }
+
+    pub fn dismiss_notification(&mut self, id: usize, cx: &mut Context<Self>) {
+        self.notifications.dismiss(id);
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe_io_error, Notifications, Severity};
+    use std::io;
+    use std::path::Path;
+
+    #[test]
+    fn dismiss_removes_only_that_notification() {
+        let mut notifications = Notifications::default();
+        let first = notifications.push(Severity::Info, "saved".into());
+        notifications.push(Severity::Error, "failed".into());
+        notifications.dismiss(first);
+        let messages: Vec<&str> = notifications.items().iter().map(|n| n.message.as_str()).collect();
+        assert_eq!(messages, ["failed"]);
+    }
+
+    #[test]
+    fn io_errors_name_the_path_and_reason() {
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(describe_io_error(&denied, Path::new("/etc/shadow")), "/etc/shadow: permission denied");
+        let gone = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(
+            describe_io_error(&gone, Path::new("/no/such/dir/a.txt")),
+            "/no/such/dir/a.txt: /no/such/dir no longer exists"
+        );
+        let full = io::Error::from(io::ErrorKind::StorageFull);
+        assert_eq!(describe_io_error(&full, Path::new("a.txt")), "a.txt: the disk is full");
+        let broken = io::Error::new(io::ErrorKind::InvalidData, "expected `,` at line 3 column 5");
+        assert_eq!(describe_io_error(&broken, Path::new("a.sublime-project")), "a.sublime-project: expected `,` at line 3 column 5");
+    }
+}
@@ -0,0 +1,266 @@
+use gpui::*;
+use std::path::PathBuf;
+
+use crate::app_state::ScrollDemo;
+use crate::commands;
+use crate::encoding;
+use crate::goto::GotoItems;
+use crate::menu::OpenMenu;
+
+/// What a picker lists, and so what confirming an item does.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PickerKind {
+    ClipboardHistory,
+    Syntax,
+    ReopenWithEncoding,
+    SaveWithEncoding,
+    NewFile,
+    NewFolder,
+    Rename,
+    Duplicate,
+    RecentProject,
+    GotoAnything,
+    GotoLine,
+    CommandPalette,
+    ColorScheme,
+}
+
+impl PickerKind {
+    /// Kinds that take a typed name rather than offering items.
+    pub fn is_name_prompt(&self) -> bool {
+        matches!(self, PickerKind::NewFile | PickerKind::NewFolder | PickerKind::Rename | PickerKind::Duplicate)
+    }
+
+    /// What a name prompt is asking for.
+    pub fn prompt(&self) -> &'static str {
+        match self {
+            PickerKind::NewFile => "Name of the new file",
+            PickerKind::NewFolder => "Name of the new folder",
+            PickerKind::Rename => "New name",
+            PickerKind::Duplicate => "Name of the copy",
+            PickerKind::GotoLine => "Line, line:column, or +/- lines from here",
+            _ => "",
+        }
+    }
+}
+
+/// A filterable list shown over the editor. Typing narrows `items` to the ones
+/// containing the query; `selected` indexes into those matches, which are
+/// worked out again only when the query or the items change. Name prompts
+/// have no items and use the query itself, with `target` the path they act on.
+/// Goto Anything ranks its items fuzzily instead, through `goto`, as does the
+/// Command Palette. `hints` are shown right-aligned beside the items.
+pub struct Picker {
+    pub kind: PickerKind,
+    pub query: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+    pub target: Option<PathBuf>,
+    pub goto: Option<GotoItems>,
+    pub hints: Vec<String>,
+    matches: Vec<usize>,
+}
+
+impl Picker {
+    pub fn new(kind: PickerKind, items: Vec<String>) -> Self {
+        let mut picker = Self {
+            kind,
+            query: String::new(),
+            items,
+            selected: 0,
+            target: None,
+            goto: None,
+            hints: Vec::new(),
+            matches: Vec::new(),
+        };
+        picker.update_matches();
+        picker
+    }
+
+    pub fn name_prompt(kind: PickerKind, target: PathBuf, name: String) -> Self {
+        Self { query: name, target: Some(target), ..Self::new(kind, Vec::new()) }
+    }
+
+    /// Goto Anything over `goto`, with its files still to be listed.
+    pub fn goto_anything(goto: GotoItems) -> Self {
+        let mut picker = Self { goto: Some(goto), ..Self::new(PickerKind::GotoAnything, Vec::new()) };
+        picker.update_matches();
+        picker
+    }
+
+    /// Indices of the items matching the query, ignoring case.
+    pub fn matches(&self) -> &[usize] {
+        &self.matches
+    }
+
+    fn update_matches(&mut self) {
+        self.matches = if let Some(goto) = &self.goto {
+            goto.matches(&self.query, &self.items)
+        } else if self.kind == PickerKind::CommandPalette {
+            commands::palette_matches(&self.query, &self.items)
+        } else {
+            let query = self.query.to_lowercase();
+            (0..self.items.len()).filter(|&i| self.items[i].to_lowercase().contains(&query)).collect()
+        };
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.selected = 0;
+        self.update_matches();
+    }
+
+    /// Replaces the items, as when Goto Anything's files come in, moving the
+    /// highlight back to the top.
+    pub fn set_items(&mut self, items: Vec<String>) {
+        self.items = items;
+        self.selected = 0;
+        self.update_matches();
+    }
+
+    /// Moves the highlight through the matches, wrapping at either end.
+    pub fn move_selection(&mut self, down: bool) {
+        let count = self.matches.len();
+        if count == 0 {
+            return;
+        }
+        self.selected = if down { (self.selected + 1) % count } else { (self.selected + count - 1) % count };
+    }
+
+    /// The text shown for a match.
+    pub fn label(&self, item: usize) -> &str {
+        match &self.goto {
+            Some(goto) => goto.label(&self.query, item, &self.items),
+            None => &self.items[item],
+        }
+    }
+
+    /// The item the highlight is on, as an index into `items`.
+    pub fn selected_item(&self) -> Option<usize> {
+        self.matches.get(self.selected).copied()
+    }
+}
+
+impl ScrollDemo {
+    pub fn open_picker(&mut self, picker: Picker, window: &mut Window, cx: &mut Context<Self>) {
+        self.cancel_goto_line();
+        self.picker = Some(picker);
+        self.open_menu = OpenMenu::None;
+        window.focus(&self.picker_focus_handle);
+        cx.notify();
+    }
+
+    /// Lists every loaded syntax for the active tab, current one first. Picking
+    /// one overrides the syntax its file name suggests.
+    pub fn select_syntax(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.active_tab().is_none() {
+            return;
+        }
+        let mut names: Vec<String> = self
+            .syntax_set
+            .syntaxes()
+            .iter()
+            .filter(|syntax| !syntax.hidden)
+            .map(|syntax| syntax.name.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+        if let Some(pos) = names.iter().position(|name| name == &self.current_syntax_name) {
+            let current = names.remove(pos);
+            names.insert(0, current);
+        }
+        self.open_picker(Picker::new(PickerKind::Syntax, names), window, cx);
+    }
+
+    /// Lists the encodings a file can be reopened or saved in.
+    pub fn select_encoding(&mut self, kind: PickerKind, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(tab) = self.active_tab() else { return };
+        if kind == PickerKind::ReopenWithEncoding && tab.path().is_none() {
+            return;
+        }
+        let names = encoding::choices().iter().map(|encoding| encoding.name()).collect();
+        self.open_picker(Picker::new(kind, names), window, cx);
+    }
+
+    pub fn close_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.picker = None;
+        self.color_scheme_preview = None;
+        self.cancel_goto_line();
+        window.focus(&self.focus_handle);
+        cx.notify();
+    }
+
+    pub fn confirm_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(picker) = self.picker.take() else { return };
+        if picker.kind == PickerKind::GotoLine {
+            self.picker = Some(picker);
+            self.confirm_goto_line();
+            return self.close_picker(window, cx);
+        }
+        if picker.kind == PickerKind::GotoAnything {
+            self.close_picker(window, cx);
+            return self.confirm_goto(&picker, cx);
+        }
+        if picker.kind.is_name_prompt() {
+            self.close_picker(window, cx);
+            if let Some(target) = picker.target {
+                self.confirm_name_prompt(picker.kind, target, &picker.query, cx);
+            }
+            return;
+        }
+        let Some(item) = picker.selected_item() else {
+            self.picker = Some(picker);
+            return;
+        };
+        self.close_picker(window, cx);
+        match picker.kind {
+            PickerKind::ClipboardHistory => self.paste_history_entry(item, cx),
+            PickerKind::Syntax => self.set_syntax(picker.items[item].clone(), cx),
+            PickerKind::ReopenWithEncoding => self.reopen_with_encoding(encoding::choices()[item], cx),
+            PickerKind::SaveWithEncoding => self.save_with_encoding(encoding::choices()[item], cx),
+            PickerKind::CommandPalette => self.run_command(&picker.items[item], window, cx),
+            PickerKind::ColorScheme => self.set_color_scheme(picker.items[item].clone(), cx),
+            PickerKind::RecentProject => {
+                let file = self.recent_projects[item].clone();
+                self.open_project(&file, cx);
+            }
+            PickerKind::NewFile
+            | PickerKind::NewFolder
+            | PickerKind::Rename
+            | PickerKind::Duplicate
+            | PickerKind::GotoAnything
+            | PickerKind::GotoLine => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Picker, PickerKind};
+
+    fn picker(items: &[&str]) -> Picker {
+        Picker::new(PickerKind::ClipboardHistory, items.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn query_filters_case_insensitively() {
+        let mut picker = picker(&["Alpha", "beta", "ALPHABET"]);
+        picker.set_query("alpha".into());
+        assert_eq!(picker.matches(), vec![0, 2]);
+        picker.move_selection(true);
+        assert_eq!(picker.selected_item(), Some(2));
+        picker.set_query("zzz".into());
+        assert_eq!(picker.selected_item(), None);
+        picker.set_items(vec!["zzz".into()]);
+        assert_eq!(picker.selected_item(), Some(0));
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let mut picker = picker(&["a", "b", "c"]);
+        picker.move_selection(false);
+        assert_eq!(picker.selected_item(), Some(2));
+        picker.move_selection(true);
+        assert_eq!(picker.selected_item(), Some(0));
+    }
+}
@@ -0,0 +1,501 @@
+use gpui::*;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::app_state::ScrollDemo;
+use crate::atomic_write::{self, SaveOptions};
+use crate::file_ops::moved_path;
+use crate::json;
+use crate::notifications::describe_io_error;
+use crate::picker::{Picker, PickerKind};
+
+/// How many projects Project > Recent Projects remembers.
+const MAX_RECENT_PROJECTS: usize = 10;
+
+/// One root folder of a project, as written in a `.sublime-project` file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectFolder {
+    pub path: PathBuf,
+    /// Shown in the explorer instead of the folder's own name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub folder_exclude_patterns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_exclude_patterns: Vec<String>,
+    /// Keys the editor doesn't use, kept so saving doesn't drop them.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The folders shown in the explorer and the settings that apply to them,
+/// optionally backed by a `.sublime-project` file. Folder paths are kept
+/// absolute; in the file they are relative to it where possible, as Sublime
+/// writes them.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    #[serde(skip)]
+    pub file: Option<PathBuf>,
+    #[serde(default)]
+    pub folders: Vec<ProjectFolder>,
+    /// Project-level settings, kept as written for the settings layers.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub settings: Map<String, Value>,
+    /// Keys the editor doesn't use, such as `build_systems`, kept so saving
+    /// doesn't drop them.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Project {
+    /// A project without a file holding just `folder`, as Open Folder makes.
+    pub fn from_folder(folder: PathBuf) -> Self {
+        Self { folders: vec![ProjectFolder { path: folder, ..ProjectFolder::default() }], ..Self::default() }
+    }
+
+    /// Reads a project file, resolving its folders against its directory.
+    pub fn load(file: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(file)?;
+        let mut project: Project =
+            json::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let base = file.parent().unwrap_or(Path::new(""));
+        for folder in &mut project.folders {
+            folder.path = normalize(&base.join(&folder.path));
+        }
+        project.file = Some(file.to_path_buf());
+        Ok(project)
+    }
+
+    /// Writes the whole project to `file`, as Save Project As does, with
+    /// folders inside its directory made relative to it.
+    pub fn save(&self, file: &Path) -> io::Result<()> {
+        let stored = Project {
+            file: None,
+            folders: self.stored_folders(file),
+            settings: self.settings.clone(),
+            extra: self.extra.clone(),
+        };
+        let json = serde_json::to_string_pretty(&stored).map_err(io::Error::other)?;
+        atomic_write::write(file, json.as_bytes(), &SaveOptions::default())
+    }
+
+    /// Writes the project's folders back to `file`, changing only the
+    /// `folders` key so the rest of the file, comments included, stays as the
+    /// user wrote it. A file that is gone is written whole.
+    pub fn save_folders(&self, file: &Path) -> io::Result<()> {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return self.save(file),
+            Err(err) => return Err(err),
+        };
+        let folders = serde_json::to_value(self.stored_folders(file)).map_err(io::Error::other)?;
+        let text =
+            json::set_key(&text, "folders", &folders).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        atomic_write::write(file, text.as_bytes(), &SaveOptions::default())
+    }
+
+    /// The folders as written in `file`: relative to it where they are inside
+    /// its directory.
+    fn stored_folders(&self, file: &Path) -> Vec<ProjectFolder> {
+        let base = file.parent().unwrap_or(Path::new(""));
+        self.folders.iter().map(|folder| ProjectFolder { path: relative_to(&folder.path, base), ..folder.clone() }).collect()
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = &PathBuf> {
+        self.folders.iter().map(|folder| &folder.path)
+    }
+
+    /// The root folder `path` is in; the innermost one if roots are nested.
+    pub fn folder_for(&self, path: &Path) -> Option<&ProjectFolder> {
+        self.folders
+            .iter()
+            .filter(|folder| path.starts_with(&folder.path))
+            .max_by_key(|folder| folder.path.components().count())
+    }
+
+    /// What the explorer calls a root folder.
+    pub fn display_name(&self, root: &Path) -> Option<String> {
+        self.folders.iter().find(|folder| folder.path == root).and_then(|folder| folder.name.clone())
+    }
+
+    /// The exclude patterns that apply to entries under `dir`.
+    pub fn excludes_for(&self, dir: &Path) -> Excludes {
+        self.folder_for(dir).map(Excludes::new).unwrap_or_default()
+    }
+
+    /// Points the roots at or under `from` to their new place under `to`.
+    /// Returns whether any moved.
+    pub fn folders_moved(&mut self, from: &Path, to: &Path) -> bool {
+        let mut moved = false;
+        for folder in &mut self.folders {
+            if let Some(path) = moved_path(&folder.path, from, to) {
+                folder.path = path;
+                moved = true;
+            }
+        }
+        moved
+    }
+
+    /// Adds `folder` as another root, unless it is one already.
+    pub fn add_folder(&mut self, folder: PathBuf) -> bool {
+        if self.roots().any(|root| *root == folder) {
+            return false;
+        }
+        self.folders.push(ProjectFolder { path: folder, ..ProjectFolder::default() });
+        true
+    }
+}
+
+/// A folder's exclude patterns, in `.gitignore` glob syntax: a pattern
+/// without a slash matches an entry's name anywhere below the folder.
+#[derive(Clone)]
+pub struct Excludes {
+    folders: Gitignore,
+    files: Gitignore,
+}
+
+impl Default for Excludes {
+    fn default() -> Self {
+        Self { folders: Gitignore::empty(), files: Gitignore::empty() }
+    }
+}
+
+impl Excludes {
+    pub fn new(folder: &ProjectFolder) -> Self {
+        let build = |patterns: &[String]| {
+            let mut builder = GitignoreBuilder::new(&folder.path);
+            for pattern in patterns {
+                let _ = builder.add_line(None, pattern);
+            }
+            builder.build().unwrap_or_else(|_| Gitignore::empty())
+        };
+        Self { folders: build(&folder.folder_exclude_patterns), files: build(&folder.file_exclude_patterns) }
+    }
+
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let patterns = if is_dir { &self.folders } else { &self.files };
+        patterns.matched(path, is_dir).is_ignore()
+    }
+}
+
+/// `path` with `.` and `..` components worked out, without touching the disk.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if out.file_name().is_some() => {
+                out.pop();
+            }
+            _ => out.push(component),
+        }
+    }
+    out
+}
+
+/// `path` relative to `base` when it is inside it, else unchanged.
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    match path.strip_prefix(base) {
+        Ok(rest) if rest.as_os_str().is_empty() => PathBuf::from("."),
+        Ok(rest) => rest.to_path_buf(),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// The file the project files opened or saved lately are kept in, newest
+/// first, next to the session file.
+pub struct RecentProjectsFile;
+
+impl RecentProjectsFile {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("sublime-rust-gpu").join("recent_projects.json"))
+    }
+
+    pub fn load() -> Vec<PathBuf> {
+        let Some(path) = Self::path() else { return Vec::new() };
+        fs::read_to_string(path).ok().and_then(|text| serde_json::from_str(&text).ok()).unwrap_or_default()
+    }
+
+    pub fn save(recent: &[PathBuf]) -> io::Result<()> {
+        let Some(path) = Self::path() else { return Ok(()) };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(recent).map_err(io::Error::other)?;
+        atomic_write::write(&path, json.as_bytes(), &SaveOptions::default())
+    }
+}
+
+/// Moves `file` to the front of `recent`, dropping the oldest past the limit.
+pub fn remember(recent: &mut Vec<PathBuf>, file: &Path) {
+    recent.retain(|known| known != file);
+    recent.insert(0, file.to_path_buf());
+    recent.truncate(MAX_RECENT_PROJECTS);
+}
+
+impl ScrollDemo {
+    /// Makes `project` the open one: the explorer shows its folders, each
+    /// starting expanded and scrolled to the top. Open tabs are left alone.
+    pub fn set_project(&mut self, project: Project, cx: &mut Context<Self>) {
+        self.project = project;
+        self.expanded_dirs = self.project.roots().cloned().collect();
+        self.project_tree.clear();
+        self.load_expanded_dirs(cx);
+        if let Some(root) = self.project.roots().next() {
+            self.fif_where = root.to_string_lossy().to_string();
+        }
+        self.left_handle.set_offset(Point::default());
+        self.load_project_settings(cx);
+        self.update_watches();
+        cx.notify();
+    }
+
+    pub fn prompt_open_project(&mut self, cx: &mut Context<Self>) {
+        let receiver = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: None,
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = receiver.await else { return };
+            let Some(file) = paths.into_iter().next() else { return };
+            this.update(cx, |this, cx| this.open_project(&file, cx)).ok();
+        })
+        .detach();
+    }
+
+    pub fn open_project(&mut self, file: &Path, cx: &mut Context<Self>) {
+        match Project::load(file) {
+            Ok(project) => {
+                self.set_project(project, cx);
+                self.remember_project(file, cx);
+            }
+            Err(err) => self.notify_error(format!("Couldn't open the project {}", describe_io_error(&err, file)), cx),
+        }
+    }
+
+    pub fn prompt_save_project_as(&mut self, cx: &mut Context<Self>) {
+        let directory = self.project.roots().next().cloned().unwrap_or_default();
+        let name = match directory.file_name() {
+            Some(name) => format!("{}.sublime-project", name.to_string_lossy()),
+            None => "untitled.sublime-project".to_string(),
+        };
+        let receiver = cx.prompt_for_new_path(&directory, Some(&name));
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(file))) = receiver.await else { return };
+            this.update(cx, |this, cx| {
+                // The project only moves to the new file once it is written,
+                // so a failed save leaves it on the one it had.
+                if let Err(err) = this.project.save(&file) {
+                    return this.notify_error(format!("Couldn't save the project {}", describe_io_error(&err, &file)), cx);
+                }
+                this.project.file = Some(file.clone());
+                this.remember_project(&file, cx);
+                this.update_watches();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Writes the project back to its file, if it has one.
+    pub fn write_project(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(file) = self.project.file.clone() else { return true };
+        match self.project.save_folders(&file) {
+            Ok(()) => true,
+            Err(err) => {
+                self.notify_error(format!("Couldn't save the project {}", describe_io_error(&err, &file)), cx);
+                false
+            }
+        }
+    }
+
+    fn remember_project(&mut self, file: &Path, cx: &mut Context<Self>) {
+        remember(&mut self.recent_projects, file);
+        if let Err(err) = RecentProjectsFile::save(&self.recent_projects) {
+            self.notify_error(format!("Couldn't store the recent projects: {err}"), cx);
+        }
+    }
+
+    /// Lists the recent projects that still exist.
+    pub fn select_recent_project(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.recent_projects.retain(|file| file.is_file());
+        if self.recent_projects.is_empty() {
+            return self.notify_info("There are no recent projects".to_string(), cx);
+        }
+        let items = self.recent_projects.iter().map(|file| file.to_string_lossy().to_string()).collect();
+        self.open_picker(Picker::new(PickerKind::RecentProject, items), window, cx);
+    }
+
+    pub fn prompt_add_folder(&mut self, cx: &mut Context<Self>) {
+        let receiver = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: true,
+            prompt: None,
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(folders))) = receiver.await else { return };
+            this.update(cx, |this, cx| {
+                for folder in folders {
+                    this.add_folder(folder, cx);
+                }
+                this.write_project(cx);
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn add_folder(&mut self, folder: PathBuf, cx: &mut Context<Self>) {
+        if self.project.add_folder(folder.clone()) {
+            self.expanded_dirs.insert(folder.clone());
+            self.load_dir(folder, false, cx);
+            cx.notify();
+        }
+    }
+
+    /// Removes the selected root folder from the project; files stay on disk.
+    /// Its listings go too, apart from those another root still shows.
+    pub fn remove_selected_folder(&mut self, cx: &mut Context<Self>) {
+        let Some(selected) = self.explorer_selected.clone() else { return };
+        let Some(index) = self.project.folders.iter().position(|folder| folder.path == selected) else {
+            return self.notify_info(format!("{} isn't a project folder", selected.display()), cx);
+        };
+        self.project.folders.remove(index);
+        self.expanded_dirs.retain(|dir| !dir.starts_with(&selected) || self.project.folder_for(dir).is_some());
+        self.explorer_selected = None;
+        self.project_tree.forget(&selected);
+        self.load_expanded_dirs(cx);
+        self.update_watches();
+        self.write_project(cx);
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{remember, Excludes, Project, ProjectFolder};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn folders_are_resolved_against_the_project_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("app.sublime-project");
+        fs::write(
+            &file,
+            r#"{
+                // Sublime allows comments here
+                "folders": [
+                    { "path": ".", "name": "App", "folder_exclude_patterns": ["target"] },
+                    { "path": "../shared" },
+                    { "path": "/opt/lib" },
+                ],
+                "settings": { "tab_size": 2 }
+            }"#,
+        )
+        .unwrap();
+        let project = Project::load(&file).unwrap();
+        let roots: Vec<&PathBuf> = project.roots().collect();
+        assert_eq!(roots, [&dir.path().to_path_buf(), &dir.path().parent().unwrap().join("shared"), &PathBuf::from("/opt/lib")]);
+        assert_eq!(project.display_name(dir.path()).as_deref(), Some("App"));
+        assert_eq!(project.settings["tab_size"], 2);
+
+        project.save(&file).unwrap();
+        let text = fs::read_to_string(&file).unwrap();
+        assert!(text.contains("\"path\": \".\""));
+        assert_eq!(Project::load(&file).unwrap(), project);
+    }
+
+    #[test]
+    fn keys_the_editor_doesnt_use_survive_a_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("app.sublime-project");
+        fs::write(
+            &file,
+            r#"{
+                "folders": [{ "path": ".", "follow_symlinks": true }],
+                "build_systems": [{ "name": "Test", "cmd": ["cargo", "test"] }],
+                "debugger_configurations": [],
+            }"#,
+        )
+        .unwrap();
+        let project = Project::load(&file).unwrap();
+        assert_eq!(project.folders[0].extra["follow_symlinks"], true);
+        project.save(&file).unwrap();
+
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(saved["build_systems"][0]["cmd"][1], "test");
+        assert_eq!(saved["debugger_configurations"], serde_json::json!([]));
+        assert_eq!(saved["folders"][0]["follow_symlinks"], true);
+        assert_eq!(Project::load(&file).unwrap(), project);
+    }
+
+    #[test]
+    fn saving_folders_keeps_comments_and_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("app.sublime-project");
+        let text = "{\n    // The app itself\n    \"folders\": [{ \"path\": \".\" }],\n    \"settings\": { \"tab_size\": 2, },\n}\n";
+        fs::write(&file, text).unwrap();
+        let mut project = Project::load(&file).unwrap();
+        project.add_folder(dir.path().join("docs"));
+        project.save_folders(&file).unwrap();
+
+        let saved = fs::read_to_string(&file).unwrap();
+        assert!(saved.starts_with("{\n    // The app itself\n    \"folders\": [{\"path\":\".\"},{\"path\":\"docs\"}],"));
+        assert!(saved.ends_with("    \"settings\": { \"tab_size\": 2, },\n}\n"));
+        assert_eq!(Project::load(&file).unwrap(), project);
+    }
+
+    #[test]
+    fn nested_roots_use_the_innermost_folder() {
+        let mut project = Project::from_folder("/p".into());
+        project.add_folder("/p/vendor".into());
+        assert!(!project.add_folder("/p".into()));
+        assert_eq!(project.folder_for(Path::new("/p/vendor/x.rs")).unwrap().path, Path::new("/p/vendor"));
+        assert_eq!(project.folder_for(Path::new("/p/src/x.rs")).unwrap().path, Path::new("/p"));
+        assert!(project.folder_for(Path::new("/q")).is_none());
+    }
+
+    #[test]
+    fn renaming_a_root_moves_it() {
+        let mut project = Project::from_folder("/p/app".into());
+        project.add_folder("/q".into());
+        assert!(project.folders_moved(Path::new("/p"), Path::new("/projects")));
+        assert_eq!(project.roots().collect::<Vec<_>>(), [Path::new("/projects/app"), Path::new("/q")]);
+        assert!(!project.folders_moved(Path::new("/q/src"), Path::new("/q/lib")));
+    }
+
+    #[test]
+    fn exclude_patterns_match_names_below_the_folder() {
+        let folder = ProjectFolder {
+            path: "/p".into(),
+            folder_exclude_patterns: vec!["node_modules".into()],
+            file_exclude_patterns: vec!["*.pyc".into()],
+            ..ProjectFolder::default()
+        };
+        let excludes = Excludes::new(&folder);
+        assert!(excludes.is_excluded(Path::new("/p/web/node_modules"), true));
+        assert!(!excludes.is_excluded(Path::new("/p/web/node_modules"), false));
+        assert!(excludes.is_excluded(Path::new("/p/a/b.pyc"), false));
+        assert!(!excludes.is_excluded(Path::new("/p/a/b.py"), false));
+    }
+
+    #[test]
+    fn recent_projects_are_newest_first_without_repeats() {
+        let mut recent: Vec<PathBuf> = (0..10).map(|i| PathBuf::from(format!("/p{i}.sublime-project"))).collect();
+        remember(&mut recent, Path::new("/p5.sublime-project"));
+        assert_eq!(recent[0], Path::new("/p5.sublime-project"));
+        assert_eq!(recent.len(), 10);
+        remember(&mut recent, Path::new("/new.sublime-project"));
+        assert_eq!(recent.len(), 10);
+        assert_eq!(recent[0], Path::new("/new.sublime-project"));
+        assert!(!recent.contains(&PathBuf::from("/p9.sublime-project")));
+    }
+}
@@ -0,0 +1,275 @@
+use gpui::*;
+use ignore::WalkBuilder;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app_state::ScrollDemo;
+use crate::project::Excludes;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_dir: bool,
+    /// Excluded by a `.gitignore`, `.ignore` or git exclude file, or the `.git`
+    /// directory itself.
+    pub ignored: bool,
+}
+
+/// The explorer's view of the folder: the listings of directories that have
+/// been expanded, read in the background and kept in memory so rendering
+/// never touches the disk. Listings are refreshed when the watcher reports a
+/// change inside them.
+#[derive(Default)]
+pub struct ProjectTree {
+    listings: HashMap<PathBuf, Vec<TreeEntry>>,
+    loading: HashSet<PathBuf>,
+    pub hide_ignored: bool,
+}
+
+impl ProjectTree {
+    /// The entries of `dir` to show, or `None` while it has not been read.
+    pub fn entries(&self, dir: &Path) -> Option<Vec<&TreeEntry>> {
+        let listing = self.listings.get(dir)?;
+        Some(listing.iter().filter(|entry| !(self.hide_ignored && entry.ignored)).collect())
+    }
+
+    /// The rows the explorer shows under `root`, top to bottom, as paths and
+    /// whether each is a directory. `root` is the first row; the entries of an
+    /// expanded directory follow it once its listing has been read.
+    pub fn visible_rows(&self, root: &Path, expanded: &HashSet<PathBuf>) -> Vec<(PathBuf, bool)> {
+        let mut rows = vec![(root.to_path_buf(), true)];
+        self.push_rows(root, expanded, &mut rows);
+        rows
+    }
+
+    fn push_rows(&self, dir: &Path, expanded: &HashSet<PathBuf>, rows: &mut Vec<(PathBuf, bool)>) {
+        if !expanded.contains(dir) {
+            return;
+        }
+        for entry in self.entries(dir).unwrap_or_default() {
+            rows.push((entry.path.clone(), entry.is_dir));
+            if entry.is_dir {
+                self.push_rows(&entry.path, expanded, rows);
+            }
+        }
+    }
+
+    /// Directories whose listings are held, and so need watching.
+    pub fn loaded_dirs(&self) -> impl Iterator<Item = &PathBuf> {
+        self.listings.keys()
+    }
+
+    pub fn clear(&mut self) {
+        self.listings.clear();
+        self.loading.clear();
+    }
+
+    /// Whether `dir` needs reading, marking it as being read if so.
+    fn start_loading(&mut self, dir: &Path, refresh: bool) -> bool {
+        if self.loading.contains(dir) || (!refresh && self.listings.contains_key(dir)) {
+            return false;
+        }
+        self.loading.insert(dir.to_path_buf());
+        true
+    }
+
+    /// Stores a finished read, unless the tree was cleared while it ran.
+    fn finish_loading(&mut self, dir: PathBuf, entries: Option<Vec<TreeEntry>>) {
+        if !self.loading.remove(&dir) {
+            return;
+        }
+        match entries {
+            Some(entries) => {
+                self.listings.insert(dir, entries);
+            }
+            None => self.forget(&dir),
+        }
+    }
+
+    /// Drops `dir` and everything below it, after it was deleted or moved.
+    pub(crate) fn forget(&mut self, dir: &Path) {
+        self.listings.retain(|path, _| !path.starts_with(dir));
+    }
+}
+
+/// Reads `dir`, directories first and then by name, marking which entries the
+/// ignore files exclude and leaving out those the project excludes. `None`
+/// when it can't be read.
+pub fn read_dir_entries(dir: &Path, excludes: &Excludes) -> Option<Vec<TreeEntry>> {
+    let kept: HashSet<PathBuf> = WalkBuilder::new(dir)
+        .max_depth(Some(1))
+        .hidden(false)
+        .require_git(false)
+        .build()
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .collect();
+    let mut entries: Vec<TreeEntry> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            TreeEntry {
+                is_dir: path.is_dir(),
+                ignored: name == ".git" || !kept.contains(&path),
+                path,
+                name,
+            }
+        })
+        .filter(|entry| !excludes.is_excluded(&entry.path, entry.is_dir))
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Some(entries)
+}
+
+impl ScrollDemo {
+    /// Expands or collapses a directory in the explorer, reading it the first
+    /// time it is opened.
+    pub fn toggle_dir(&mut self, dir: PathBuf, cx: &mut Context<Self>) {
+        if !self.expanded_dirs.remove(&dir) {
+            self.load_dir(dir.clone(), false, cx);
+            self.expanded_dirs.insert(dir);
+        }
+        cx.notify();
+    }
+
+    /// Reads every expanded directory that has no listing yet.
+    pub fn load_expanded_dirs(&mut self, cx: &mut Context<Self>) {
+        for dir in self.expanded_dirs.clone() {
+            self.load_dir(dir, false, cx);
+        }
+    }
+
+    /// Reads `dir` on a background thread into the project tree. With
+    /// `refresh` an existing listing is read again.
+    pub fn load_dir(&mut self, dir: PathBuf, refresh: bool, cx: &mut Context<Self>) {
+        if !self.project_tree.start_loading(&dir, refresh) {
+            return;
+        }
+        let excludes = self.project.excludes_for(&dir);
+        cx.spawn(async move |this, cx| {
+            let entries = cx.background_executor().spawn({
+                let dir = dir.clone();
+                async move { read_dir_entries(&dir, &excludes) }
+            });
+            let entries = entries.await;
+            this.update(cx, |this, cx| {
+                this.project_tree.finish_loading(dir, entries);
+                this.update_watches();
+                this.continue_reveal();
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Refreshes the listing a changed path appears in.
+    pub fn tree_path_changed(&mut self, path: &Path, cx: &mut Context<Self>) {
+        if !path.exists() {
+            self.project_tree.forget(path);
+        }
+        if let Some(parent) = path.parent() {
+            if self.project_tree.listings.contains_key(parent) {
+                self.load_dir(parent.to_path_buf(), true, cx);
+            }
+        }
+    }
+
+    pub fn toggle_ignored_files(&mut self, cx: &mut Context<Self>) {
+        self.project_tree.hide_ignored = !self.project_tree.hide_ignored;
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_dir_entries, ProjectTree, TreeEntry};
+    use crate::project::{Excludes, ProjectFolder};
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn listings_put_directories_first_and_mark_ignored_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.log\ntarget/\n").unwrap();
+        fs::write(dir.path().join("b.rs"), "").unwrap();
+        fs::write(dir.path().join("a.log"), "").unwrap();
+        fs::create_dir(dir.path().join("target")).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+
+        let entries = read_dir_entries(dir.path(), &Excludes::default()).unwrap();
+        let listed: Vec<(&str, bool)> = entries.iter().map(|e| (e.name.as_str(), e.ignored)).collect();
+        assert_eq!(listed, [("src", false), ("target", true), (".gitignore", false), ("a.log", true), ("b.rs", false)]);
+
+        let mut tree = ProjectTree::default();
+        assert!(tree.start_loading(dir.path(), false));
+        assert!(tree.entries(dir.path()).is_none());
+        tree.finish_loading(dir.path().to_path_buf(), Some(entries));
+        assert!(!tree.start_loading(dir.path(), false));
+        tree.hide_ignored = true;
+        let shown: Vec<&str> = tree.entries(dir.path()).unwrap().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(shown, ["src", ".gitignore", "b.rs"]);
+    }
+
+    #[test]
+    fn project_excludes_leave_entries_out() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("node_modules")).unwrap();
+        fs::write(dir.path().join("main.pyc"), "").unwrap();
+        fs::write(dir.path().join("main.py"), "").unwrap();
+        let folder = ProjectFolder {
+            path: dir.path().to_path_buf(),
+            folder_exclude_patterns: vec!["node_modules".into()],
+            file_exclude_patterns: vec!["*.pyc".into()],
+            ..ProjectFolder::default()
+        };
+        let entries = read_dir_entries(dir.path(), &Excludes::new(&folder)).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["main.py"]);
+    }
+
+    #[test]
+    fn forgetting_a_directory_drops_its_subtree() {
+        let mut tree = ProjectTree::default();
+        for dir in ["/p", "/p/a", "/p/a/b", "/p/ab"] {
+            tree.start_loading(dir.as_ref(), false);
+            tree.finish_loading(dir.into(), Some(Vec::new()));
+        }
+        tree.forget("/p/a".as_ref());
+        let mut left: Vec<_> = tree.loaded_dirs().cloned().collect();
+        left.sort();
+        assert_eq!(left, [PathBuf::from("/p"), "/p/ab".into()]);
+    }
+
+    #[test]
+    fn visible_rows_follow_expanded_directories() {
+        let entry = |path: &str, is_dir| TreeEntry {
+            path: path.into(),
+            name: path.rsplit('/').next().unwrap().to_string(),
+            is_dir,
+            ignored: false,
+        };
+        let mut tree = ProjectTree::default();
+        for (dir, entries) in [
+            ("/p", vec![entry("/p/a", true), entry("/p/b", true), entry("/p/c.rs", false)]),
+            ("/p/a", vec![entry("/p/a/x.rs", false)]),
+            ("/p/b", vec![entry("/p/b/y.rs", false)]),
+        ] {
+            tree.start_loading(dir.as_ref(), false);
+            tree.finish_loading(dir.into(), Some(entries));
+        }
+        let expanded: HashSet<PathBuf> = ["/p", "/p/a"].into_iter().map(PathBuf::from).collect();
+        let rows: Vec<(String, bool)> = tree
+            .visible_rows("/p".as_ref(), &expanded)
+            .into_iter()
+            .map(|(path, is_dir)| (path.to_string_lossy().to_string(), is_dir))
+            .collect();
+        let expected = [("/p", true), ("/p/a", true), ("/p/a/x.rs", false), ("/p/b", true), ("/p/c.rs", false)];
+        assert_eq!(rows, expected.map(|(path, is_dir)| (path.to_string(), is_dir)));
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A selected range of the buffer in byte offsets. `head` is where the caret
+/// is drawn and what moves; `anchor` stays put while extending. An empty
+/// selection is a plain cursor. `goal` is the display column that vertical
+/// moves aim for, so a caret passing over short lines returns to its column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+    pub goal: Option<usize>,
+}
+
+impl Selection {
+    pub fn cursor(offset: usize) -> Self {
+        Self::new(offset, offset)
+    }
+
+    pub fn new(anchor: usize, head: usize) -> Self {
+        Self { anchor, head, goal: None }
+    }
+
+    pub fn start(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    pub fn end(&self) -> usize {
+        self.anchor.max(self.head)
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start()..self.end()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    /// Moves the head to `offset`, keeping the anchor when `extend` is set and
+    /// collapsing to a cursor otherwise.
+    pub fn moved_to(&self, offset: usize, extend: bool) -> Self {
+        if extend {
+            Self::new(self.anchor, offset)
+        } else {
+            Self::cursor(offset)
+        }
+    }
+
+    pub fn with_goal(self, goal: usize) -> Self {
+        Self { goal: Some(goal), ..self }
+    }
+}
+
+/// Sorts selections by position and merges any that overlap or touch, so each
+/// part of the buffer is edited at most once. The direction of a merged
+/// selection follows the later one.
+pub fn normalize(mut selections: Vec<Selection>) -> Vec<Selection> {
+    selections.sort_by_key(|s| (s.start(), s.end()));
+    let mut merged: Vec<Selection> = Vec::with_capacity(selections.len());
+    for selection in selections {
+        if let Some(last) = merged.last_mut() {
+            let overlaps = selection.start() < last.end()
+                || (selection.start() == last.end() && (selection.is_empty() || last.is_empty()));
+            if overlaps {
+                let start = last.start();
+                let end = last.end().max(selection.end());
+                *last = if selection.head < selection.anchor {
+                    Selection::new(end, start)
+                } else {
+                    Selection::new(start, end)
+                };
+                continue;
+            }
+        }
+        merged.push(selection);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_sorts_and_merges_overlaps() {
+        let selections = vec![
+            Selection::new(10, 14),
+            Selection::cursor(2),
+            Selection::new(12, 18),
+            Selection::cursor(2),
+        ];
+        assert_eq!(normalize(selections), vec![Selection::cursor(2), Selection::new(10, 18)]);
+    }
+
+    #[test]
+    fn adjacent_ranges_stay_separate() {
+        let selections = vec![Selection::new(0, 3), Selection::new(3, 6)];
+        assert_eq!(normalize(selections.clone()), selections);
+    }
+
+    #[test]
+    fn moved_to_extends_from_anchor() {
+        let selection = Selection::cursor(4);
+        assert_eq!(selection.moved_to(7, true), Selection::new(4, 7));
+        assert_eq!(selection.moved_to(7, false), Selection::cursor(7));
+        assert_eq!(Selection::new(7, 4).range(), 4..7);
+    }
+}
@@ -0,0 +1,322 @@
+use gpui::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::app_state::ScrollDemo;
+use crate::atomic_write::{self, SaveOptions};
+use crate::buffer::Buffer;
+use crate::editor::TabView;
+use crate::encoding::TextEncoding;
+use crate::line_ending::LineEnding;
+use crate::project::{Project, ProjectFolder};
+use crate::selection::Selection;
+use crate::tab::TabId;
+
+/// How often the session is written while the editor runs, so a crash loses
+/// at most this much.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What is needed to put the window back the way it was: the open tabs with
+/// any unsaved text, and the project and explorer.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// The `.sublime-project` file the project was opened from, if any.
+    pub project_file: Option<PathBuf>,
+    pub folders: Vec<ProjectFolder>,
+    pub expanded_dirs: Vec<PathBuf>,
+    pub sidebar_width: Option<f32>,
+    pub tabs: Vec<SessionTab>,
+    pub active_tab: Option<usize>,
+    pub next_untitled: usize,
+}
+
+/// A tab with where its caret and scroll position were, and anything about
+/// its buffer that reading the file again wouldn't bring back.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionTab {
+    pub id: TabId,
+    /// The buffer text when it had unsaved changes.
+    #[serde(default)]
+    pub unsaved: Option<String>,
+    #[serde(default)]
+    pub syntax: Option<String>,
+    #[serde(default)]
+    pub selections: Vec<Selection>,
+    #[serde(default)]
+    pub scroll_offset: (f32, f32),
+    /// The encoding's name and whether it has a BOM, so a tab reopened in
+    /// another encoding than the detected one stays in it.
+    #[serde(default)]
+    pub encoding: Option<(String, bool)>,
+    /// Line endings converted but not saved yet.
+    #[serde(default)]
+    pub line_ending: Option<LineEnding>,
+}
+
+impl SessionTab {
+    fn encoding(&self) -> Option<TextEncoding> {
+        let (name, bom) = self.encoding.as_ref()?;
+        Some(TextEncoding::new(encoding_rs::Encoding::for_label(name.as_bytes())?, *bom))
+    }
+}
+
+impl Session {
+    /// Where the session lives, under the platform's local data directory.
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("sublime-rust-gpu").join("session.json"))
+    }
+
+    /// The session stored at `path`, if there is a readable one.
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(self).map_err(io::Error::other)?;
+        atomic_write::write(path, json.as_bytes(), &SaveOptions::default())
+    }
+
+    /// The project to reopen: the project file if it still loads, else the
+    /// stored folders that still exist.
+    pub fn project(&mut self) -> Option<Project> {
+        if let Some(Ok(project)) = self.project_file.as_deref().map(Project::load) {
+            return Some(project);
+        }
+        let mut folders = std::mem::take(&mut self.folders);
+        folders.retain(|folder| folder.path.is_dir());
+        (!folders.is_empty()).then(|| Project { folders, ..Project::default() })
+    }
+}
+
+/// Rebuilds the buffer for a stored tab. A file is read again from disk, in
+/// the tab's encoding if it had one picked, and any unsaved text is laid over
+/// it as an edit, so the tab comes back modified and undo returns to what is
+/// on disk. Files that have gone missing only come back when they had unsaved
+/// text; Find Results are not restored.
+pub fn restore_buffer(tab: &SessionTab) -> Option<Buffer> {
+    let encoding = tab.encoding();
+    let mut buffer = match &tab.id {
+        TabId::File(path) => match fs::read(path) {
+            Ok(bytes) => {
+                let buffer = Buffer::from_bytes(&bytes);
+                match encoding {
+                    Some(encoding) if !buffer.read_only && encoding != buffer.encoding => Buffer::from_text(&encoding.decode(&bytes)),
+                    _ => buffer,
+                }
+            }
+            Err(_) if tab.unsaved.is_some() => Buffer::from_text(""),
+            Err(_) => return None,
+        },
+        TabId::Untitled(_) => Buffer::from_text(""),
+        TabId::FindResults => return None,
+    };
+    if buffer.read_only {
+        return Some(buffer);
+    }
+    if let Some(encoding) = encoding {
+        buffer.encoding = encoding;
+    }
+    if let Some(text) = &tab.unsaved {
+        buffer.set_text(text);
+    }
+    // A mixed file keeps its own breaks only while it is unchanged on disk.
+    if let Some(line_ending) = tab.line_ending.filter(|&line_ending| line_ending != LineEnding::Mixed) {
+        buffer.line_ending = line_ending;
+    }
+    Some(buffer)
+}
+
+impl ScrollDemo {
+    /// A snapshot of the window for the session file.
+    pub fn session(&self) -> Session {
+        let active = self.active_tab();
+        let tabs: Vec<SessionTab> = self
+            .open_tabs
+            .iter()
+            .filter(|tab| **tab != TabId::FindResults)
+            .map(|tab| {
+                let buffer = self.tab_contents.get(tab).filter(|buffer| !buffer.read_only);
+                let dirty = matches!(tab, TabId::Untitled(_)) || self.dirty_tabs.contains(tab);
+                let view = match (active.as_ref() == Some(tab), self.tab_views.get(tab)) {
+                    (true, _) => Some(TabView { selections: self.selections.clone(), scroll: self.right_handle.offset() }),
+                    (false, view) => view.cloned(),
+                };
+                SessionTab {
+                    id: tab.clone(),
+                    unsaved: buffer.filter(|_| dirty).map(Buffer::text),
+                    syntax: self.syntax_overrides.get(tab).cloned(),
+                    selections: view.as_ref().map(|view| view.selections.clone()).unwrap_or_default(),
+                    scroll_offset: view.map_or((0.0, 0.0), |view| (view.scroll.x.into(), view.scroll.y.into())),
+                    encoding: buffer.map(|buffer| (buffer.encoding.encoding.name().to_string(), buffer.encoding.bom)),
+                    line_ending: buffer.filter(|_| dirty).map(|buffer| buffer.line_ending),
+                }
+            })
+            .collect();
+        Session {
+            project_file: self.project.file.clone(),
+            folders: self.project.folders.clone(),
+            expanded_dirs: self.expanded_dirs.iter().cloned().collect(),
+            sidebar_width: Some(self.sidebar_width),
+            active_tab: active.and_then(|active| tabs.iter().position(|tab| tab.id == active)),
+            tabs,
+            next_untitled: self.next_untitled,
+        }
+    }
+
+    /// Writes the session file. Failures are ignored: there is nobody to tell
+    /// on quit, and the next autosave tries again.
+    pub fn write_session(&self) {
+        if let Some(path) = Session::path() {
+            let _ = self.session().save(&path);
+        }
+    }
+
+    /// Reopens the tabs and explorer state of a stored session, and starts
+    /// writing the session on quit and every `AUTOSAVE_INTERVAL`.
+    pub fn restore_session(&mut self, mut session: Session, cx: &mut Context<Self>) {
+        if let Some(project) = session.project() {
+            if let Some(root) = project.roots().next() {
+                self.fif_where = root.to_string_lossy().to_string();
+            }
+            self.project = project;
+        }
+        self.expanded_dirs = session.expanded_dirs.into_iter().collect();
+        if let Some(width) = session.sidebar_width {
+            self.sidebar_width = width.clamp(50.0, 600.0);
+        }
+        self.next_untitled = self.next_untitled.max(session.next_untitled);
+        let active = session.active_tab.and_then(|idx| session.tabs.get(idx)).map(|tab| tab.id.clone());
+        for tab in session.tabs {
+            let Some(buffer) = restore_buffer(&tab) else { continue };
+            if let TabId::Untitled(n) = tab.id {
+                self.next_untitled = self.next_untitled.max(n + 1);
+            }
+            if let Some(name) = tab.syntax {
+                self.syntax_overrides.insert(tab.id.clone(), name);
+            }
+            if !tab.selections.is_empty() {
+                let (x, y) = tab.scroll_offset;
+                self.tab_views.insert(tab.id.clone(), TabView { selections: tab.selections, scroll: point(px(x), px(y)) });
+            }
+            self.tab_contents.insert(tab.id.clone(), buffer);
+            self.open_tabs.push(tab.id.clone());
+            self.sync_dirty(&tab.id);
+        }
+        let index = active.and_then(|active| self.open_tabs.iter().position(|tab| *tab == active));
+        self.activate_tab(index.or((!self.open_tabs.is_empty()).then_some(0)));
+        self.autosave_session(cx);
+    }
+
+    fn autosave_session(&mut self, cx: &mut Context<Self>) {
+        cx.on_app_quit(|this, _cx| {
+            this.write_session();
+            async {}
+        })
+        .detach();
+        cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(AUTOSAVE_INTERVAL).await;
+            if this.update(cx, |this, _cx| this.write_session()).is_err() {
+                break;
+            }
+        })
+        .detach();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{restore_buffer, Session, SessionTab};
+    use crate::encoding::TextEncoding;
+    use crate::line_ending::LineEnding;
+    use crate::selection::Selection;
+    use encoding_rs::WINDOWS_1252;
+    use crate::tab::TabId;
+    use std::fs;
+
+    fn tab(id: TabId, unsaved: Option<&str>) -> SessionTab {
+        SessionTab {
+            id,
+            unsaved: unsaved.map(String::from),
+            syntax: None,
+            selections: Vec::new(),
+            scroll_offset: (0.0, 0.0),
+            encoding: None,
+            line_ending: None,
+        }
+    }
+
+    #[test]
+    fn session_round_trips_through_json() {
+        let mut file = tab(TabId::File("/tmp/a.rs".into()), None);
+        file.selections = vec![Selection::new(1, 4)];
+        file.scroll_offset = (0.0, -120.0);
+        file.encoding = Some(("windows-1252".into(), false));
+        file.line_ending = Some(LineEnding::CrLf);
+        let session = Session {
+            tabs: vec![file, tab(TabId::Untitled(3), Some("draft"))],
+            active_tab: Some(1),
+            next_untitled: 4,
+            ..Session::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/session.json");
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path).unwrap();
+        assert_eq!(loaded.tabs[1].id, TabId::Untitled(3));
+        assert_eq!(loaded.tabs[1].unsaved.as_deref(), Some("draft"));
+        assert_eq!(loaded.tabs[0].selections, [Selection::new(1, 4)]);
+        assert_eq!(loaded.tabs[0].scroll_offset, (0.0, -120.0));
+        assert_eq!(loaded.tabs[0].encoding(), Some(TextEncoding::new(WINDOWS_1252, false)));
+        assert_eq!(loaded.tabs[0].line_ending, Some(LineEnding::CrLf));
+        assert_eq!(loaded.active_tab, Some(1));
+    }
+
+    #[test]
+    fn picked_encoding_and_line_endings_are_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, b"caf\xc3\xa9\n").unwrap();
+        let mut stored = tab(TabId::File(path), None);
+        stored.encoding = Some(("windows-1252".into(), false));
+        stored.line_ending = Some(LineEnding::CrLf);
+
+        let buffer = restore_buffer(&stored).unwrap();
+        assert_eq!(buffer.encoding, TextEncoding::new(WINDOWS_1252, false));
+        assert_eq!(buffer.text(), "cafÃ©\n");
+        assert_eq!(buffer.line_ending, LineEnding::CrLf);
+        assert!(buffer.is_modified());
+    }
+
+    #[test]
+    fn unsaved_text_is_restored_over_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "on disk\n").unwrap();
+
+        let mut buffer = restore_buffer(&tab(TabId::File(path.clone()), Some("edited\n"))).unwrap();
+        assert_eq!(buffer.text(), "edited\n");
+        assert!(buffer.is_modified());
+        buffer.undo();
+        assert_eq!(buffer.text(), "on disk\n");
+        assert!(!buffer.is_modified());
+
+        let clean = restore_buffer(&tab(TabId::File(path), None)).unwrap();
+        assert!(!clean.is_modified());
+    }
+
+    #[test]
+    fn missing_files_return_only_with_unsaved_text() {
+        let gone = TabId::File("/no/such/dir/a.txt".into());
+        assert!(restore_buffer(&tab(gone.clone(), None)).is_none());
+        assert_eq!(restore_buffer(&tab(gone, Some("kept"))).unwrap().text(), "kept");
+        assert!(restore_buffer(&tab(TabId::FindResults, None)).is_none());
+    }
+}
@@ -0,0 +1,351 @@
+use gpui::*;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::app_state::ScrollDemo;
+use crate::atomic_write::{self, BackupMode, SaveOptions};
+use crate::json;
+use crate::notifications::describe_io_error;
+
+const DEFAULT_SETTINGS: &str = include_str!("../assets/Preferences.sublime-settings");
+
+/// The user settings file, in `config_dir`; syntax specific ones sit next to
+/// it, named after their syntax.
+const USER_SETTINGS: &str = "Preferences.sublime-settings";
+const SETTINGS_EXTENSION: &str = "sublime-settings";
+
+/// Where the user's settings and keymap live, under the platform's config
+/// directory.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("sublime-rust-gpu"))
+}
+
+/// Everything that can be configured, as it applies after all the layers.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Settings {
+    pub color_scheme: String,
+    pub font_face: String,
+    pub line_height: f32,
+    pub tab_size: usize,
+    pub translate_tabs_to_spaces: bool,
+    pub sidebar_width: f32,
+    pub find_in_files_use_gitignore: bool,
+    pub hot_exit: bool,
+    pub save_backup: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        SettingsLayers::default().global()
+    }
+}
+
+impl Settings {
+    /// Whether the values are in range, beyond being of the right type.
+    fn check(&self) -> Result<(), String> {
+        if !(1..=16).contains(&self.tab_size) {
+            return Err("tab_size must be between 1 and 16".into());
+        }
+        if !(8.0..=100.0).contains(&self.line_height) {
+            return Err("line_height must be between 8 and 100".into());
+        }
+        if !(50.0..=600.0).contains(&self.sidebar_width) {
+            return Err("sidebar_width must be between 50 and 600".into());
+        }
+        Ok(())
+    }
+
+    pub fn save_options(&self) -> SaveOptions {
+        SaveOptions { backup: BackupMode::parse(&self.save_backup) }
+    }
+
+    /// What Tab inserts.
+    pub fn indent(&self) -> String {
+        if self.translate_tabs_to_spaces {
+            " ".repeat(self.tab_size)
+        } else {
+            "\t".to_string()
+        }
+    }
+}
+
+/// The layers settings come from, each overriding those before it: the
+/// defaults, the user's, the ones for the file's syntax, and the project's.
+/// Every setting in a layer has been checked against the defaults, so any
+/// combination of layers makes valid `Settings`.
+pub struct SettingsLayers {
+    defaults: Map<String, Value>,
+    user: Map<String, Value>,
+    syntax: HashMap<String, Map<String, Value>>,
+    project: Map<String, Value>,
+}
+
+impl Default for SettingsLayers {
+    fn default() -> Self {
+        Self {
+            defaults: json::from_str(DEFAULT_SETTINGS).expect("the default settings are valid"),
+            user: Map::new(),
+            syntax: HashMap::new(),
+            project: Map::new(),
+        }
+    }
+}
+
+impl SettingsLayers {
+    /// The settings outside of any one file.
+    pub fn global(&self) -> Settings {
+        self.resolve(None)
+    }
+
+    /// The settings for a file in `syntax`.
+    pub fn for_syntax(&self, syntax: &str) -> Settings {
+        self.resolve(self.syntax.get(syntax))
+    }
+
+    fn resolve(&self, syntax: Option<&Map<String, Value>>) -> Settings {
+        let mut merged = self.defaults.clone();
+        for layer in [Some(&self.user), syntax, Some(&self.project)].into_iter().flatten() {
+            merged.extend(layer.clone());
+        }
+        serde_json::from_value(Value::Object(merged)).expect("settings layers are checked")
+    }
+
+    /// Replaces the user layer, returning what was wrong with it.
+    pub fn set_user(&mut self, mut layer: Map<String, Value>) -> Vec<String> {
+        let errors = self.check(&mut layer);
+        self.user = layer;
+        errors
+    }
+
+    pub fn set_syntax(&mut self, syntax: &str, mut layer: Map<String, Value>) -> Vec<String> {
+        let errors = self.check(&mut layer);
+        self.syntax.insert(syntax.to_string(), layer);
+        errors
+    }
+
+    pub fn set_project(&mut self, mut layer: Map<String, Value>) -> Vec<String> {
+        let errors = self.check(&mut layer);
+        self.project = layer;
+        errors
+    }
+
+    /// Drops the settings in `layer` that aren't known or aren't valid on
+    /// their own, describing each.
+    fn check(&self, layer: &mut Map<String, Value>) -> Vec<String> {
+        let mut errors = Vec::new();
+        layer.retain(|key, value| {
+            if !self.defaults.contains_key(key) {
+                errors.push(format!("unknown setting \"{key}\""));
+                return false;
+            }
+            let mut merged = self.defaults.clone();
+            merged.insert(key.clone(), value.clone());
+            let checked = serde_json::from_value::<Settings>(Value::Object(merged))
+                .map_err(|err| err.to_string())
+                .and_then(|settings| settings.check());
+            match checked {
+                Ok(()) => true,
+                Err(err) => {
+                    errors.push(format!("\"{key}\": {err}"));
+                    false
+                }
+            }
+        });
+        errors
+    }
+}
+
+/// What a new user settings file starts out as: every default setting,
+/// commented out.
+fn user_settings_template() -> String {
+    let defaults = DEFAULT_SETTINGS.lines().filter(|line| line.trim_start().starts_with('"'));
+    let mut template = String::from("// Your settings, applied over the defaults. Uncomment any of these to change\n// it; changes apply as soon as you save.\n{\n");
+    for line in defaults {
+        template.push_str(&format!("    // {}\n", line.trim()));
+    }
+    template.push_str("}\n");
+    template
+}
+
+/// Reads a settings file, or `None` when there is none.
+fn read_layer(path: &Path) -> Result<Option<Map<String, Value>>, String> {
+    let Ok(text) = fs::read_to_string(path) else { return Ok(None) };
+    json::from_str(&text).map(Some).map_err(|err| err.to_string())
+}
+
+impl ScrollDemo {
+    /// The settings for the active tab, with its syntax's layer applied.
+    pub fn active_settings(&self) -> Settings {
+        self.settings_layers.for_syntax(&self.current_syntax_name)
+    }
+
+    /// Reads the user's settings files and applies them, reporting the
+    /// settings that were left out and why.
+    pub fn load_user_settings(&mut self, cx: &mut Context<Self>) {
+        let Some(dir) = config_dir() else { return };
+        let mut errors = Vec::new();
+        let mut describe = |file: &str, problems: Vec<String>| {
+            errors.extend(problems.into_iter().map(|problem| format!("{file}: {problem}")));
+        };
+        let mut user = Map::new();
+        let mut syntax = Vec::new();
+        for entry in fs::read_dir(&dir).into_iter().flatten().filter_map(Result::ok) {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != SETTINGS_EXTENSION) {
+                continue;
+            }
+            let file = entry.file_name().to_string_lossy().to_string();
+            match read_layer(&path) {
+                Ok(Some(layer)) if file == USER_SETTINGS => user = layer,
+                Ok(Some(layer)) => syntax.push((file, layer)),
+                Ok(None) => {}
+                Err(err) => describe(&file, vec![err]),
+            }
+        }
+        describe(USER_SETTINGS, self.settings_layers.set_user(user));
+        self.settings_layers.syntax.clear();
+        for (file, layer) in syntax {
+            let name = Path::new(&file).file_stem().unwrap_or_default().to_string_lossy().to_string();
+            describe(&file, self.settings_layers.set_syntax(&name, layer));
+        }
+        self.apply_settings(cx);
+        if !errors.is_empty() {
+            self.notify_error(format!("Problems in your settings: {}", errors.join("; ")), cx);
+        }
+    }
+
+    /// Applies the open project's settings over the user's.
+    pub fn load_project_settings(&mut self, cx: &mut Context<Self>) {
+        let errors = self.settings_layers.set_project(self.project.settings.clone());
+        self.apply_settings(cx);
+        if !errors.is_empty() {
+            self.notify_error(format!("Problems in the project settings: {}", errors.join("; ")), cx);
+        }
+    }
+
+    /// Makes the settings outside of any one file take effect. The side bar
+    /// width and the Find in Files gitignore toggle can be changed by hand,
+    /// so they only follow the settings when those change.
+    fn apply_settings(&mut self, cx: &mut Context<Self>) {
+        let settings = self.settings_layers.global();
+        if settings.sidebar_width != self.settings.sidebar_width {
+            self.sidebar_width = settings.sidebar_width;
+        }
+        if settings.find_in_files_use_gitignore != self.settings.find_in_files_use_gitignore {
+            self.fif_use_gitignore = settings.find_in_files_use_gitignore;
+        }
+        if !self.theme_set.themes.contains_key(&settings.color_scheme) && settings.color_scheme != self.settings.color_scheme {
+            self.notify_error(format!("There is no color scheme \"{}\"", settings.color_scheme), cx);
+        }
+        self.save_options = settings.save_options();
+        self.settings = settings;
+        cx.notify();
+    }
+
+    /// Sets one setting in the user settings file and applies it, changing
+    /// only that setting's value so the rest of the file is kept as written.
+    pub fn set_user_setting(&mut self, key: &str, value: Value, cx: &mut Context<Self>) {
+        let Some(dir) = config_dir() else { return };
+        let path = dir.join(USER_SETTINGS);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => user_settings_template(),
+            Err(err) => return self.notify_error(format!("Couldn't change {}", describe_io_error(&err, &path)), cx),
+        };
+        let text = match json::set_key(&text, key, &value) {
+            Ok(text) => text,
+            Err(err) => return self.notify_error(format!("Couldn't change {}: {err}", path.display()), cx),
+        };
+        let written = fs::create_dir_all(&dir).and_then(|()| atomic_write::write(&path, text.as_bytes(), &SaveOptions::default()));
+        if let Err(err) = written {
+            return self.notify_error(format!("Couldn't save {}", describe_io_error(&err, &path)), cx);
+        }
+        self.load_user_settings(cx);
+    }
+
+    /// Opens the user settings, creating the file first if need be.
+    pub fn edit_settings(&mut self, cx: &mut Context<Self>) {
+        self.open_config_file(USER_SETTINGS, &user_settings_template(), cx);
+    }
+
+    /// Opens the settings for the active tab's syntax.
+    pub fn edit_syntax_settings(&mut self, cx: &mut Context<Self>) {
+        let file = format!("{}.{SETTINGS_EXTENSION}", self.current_syntax_name);
+        let template = format!("// Settings for {} files, applied over your own.\n{{\n}}\n", self.current_syntax_name);
+        self.open_config_file(&file, &template, cx);
+    }
+
+    /// Opens `file` from the config directory in a tab, creating it from
+    /// `template` if it doesn't exist yet.
+    pub fn open_config_file(&mut self, file: &str, template: &str, cx: &mut Context<Self>) {
+        let Some(dir) = config_dir() else { return };
+        let path = dir.join(file);
+        if !path.exists() {
+            if let Err(err) = fs::create_dir_all(&dir).and_then(|()| fs::write(&path, template)) {
+                return self.notify_error(format!("Couldn't create {}", describe_io_error(&err, &path)), cx);
+            }
+        }
+        self.open_file(path, cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{user_settings_template, Settings, SettingsLayers};
+    use crate::atomic_write::BackupMode;
+    use crate::json;
+    use serde_json::{json, Map, Value};
+
+    fn layer(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn defaults_match_what_was_hard_coded() {
+        let settings = Settings::default();
+        assert_eq!(settings.color_scheme, "base16-ocean.dark");
+        assert_eq!(settings.font_face, "Courier New");
+        assert_eq!(settings.line_height, 20.0);
+        assert_eq!(settings.indent(), "    ");
+        assert_eq!(settings.sidebar_width, 250.0);
+        assert!(settings.find_in_files_use_gitignore && !settings.hot_exit);
+        assert_eq!(settings.save_options().backup, BackupMode::None);
+        let template = user_settings_template();
+        assert!(template.contains("    // \"tab_size\": 4,"));
+        assert!(json::from_str::<Map<String, Value>>(&template).unwrap().is_empty());
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let mut layers = SettingsLayers::default();
+        layers.set_user(layer(json!({ "tab_size": 2, "font_face": "Menlo" })));
+        layers.set_syntax("Python", layer(json!({ "tab_size": 8, "translate_tabs_to_spaces": false })));
+        layers.set_project(layer(json!({ "font_face": "Monaco" })));
+        let global = layers.global();
+        assert_eq!((global.tab_size, global.font_face.as_str()), (2, "Monaco"));
+        let python = layers.for_syntax("Python");
+        assert_eq!((python.tab_size, python.indent().as_str()), (8, "\t"));
+        assert_eq!(layers.for_syntax("Rust"), global);
+    }
+
+    #[test]
+    fn invalid_settings_are_dropped_and_reported() {
+        let mut layers = SettingsLayers::default();
+        let errors = layers.set_user(layer(json!({
+            "tab_size": "four",
+            "line_height": 2,
+            "font_size": 12,
+            "hot_exit": true,
+        })));
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().any(|err| err.starts_with("\"tab_size\": invalid type")));
+        assert!(errors.iter().any(|err| err.contains("line_height must be")));
+        assert!(errors.iter().any(|err| err.contains("unknown setting \"font_size\"")));
+        let settings = layers.global();
+        assert_eq!((settings.tab_size, settings.line_height, settings.hot_exit), (4, 20.0, true));
+    }
+}
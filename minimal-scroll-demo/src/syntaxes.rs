@@ -0,0 +1,166 @@
+use gpui::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use syntect::dumps::{dump_to_file, from_dump_file};
+use syntect::parsing::{SyntaxDefinition, SyntaxSet};
+use walkdir::WalkDir;
+
+use crate::app_state::ScrollDemo;
+use crate::hash::fnv1a;
+use crate::settings::config_dir;
+
+/// Where the user's packages live, each in a folder of its own as in Sublime,
+/// with `.sublime-syntax` files anywhere inside.
+pub fn packages_dir() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("Packages"))
+}
+
+/// Where the syntaxes built from the user's files are kept between runs, as
+/// building them takes a while.
+fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("sublime-rust-gpu").join("syntaxes.packdump"))
+}
+
+/// Every `.sublime-syntax` file under `dir`, in a stable order.
+fn syntax_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "sublime-syntax"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Changes whenever a syntax file is added, removed or edited, or the editor
+/// is rebuilt, possibly with other default syntaxes. It is kept with the
+/// cache, so it uses the fixed FNV-1a rather than std's hashers.
+fn fingerprint(files: &[PathBuf]) -> u64 {
+    let mut key = env!("CARGO_PKG_VERSION").as_bytes().to_vec();
+    for path in files {
+        key.push(0);
+        key.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        let metadata = fs::metadata(path).ok();
+        key.extend_from_slice(&metadata.as_ref().map_or(u64::MAX, |metadata| metadata.len()).to_le_bytes());
+        let modified = metadata.and_then(|metadata| metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok());
+        key.extend_from_slice(&modified.map_or(u128::MAX, |modified| modified.as_nanos()).to_le_bytes());
+    }
+    fnv1a(&key)
+}
+
+/// syntect's default syntaxes plus those in `files`, which win over a default
+/// one of the same name or extension, with what was wrong with the files that
+/// couldn't be loaded.
+pub fn build_syntax_set(files: &[PathBuf]) -> (SyntaxSet, Vec<String>) {
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    let mut errors = Vec::new();
+    for path in files {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let syntax = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| SyntaxDefinition::load_from_str(&text, true, Some(&name)).map_err(|err| err.to_string()));
+        match syntax {
+            Ok(syntax) => builder.add(syntax),
+            Err(err) => errors.push(format!("{}: {err}", path.display())),
+        }
+    }
+    (builder.build(), errors)
+}
+
+/// The cached syntaxes, if they were built from files with `fingerprint`.
+fn read_cache(path: &Path, fingerprint: u64) -> Option<SyntaxSet> {
+    let (cached, syntax_set): (u64, SyntaxSet) = from_dump_file(path).ok()?;
+    (cached == fingerprint).then_some(syntax_set)
+}
+
+fn write_cache(path: &Path, fingerprint: u64, syntax_set: &SyntaxSet) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    dump_to_file(&(fingerprint, syntax_set), path).map_err(|err| err.to_string())
+}
+
+impl ScrollDemo {
+    /// Loads the default syntaxes plus the user's `.sublime-syntax` files,
+    /// from the cache when none of those files changed since it was written.
+    /// A set with broken files in it isn't cached, so they are reported again
+    /// on every start until fixed.
+    pub fn load_syntaxes(&mut self, cx: &mut Context<Self>) {
+        let files = packages_dir().map(|dir| syntax_files(&dir)).unwrap_or_default();
+        if files.is_empty() {
+            return;
+        }
+        let fingerprint = fingerprint(&files);
+        let cache = cache_path();
+        if let Some(syntax_set) = cache.as_deref().and_then(|cache| read_cache(cache, fingerprint)) {
+            self.syntax_set = syntax_set;
+        } else {
+            let (syntax_set, errors) = build_syntax_set(&files);
+            if errors.is_empty() {
+                // The cache only saves time, so not being able to write it
+                // isn't worth bothering anyone about.
+                if let Some(cache) = cache {
+                    let _ = write_cache(&cache, fingerprint, &syntax_set);
+                }
+            } else {
+                self.notify_error(format!("Problems in your syntaxes: {}", errors.join("; ")), cx);
+            }
+            self.syntax_set = syntax_set;
+        }
+        self.update_syntax();
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_syntax_set, fingerprint, read_cache, syntax_files, write_cache};
+    use std::fs;
+
+    const TOML: &str = "\
+%YAML 1.2
+---
+name: TOML
+file_extensions: [toml]
+scope: source.toml
+contexts:
+  main:
+    - match: '#.*$'
+      scope: comment.line.number-sign.toml
+";
+
+    #[test]
+    fn user_syntaxes_are_added_to_the_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("User")).unwrap();
+        fs::write(dir.path().join("User/TOML.sublime-syntax"), TOML).unwrap();
+        fs::write(dir.path().join("Broken.sublime-syntax"), "name: [").unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        let files = syntax_files(dir.path());
+        assert_eq!(files.len(), 2);
+        let (syntax_set, errors) = build_syntax_set(&files);
+        assert_eq!(syntax_set.find_syntax_by_extension("toml").map(|syntax| syntax.name.as_str()), Some("TOML"));
+        assert!(syntax_set.find_syntax_by_name("Rust").is_some());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Broken.sublime-syntax"));
+    }
+
+    #[test]
+    fn the_cache_is_used_only_while_the_files_are_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let syntax = dir.path().join("TOML.sublime-syntax");
+        fs::write(&syntax, TOML).unwrap();
+        let files = vec![syntax.clone()];
+        let (syntax_set, _) = build_syntax_set(&files);
+        let cache = dir.path().join("cache/syntaxes.packdump");
+        write_cache(&cache, fingerprint(&files), &syntax_set).unwrap();
+
+        let cached = read_cache(&cache, fingerprint(&files)).unwrap();
+        assert!(cached.find_syntax_by_name("TOML").is_some());
+        fs::write(&syntax, format!("{TOML}    - match: '='\n      scope: keyword.operator.toml\n")).unwrap();
+        assert!(read_cache(&cache, fingerprint(&files)).is_none());
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// What a tab shows. Only `File` tabs are backed by a path on disk; the others
+/// have nowhere to save until Save As gives them one.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TabId {
+    File(PathBuf),
+    Untitled(usize),
+    FindResults,
+}
+
+impl TabId {
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            TabId::File(path) => Some(path),
+            TabId::Untitled(_) | TabId::FindResults => None,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            TabId::File(path) => path.file_name().and_then(|n| n.to_str()).unwrap_or("?").to_string(),
+            TabId::Untitled(n) => format!("untitled {n}"),
+            TabId::FindResults => "Find Results".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_file_tabs_have_a_path() {
+        let file = TabId::File(PathBuf::from("/tmp/notes.md"));
+        assert_eq!(file.path(), Some(&PathBuf::from("/tmp/notes.md")));
+        assert_eq!(file.label(), "notes.md");
+        assert_eq!(TabId::Untitled(2).path(), None);
+        assert_eq!(TabId::Untitled(2).label(), "untitled 2");
+        assert_eq!(TabId::FindResults.path(), None);
+    }
+}
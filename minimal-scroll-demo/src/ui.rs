@@ -3,24 +3,37 @@ use gpui::*;
 use gpui_component::scroll::ScrollableElement;
 use gpui_component::{h_flex, v_flex};
 use std::collections::HashMap;
-use std::fs;
+use std::ops::Range;
 use std::path::PathBuf;
 
 use syntect::easy::HighlightLines;
+use syntect::highlighting::Style;
 
 use crate::app_state::ScrollDemo;
+use crate::close::{CloseChoice, PendingClose};
+use crate::color_scheme::EditorColors;
+use crate::watcher::ReloadChoice;
+use crate::editor::{Motion, TEXT_PADDING};
+use crate::explorer::ROW_HEIGHT;
+use crate::file_ops::DraggedEntry;
+use crate::selection::{normalize, Selection};
 use crate::actions::*;
 use crate::menu::*;
+use crate::line_ending::LineEnding;
+use crate::notifications::Severity;
+use crate::picker::{Picker, PickerKind};
 
 impl ScrollDemo {
     pub fn render_project_explorer(&self, path: PathBuf, cx: &mut Context<Self>) -> impl IntoElement {
         let is_expanded = self.expanded_dirs.contains(&path);
-        let dir_name = path
-            .file_name()
-            .map_or("?", |os_str| os_str.to_str().unwrap_or("?"))
-            .to_string();
+        let dir_name = self.project.display_name(&path).unwrap_or_else(|| {
+            path.file_name().map_or("?", |os_str| os_str.to_str().unwrap_or("?")).to_string()
+        });
 
+        let is_selected = self.explorer_selected.as_ref() == Some(&path);
         let dir_label = div()
+            .id(SharedString::from(path.to_string_lossy().to_string()))
+            .h(px(ROW_HEIGHT))
             .flex()
             .items_center()
             .child(
@@ -32,6 +45,7 @@ impl ScrollDemo {
             )
             .child(div().pl(px(4.0)).child(dir_name))
             .text_color(rgb(0xdddddd))
+            .when(is_selected, |el| el.bg(rgb(0x37373d)))
             .hover(|s| s.bg(rgb(0x2d2d2d)))
             .cursor_pointer()
             .on_mouse_down(
@@ -39,74 +53,70 @@ impl ScrollDemo {
                 cx.listener({
                     let path_clone = path.clone();
                     move |this, _, _, cx| {
-                        if this.expanded_dirs.contains(&path_clone) {
-                            this.expanded_dirs.remove(&path_clone);
-                        } else {
-                            this.expanded_dirs.insert(path_clone.clone());
-                        }
-                        cx.notify();
+                        this.explorer_selected = Some(path_clone.clone());
+                        this.toggle_dir(path_clone.clone(), cx)
                     }
                 }),
-            );
+            )
+            .on_mouse_down(MouseButton::Right, cx.listener({
+                let path_clone = path.clone();
+                move |this, event: &MouseDownEvent, _, cx| this.open_explorer_menu(path_clone.clone(), event.position, cx)
+            }))
+            .on_drag(DraggedEntry(path.clone()), |entry, _, _, cx| cx.new(|_| entry.clone()))
+            .drag_over::<DraggedEntry>(|style, _, _, _| style.bg(rgb(0x094771)))
+            .on_drop(cx.listener({
+                let path_clone = path.clone();
+                move |this, entry: &DraggedEntry, _, cx| this.move_into(entry.0.clone(), path_clone.clone(), cx)
+            }));
 
         let mut children_elements: Vec<AnyElement> = vec![];
         if is_expanded {
-            if let Ok(entries) = std::fs::read_dir(&path) {
-                let mut sorted_entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
-                sorted_entries.sort_by(|a, b| {
-                    let a_is_dir = a.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-                    let b_is_dir = b.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-                    match (a_is_dir, b_is_dir) {
-                        (true, false) => std::cmp::Ordering::Less,
-                        (false, true) => std::cmp::Ordering::Greater,
-                        _ => a.file_name().cmp(&b.file_name()),
-                    }
-                });
-
-                for entry in sorted_entries {
-                    let entry_path = entry.path();
-                    let file_name = entry.file_name().to_str().unwrap_or("?").to_string();
-
-                    if entry_path.is_dir() {
-                        children_elements.push(
-                            self.render_project_explorer(entry_path.clone(), cx)
-                                .into_any_element(),
-                        );
-                    } else {
-                        children_elements.push(
-                            div()
-                                .pl(px(16.0))
-                                .child(file_name)
-                                .text_color(rgb(0xaaaaaa))
-                                .hover(|s| s.bg(rgb(0x2d2d2d)))
-                                .cursor_pointer()
-                                .on_mouse_down(
-                                    MouseButton::Left,
-                                    cx.listener({
-                                        let entry_path_clone = entry_path.clone();
-                                        move |this, _, window, cx| {
-                                            if let Some(pos) = this.open_tabs.iter().position(|p| p == &entry_path_clone) {
-                                                this.active_tab_index = Some(pos);
-                                            } else {
-                                                if let Ok(content) = fs::read_to_string(&entry_path_clone) {
-                                                    let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
-                                                    this.tab_contents.insert(entry_path_clone.clone(), lines);
-                                                    this.open_tabs.push(entry_path_clone.clone());
-                                                    this.active_tab_index = Some(this.open_tabs.len() - 1);
-                                                }
+            match self.project_tree.entries(&path) {
+                None => children_elements.push(
+                    div().h(px(ROW_HEIGHT)).pl(px(16.0)).text_color(rgb(0x666666)).child("Loading…").into_any_element(),
+                ),
+                Some(entries) => {
+                    for entry in entries {
+                        if entry.is_dir {
+                            children_elements.push(
+                                self.render_project_explorer(entry.path.clone(), cx)
+                                    .into_any_element(),
+                            );
+                        } else {
+                            children_elements.push(
+                                div()
+                                    .id(SharedString::from(entry.path.to_string_lossy().to_string()))
+                                    .h(px(ROW_HEIGHT))
+                                    .flex()
+                                    .items_center()
+                                    .pl(px(16.0))
+                                    .child(entry.name.clone())
+                                    .text_color(if entry.ignored { rgb(0x666666) } else { rgb(0xaaaaaa) })
+                                    .when(self.explorer_selected.as_ref() == Some(&entry.path), |el| el.bg(rgb(0x37373d)))
+                                    .hover(|s| s.bg(rgb(0x2d2d2d)))
+                                    .cursor_pointer()
+                                    .on_mouse_down(
+                                        MouseButton::Left,
+                                        cx.listener({
+                                            let entry_path_clone = entry.path.clone();
+                                            move |this, _, window, cx| {
+                                                this.explorer_selected = Some(entry_path_clone.clone());
+                                                this.open_file(entry_path_clone.clone(), cx);
+                                                window.focus(&this.focus_handle);
+                                                cx.stop_propagation();
                                             }
-                                            this.update_syntax();
-                                            this.cursor_row = 0;
-                                            this.cursor_col = 0;
-                                            this.right_handle.set_offset(Point::default());
-                                            window.focus(&this.focus_handle);
-                                            cx.stop_propagation();
-                                            cx.notify();
+                                        }),
+                                    )
+                                    .on_mouse_down(MouseButton::Right, cx.listener({
+                                        let entry_path_clone = entry.path.clone();
+                                        move |this, event: &MouseDownEvent, _, cx| {
+                                            this.open_explorer_menu(entry_path_clone.clone(), event.position, cx)
                                         }
-                                    }),
-                                )
-                                .into_any_element(),
-                        );
+                                    }))
+                                    .on_drag(DraggedEntry(entry.path.clone()), |entry, _, _, cx| cx.new(|_| entry.clone()))
+                                    .into_any_element(),
+                            );
+                        }
                     }
                 }
             }
@@ -124,6 +134,75 @@ impl ScrollDemo {
     }
 }
 
+impl ScrollDemo {
+    fn render_picker(&self, picker: &Picker, cx: &mut Context<Self>) -> impl IntoElement {
+        let matches = picker.matches();
+        v_flex()
+            .key_context("overlay")
+            .w(px(500.0)).bg(rgb(0x2d2d2d)).border_1().border_color(rgb(0x454545)).shadow_lg().p_2().gap_1()
+            .child(
+                div()
+                    .bg(rgb(0x3c3c3c)).border_1().border_color(rgb(0x007acc)).px_2().py_1()
+                    .track_focus(&self.picker_focus_handle)
+                    .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                        let Some(picker) = this.picker.as_mut() else { return };
+                        match event.keystroke.key.as_str() {
+                            "up" => picker.move_selection(false),
+                            "down" => picker.move_selection(true),
+                            "enter" => this.confirm_picker(window, cx),
+                            "escape" => this.close_picker(window, cx),
+                            "backspace" => {
+                                let mut query = picker.query.clone();
+                                query.pop();
+                                picker.set_query(query);
+                            }
+                            _ => {
+                                if let Some(text) = &event.keystroke.key_char {
+                                    picker.set_query(format!("{}{}", picker.query, text));
+                                }
+                            }
+                        }
+                        match this.picker.as_ref().map(|picker| picker.kind) {
+                            Some(PickerKind::GotoLine) => this.preview_goto_line(),
+                            Some(PickerKind::ColorScheme) => this.preview_color_scheme(),
+                            _ => {}
+                        }
+                        cx.notify();
+                    }))
+                    .child(div().text_size(px(12.0)).text_color(rgb(0xffffff)).child(if picker.query.is_empty() { " ".to_string() } else { picker.query.clone() }))
+            )
+            .children(matches.iter().copied().enumerate().map(|(row, item)| {
+                let is_selected = row == picker.selected;
+                div()
+                    .id(row)
+                    .px_2().py(px(3.0)).text_size(px(12.0))
+                    .text_color(if is_selected { rgb(0xffffff) } else { rgb(0xcccccc) })
+                    .when(is_selected, |el| el.bg(rgb(0x094771)))
+                    .hover(|s| s.bg(rgb(0x3e3e3e)))
+                    .cursor_pointer()
+                    .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, window, cx| {
+                        if let Some(picker) = this.picker.as_mut() {
+                            picker.selected = row;
+                        }
+                        this.confirm_picker(window, cx);
+                        cx.stop_propagation();
+                    }))
+                    .flex().justify_between().gap_4()
+                    .child(picker.label(item).to_string())
+                    .when_some(picker.hints.get(item).filter(|hint| !hint.is_empty()), |el, hint| {
+                        el.child(div().text_size(px(10.0)).text_color(rgb(0x888888)).child(hint.clone()))
+                    })
+            }))
+            .when(picker.items.is_empty(), |el| {
+                let hint = match picker.kind.prompt() {
+                    "" => "Nothing to show",
+                    prompt => prompt,
+                };
+                el.child(div().px_2().py(px(3.0)).text_size(px(12.0)).text_color(rgb(0x888888)).child(hint))
+            })
+    }
+}
+
 impl Render for ScrollDemo {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let is_focused = _window.focused(cx) == Some(self.focus_handle.clone());
@@ -134,30 +213,43 @@ impl Render for ScrollDemo {
 
         let active_lines = self.active_tab_index
             .and_then(|idx| self.open_tabs.get(idx))
-            .and_then(|path| self.tab_contents.get(path))
-            .cloned()
+            .and_then(|tab| self.tab_contents.get(tab))
+            .map(|buffer| buffer.lines().collect::<Vec<_>>())
             .unwrap_or_else(|| vec!["Click a file in the explorer to see its content here.".to_string()]);
 
-        let syntax = self.active_tab_index
-            .and_then(|idx| self.open_tabs.get(idx))
-            .and_then(|path| self.syntax_set.find_syntax_for_file(path).ok().flatten())
+        let syntax = self.active_tab()
+            .map(|tab| self.syntax_for(&tab))
             .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
 
-        let theme = &self.theme_set.themes["base16-ocean.dark"];
+        let settings = self.active_settings();
+        let theme = self.color_scheme();
+        let colors = EditorColors::new(&theme.settings);
         let mut highlighter = HighlightLines::new(syntax, theme);
 
-        let menu_bar_labels: &[(&str, OpenMenu)] = &[
-            ("File", OpenMenu::File),
-            ("Edit", OpenMenu::Edit),
-            ("Selection", OpenMenu::Selection),
-            ("Find", OpenMenu::Find),
-            ("View", OpenMenu::View),
-            ("Goto", OpenMenu::Goto),
-            ("Tools", OpenMenu::Tools),
-            ("Project", OpenMenu::Project),
-            ("Preferences", OpenMenu::Preferences),
-            ("Help", OpenMenu::Help),
-        ];
+        let mut decorations: HashMap<usize, LineDecorations> = HashMap::new();
+        if let Some(buffer) = self.active_buffer() {
+            for selection in &self.selections {
+                if is_focused {
+                    let (row, col) = buffer.offset_to_point(selection.head);
+                    decorations.entry(row).or_default().carets.push(col);
+                }
+                if !selection.is_empty() {
+                    let (start_row, start_col) = buffer.offset_to_point(selection.start());
+                    let (end_row, end_col) = buffer.offset_to_point(selection.end());
+                    for row in start_row..=end_row {
+                        let from = if row == start_row { start_col } else { 0 };
+                        let to = if row == end_row { end_col } else { buffer.line_len(row) };
+                        let line = decorations.entry(row).or_default();
+                        line.selections.push(from..to);
+                        line.selects_line_break |= row < end_row;
+                    }
+                }
+            }
+            for (idx, &(row, col)) in self.find_matches.iter().enumerate() {
+                let is_active = Some(idx) == self.active_match_index;
+                decorations.entry(row).or_default().matches.push((col..col + self.find_query.len(), is_active));
+            }
+        }
 
         let menu_bar_h = 26.0f32;
         let footer_h = 22.0f32;
@@ -172,6 +264,52 @@ impl Render for ScrollDemo {
             .on_action(cx.listener(|this, _action: &Save, _window, cx| this.save_active(cx)))
             .on_action(cx.listener(|this, _action: &SaveAs, _window, cx| this.save_as(cx)))
             .on_action(cx.listener(|this, _action: &SaveAll, _window, cx| this.save_all(cx)))
+            .on_action(cx.listener(|this, _action: &ReloadFromDisk, _window, cx| this.reload_active(cx)))
+            .on_action(cx.listener(|this, _action: &NewFile, window, cx| {
+                this.new_file(cx);
+                window.focus(&this.focus_handle);
+            }))
+            .on_action(cx.listener(|this, _action: &OpenFile, _window, cx| this.prompt_open_files(cx)))
+            .on_action(cx.listener(|this, _action: &OpenFolder, _window, cx| this.prompt_open_folder(cx)))
+            .on_action(cx.listener(|this, _action: &OpenProject, _window, cx| this.prompt_open_project(cx)))
+            .on_action(cx.listener(|this, _action: &RecentProjects, window, cx| this.select_recent_project(window, cx)))
+            .on_action(cx.listener(|this, _action: &SaveProjectAs, _window, cx| this.prompt_save_project_as(cx)))
+            .on_action(cx.listener(|this, _action: &AddFolderToProject, _window, cx| this.prompt_add_folder(cx)))
+            .on_action(cx.listener(|this, _action: &RemoveFolderFromProject, _window, cx| this.remove_selected_folder(cx)))
+            .on_action(cx.listener(|this, _action: &Undo, _window, cx| this.undo(cx)))
+            .on_action(cx.listener(|this, _action: &Redo, _window, cx| this.redo(cx)))
+            .on_action(cx.listener(|this, _action: &Copy, _window, cx| this.copy(cx)))
+            .on_action(cx.listener(|this, _action: &Cut, _window, cx| this.cut(cx)))
+            .on_action(cx.listener(|this, _action: &Paste, _window, cx| this.paste(cx)))
+            .on_action(cx.listener(|this, _action: &PasteFromHistory, window, cx| this.paste_from_history(window, cx)))
+            .on_action(cx.listener(|this, _action: &GotoAnything, window, cx| this.goto_anything(window, cx)))
+            .on_action(cx.listener(|this, _action: &GotoLine, window, cx| this.goto_line(window, cx)))
+            .on_action(cx.listener(|this, _action: &GotoSymbol, window, cx| this.goto_symbol(window, cx)))
+            .on_action(cx.listener(|this, _action: &CommandPalette, window, cx| this.show_command_palette(window, cx)))
+            .on_action(cx.listener(|this, _action: &EditSettings, _window, cx| this.edit_settings(cx)))
+            .on_action(cx.listener(|this, _action: &EditSyntaxSettings, _window, cx| this.edit_syntax_settings(cx)))
+            .on_action(cx.listener(|this, _action: &EditKeyBindings, _window, cx| this.edit_key_bindings(cx)))
+            .on_action(cx.listener(|this, _action: &SelectColorScheme, window, cx| this.select_color_scheme(window, cx)))
+            .on_action(cx.listener(|this, _action: &SelectSyntax, window, cx| this.select_syntax(window, cx)))
+            .on_action(cx.listener(|this, _action: &ToggleIgnoredFiles, _window, cx| this.toggle_ignored_files(cx)))
+            .on_action(cx.listener(|this, _action: &RevealInSideBar, _window, cx| this.reveal_in_side_bar(cx)))
+            .on_action(cx.listener(|this, _action: &ExplorerNewFile, window, cx| this.prompt_new_entry(PickerKind::NewFile, window, cx)))
+            .on_action(cx.listener(|this, _action: &ExplorerNewFolder, window, cx| this.prompt_new_entry(PickerKind::NewFolder, window, cx)))
+            .on_action(cx.listener(|this, _action: &ExplorerRename, window, cx| this.prompt_rename(window, cx)))
+            .on_action(cx.listener(|this, _action: &ExplorerDuplicate, window, cx| this.prompt_duplicate(window, cx)))
+            .on_action(cx.listener(|this, _action: &ExplorerDelete, _window, cx| this.trash_selected(cx)))
+            .on_action(cx.listener(|this, _action: &ExplorerCopyPath, _window, cx| this.copy_selected_path(cx)))
+            .on_action(cx.listener(|this, _action: &ExplorerReveal, _window, cx| this.reveal_selected(cx)))
+            .on_action(cx.listener(|this, _action: &LineEndingsUnix, _window, cx| this.set_line_ending(LineEnding::Lf, cx)))
+            .on_action(cx.listener(|this, _action: &LineEndingsWindows, _window, cx| this.set_line_ending(LineEnding::CrLf, cx)))
+            .on_action(cx.listener(|this, _action: &ReopenWithEncoding, window, cx| this.select_encoding(PickerKind::ReopenWithEncoding, window, cx)))
+            .on_action(cx.listener(|this, _action: &SaveWithEncoding, window, cx| this.select_encoding(PickerKind::SaveWithEncoding, window, cx)))
+            .on_action(cx.listener(|this, _action: &SelectAll, _window, cx| this.select_all(cx)))
+            .on_action(cx.listener(|this, _action: &SingleSelection, _window, cx| this.single_selection(cx)))
+            .on_action(cx.listener(|this, _action: &AddCursorAbove, _window, cx| this.add_cursor(true, cx)))
+            .on_action(cx.listener(|this, _action: &AddCursorBelow, _window, cx| this.add_cursor(false, cx)))
+            .on_action(cx.listener(|this, _action: &SelectNextOccurrence, _window, cx| this.select_next_occurrence(cx)))
+            .on_action(cx.listener(|this, _action: &ExpandSelectionToLine, _window, cx| this.expand_selection_to_line(cx)))
             .on_action(cx.listener(|this, _action: &FindAction, window, cx| {
                 this.find_active = !this.find_active;
                 this.fif_active = false;
@@ -186,7 +324,14 @@ impl Render for ScrollDemo {
                 else { window.focus(&this.focus_handle); }
                 cx.notify();
             }))
-            .on_action(cx.listener(|_this, _action: &Quit, _window, cx| cx.quit()))
+            .on_action(cx.listener(|this, _action: &FindNext, _window, cx| this.find_next(cx)))
+            .on_action(cx.listener(|this, _action: &FindPrevious, _window, cx| this.find_prev(cx)))
+            .on_action(cx.listener(|this, _action: &CloseFile, _window, cx| this.close_active_tab(cx)))
+            .on_action(cx.listener(|this, _action: &Quit, _window, cx| {
+                if this.request_quit(cx) {
+                    cx.quit();
+                }
+            }))
             .on_mouse_move(cx.listener(|this, event: &MouseMoveEvent, _window, cx| {
                 if this.is_dragging_sidebar {
                     this.sidebar_width = event.position.x.into();
@@ -205,7 +350,7 @@ impl Render for ScrollDemo {
             )
             .child(
                 div().flex().flex_row().bg(rgb(0x1e1e1e)).w_full().h(px(menu_bar_h)).children(
-                    menu_bar_labels.iter().map(|(label, variant)| {
+                    MENU_BAR.iter().map(|(label, variant)| {
                         let is_open = variant == &self.open_menu;
                         let variant = variant.clone();
                         div()
@@ -245,12 +390,24 @@ impl Render for ScrollDemo {
                             .child(
                                 v_flex()
                                     .id("left-scroll-area")
+                                    .key_context("side_bar")
                                     .size_full()
                                     .track_scroll(&self.left_handle)
                                     .overflow_y_scroll()
+                                    .track_focus(&self.explorer_focus_handle)
+                                    .on_mouse_down(MouseButton::Left, cx.listener(|this, _, window, cx| {
+                                        window.focus(&this.explorer_focus_handle);
+                                        cx.notify();
+                                    }))
+                                    .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                                        this.explorer_key_down(event, window, cx)
+                                    }))
                                     .child(
-                                        v_flex().flex_none().p_2().child(
-                                            self.render_project_explorer(self.current_dir.clone(), cx),
+                                        v_flex().flex_none().p_2().children(
+                                            self.project
+                                                .roots()
+                                                .map(|root| self.render_project_explorer(root.clone(), cx).into_any_element())
+                                                .collect::<Vec<_>>(),
                                         ),
                                     ),
                             )
@@ -281,20 +438,19 @@ impl Render for ScrollDemo {
                             .bottom_0()
                             .child(
                                 div().flex().flex_row().bg(rgb(0x1e1e1e)).h(px(30.0)).overflow_x_hidden().children(
-                                    self.open_tabs.iter().enumerate().map(|(idx, path)| {
+                                    self.open_tabs.iter().enumerate().map(|(idx, tab)| {
                                         let is_active = Some(idx) == self.active_tab_index;
-                                        let mut file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("?").to_string();
-                                        if self.dirty_tabs.contains(path) { file_name.push('*'); }
-                                        let path_clone = path.clone();
+                                        let mut file_name = tab.label();
+                                        if self.deleted_tabs.contains(tab) { file_name.push_str(" (deleted)"); }
+                                        if self.dirty_tabs.contains(tab) { file_name.push('*'); }
+                                        let tab_clone = tab.clone();
                                         div()
                                             .flex().items_center().px(px(10.0)).h_full()
                                             .bg(if is_active { rgb(0x232323) } else { rgb(0x181818) })
                                             .border_r_1().border_color(rgb(0x333333))
                                             .cursor_pointer()
                                             .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, window, cx| {
-                                                this.active_tab_index = Some(idx);
-                                                this.update_syntax();
-                                                this.right_handle.set_offset(Point::default());
+                                                this.activate_tab(Some(idx));
                                                 window.focus(&this.focus_handle);
                                                 cx.notify();
                                             }))
@@ -302,7 +458,7 @@ impl Render for ScrollDemo {
                                             .child(
                                                 div().ml(px(8.0)).text_size(px(10.0)).text_color(rgb(0x666666)).hover(|s| s.text_color(rgb(0xcccccc)))
                                                     .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
-                                                        this.request_close_tab(path_clone.clone(), cx);
+                                                        this.request_close_tab(tab_clone.clone(), cx);
                                                         cx.stop_propagation();
                                                         cx.notify();
                                                     }))
@@ -319,76 +475,36 @@ impl Render for ScrollDemo {
                                     .bottom_0()
                                     .left_0()
                                     .right_0()
+                                    .bg(colors.background)
                                     .border_1()
                                     .border_color(if is_focused { rgb(0x094771) } else { rgb(0x333333) })
+                                    .key_context("editor")
                                     .track_focus(&self.focus_handle)
                                     .on_mouse_down(MouseButton::Left, cx.listener(|this, _, window, cx| {
                                         window.focus(&this.focus_handle);
                                         cx.notify();
                                     }))
                                     .on_key_down(cx.listener(|this, event: &KeyDownEvent, _window, cx| {
-                                        if event.keystroke.modifiers.platform || event.keystroke.modifiers.control { return; }
-                                        if let Some(idx) = this.active_tab_index {
-                                            let path = this.open_tabs[idx].clone();
-                                            if let Some(lines) = this.tab_contents.get_mut(&path) {
-                                                match event.keystroke.key.as_str() {
-                                                    "backspace" => {
-                                                        if this.cursor_col > 0 {
-                                                            lines[this.cursor_row].remove(this.cursor_col - 1);
-                                                            this.cursor_col -= 1;
-                                                            this.dirty_tabs.insert(path);
-                                                        } else if this.cursor_row > 0 {
-                                                            let current_line = lines.remove(this.cursor_row);
-                                                            this.cursor_row -= 1;
-                                                            this.cursor_col = lines[this.cursor_row].len();
-                                                            lines[this.cursor_row].push_str(&current_line);
-                                                            this.dirty_tabs.insert(path);
-                                                        }
-                                                    }
-                                                    "enter" => {
-                                                        let current_line = &mut lines[this.cursor_row];
-                                                        let new_line = current_line.split_off(this.cursor_col);
-                                                        lines.insert(this.cursor_row + 1, new_line);
-                                                        this.cursor_row += 1;
-                                                        this.cursor_col = 0;
-                                                        this.dirty_tabs.insert(path);
-                                                    }
-                                                    "left" => {
-                                                        if this.cursor_col > 0 { this.cursor_col -= 1; }
-                                                        else if this.cursor_row > 0 {
-                                                            this.cursor_row -= 1;
-                                                            this.cursor_col = lines[this.cursor_row].len();
-                                                        }
-                                                    }
-                                                    "right" => {
-                                                        if this.cursor_col < lines[this.cursor_row].len() { this.cursor_col += 1; }
-                                                        else if this.cursor_row < lines.len() - 1 {
-                                                            this.cursor_row += 1;
-                                                            this.cursor_col = 0;
-                                                        }
-                                                    }
-                                                    "up" => {
-                                                        if this.cursor_row > 0 {
-                                                            this.cursor_row -= 1;
-                                                            this.cursor_col = this.cursor_col.min(lines[this.cursor_row].len());
-                                                        }
-                                                    }
-                                                    "down" => {
-                                                        if this.cursor_row < lines.len() - 1 {
-                                                            this.cursor_row += 1;
-                                                            this.cursor_col = this.cursor_col.min(lines[this.cursor_row].len());
-                                                        }
-                                                    }
-                                                    "space" => { lines[this.cursor_row].insert(this.cursor_col, ' '); this.cursor_col += 1; this.dirty_tabs.insert(path); }
-                                                    "tab" => { lines[this.cursor_row].insert_str(this.cursor_col, "    "); this.cursor_col += 4; this.dirty_tabs.insert(path); }
-                                                    key if key.len() == 1 => {
-                                                        lines[this.cursor_row].insert_str(this.cursor_col, key);
-                                                        this.cursor_col += 1;
-                                                        this.dirty_tabs.insert(path);
-                                                    }
-                                                    _ => {}
+                                        let modifiers = &event.keystroke.modifiers;
+                                        if modifiers.platform || modifiers.control { return; }
+                                        let extend = modifiers.shift;
+                                        match event.keystroke.key.as_str() {
+                                            "left" => this.move_selections(Motion::Left, extend, cx),
+                                            "right" => this.move_selections(Motion::Right, extend, cx),
+                                            "up" => this.move_selections(Motion::Up, extend, cx),
+                                            "down" => this.move_selections(Motion::Down, extend, cx),
+                                            "home" => this.move_selections(Motion::LineStart, extend, cx),
+                                            "end" => this.move_selections(Motion::LineEnd, extend, cx),
+                                            "backspace" => this.backspace(cx),
+                                            "enter" => this.insert_text("\n", cx),
+                                            "tab" => {
+                                                let indent = this.active_settings().indent();
+                                                this.insert_text(&indent, cx);
+                                            }
+                                            _ => {
+                                                if let Some(text) = &event.keystroke.key_char {
+                                                    this.insert_text(text, cx);
                                                 }
-                                                cx.notify();
                                             }
                                         }
                                     }))
@@ -399,80 +515,34 @@ impl Render for ScrollDemo {
                                             .track_scroll(&self.right_handle)
                                             .overflow_y_scroll()
                                             .child(
-                                                v_flex().flex_none().p(px(16.0)).children(
+                                                v_flex().flex_none().p(px(TEXT_PADDING)).children(
                                                     active_lines.into_iter().enumerate().map(|(i, line)| {
-                                                        let is_cursor_row = is_focused && Some(i) == Some(self.cursor_row);
                                                         let ranges: Vec<(syntect::highlighting::Style, &str)> = highlighter.highlight_line(&line, &self.syntax_set).unwrap_or_default();
-                                                        let mut span_elements: Vec<AnyElement> = vec![];
-                                                        let mut current_offset = 0;
-
-                                                        for (style, text) in ranges {
-                                                            let color = style.foreground;
-                                                            let text_len = text.len();
-                                                            
-                                                            let mut search_matches_in_span = vec![];
-                                                            if !self.find_query.is_empty() {
-                                                                let mut find_start = 0;
-                                                                while let Some(pos) = text[find_start..].find(&self.find_query) {
-                                                                    let abs_pos = current_offset + find_start + pos;
-                                                                    let is_active = self.active_match_index.map(|idx| self.find_matches[idx] == (i, abs_pos)).unwrap_or(false);
-                                                                    search_matches_in_span.push((find_start + pos, find_start + pos + self.find_query.len(), is_active));
-                                                                    find_start += pos + self.find_query.len().max(1);
-                                                                }
-                                                            }
-
-                                                            if is_cursor_row && self.cursor_col >= current_offset && self.cursor_col < current_offset + text_len {
-                                                                let split_idx = self.cursor_col - current_offset;
-                                                                let (before, after) = text.split_at(split_idx);
-                                                                if !before.is_empty() { 
-                                                                    span_elements.push(div().text_color(rgb(u32::from_be_bytes([0, color.r, color.g, color.b]))).child(before.to_string()).into_any_element()); 
-                                                                }
-                                                                span_elements.push(div().text_color(rgb(0xffffff)).child("|").into_any_element());
-                                                                if !after.is_empty() { 
-                                                                    span_elements.push(div().text_color(rgb(u32::from_be_bytes([0, color.r, color.g, color.b]))).child(after.to_string()).into_any_element()); 
-                                                                }
-                                                            } else if !search_matches_in_span.is_empty() {
-                                                                let mut last_idx = 0;
-                                                                for (start, end, is_active) in search_matches_in_span {
-                                                                    if start > last_idx {
-                                                                        span_elements.push(div().text_color(rgb(u32::from_be_bytes([0, color.r, color.g, color.b]))).child(text[last_idx..start].to_string()).into_any_element());
-                                                                    }
-                                                                    span_elements.push(
-                                                                        div()
-                                                                            .bg(if is_active { rgb(0xd18616) } else { rgba(0xffff0044) })
-                                                                            .text_color(rgb(u32::from_be_bytes([0, color.r, color.g, color.b])))
-                                                                            .child(text[start..end].to_string())
-                                                                            .into_any_element()
-                                                                    );
-                                                                    last_idx = end;
-                                                                }
-                                                                if last_idx < text_len {
-                                                                    span_elements.push(div().text_color(rgb(u32::from_be_bytes([0, color.r, color.g, color.b]))).child(text[last_idx..].to_string()).into_any_element());
-                                                                }
-                                                            } else {
-                                                                span_elements.push(div().text_color(rgb(u32::from_be_bytes([0, color.r, color.g, color.b]))).child(text.to_string()).into_any_element());
-                                                            }
-                                                            current_offset += text_len;
-                                                        }
-                                                        if is_cursor_row && self.cursor_col >= current_offset { span_elements.push(div().text_color(rgb(0xffffff)).child("|").into_any_element()); }
+                                                        let span_elements = render_line_spans(&ranges, decorations.get(&i), &colors);
 
                                                         h_flex()
                                                             .id(i)
                                                             .flex_none()
-                                                            .h(px(20.0))
-                                                            .font_family("Courier New")
-                                                            .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
-                                                                this.cursor_row = i;
-                                                                if let Some(tab_idx) = this.active_tab_index {
-                                                                    let path = &this.open_tabs[tab_idx];
-                                                                    this.cursor_col = this.tab_contents.get(path).map(|l| l[i].len()).unwrap_or(0);
+                                                            .h(px(settings.line_height))
+                                                            .font_family(settings.font_face.clone())
+                                                            .on_mouse_down(MouseButton::Left, cx.listener(move |this, event: &MouseDownEvent, _, cx| {
+                                                                if let Some(buffer) = this.active_buffer() {
+                                                                    let offset = buffer.point_to_offset(i, buffer.line_len(i));
+                                                                    if event.modifiers.secondary() {
+                                                                        this.selections.push(Selection::cursor(offset));
+                                                                        this.selections = normalize(std::mem::take(&mut this.selections));
+                                                                    } else {
+                                                                        this.set_cursor(offset);
+                                                                    }
                                                                 }
                                                                 cx.notify();
                                                             }))
                                                             .child(
                                                                 div()
                                                                     .w(px(40.0))
-                                                                    .text_color(rgb(0x666666))
+                                                                    .h_full()
+                                                                    .bg(colors.gutter)
+                                                                    .text_color(colors.gutter_foreground)
                                                                     .text_size(px(12.0))
                                                                     .flex()
                                                                     .justify_end()
@@ -502,15 +572,42 @@ impl Render for ScrollDemo {
                     .child(
                         h_flex()
                             .gap_4()
-                            .child(div().text_size(px(11.0)).text_color(rgb(0xffffff)).child(format!("Ln {}, Col {}", self.cursor_row + 1, self.cursor_col + 1)))
+                            .child(div().text_size(px(11.0)).text_color(rgb(0xffffff)).child(if self.selections.len() > 1 {
+                                format!("{} selection regions", self.selections.len())
+                            } else {
+                                let (row, col) = self.cursor_position();
+                                format!("Ln {}, Col {}", row + 1, col + 1)
+                            }))
                     )
                     .child(
-                        div().text_size(px(11.0)).text_color(rgb(0xffffff)).child(self.current_syntax_name.clone())
+                        h_flex()
+                            .gap_4()
+                            .when_some(self.active_buffer().filter(|buffer| !buffer.read_only), |el, buffer| {
+                                let other = if buffer.line_ending == LineEnding::Lf { LineEnding::CrLf } else { LineEnding::Lf };
+                                el.child(
+                                    div().text_size(px(11.0)).text_color(rgb(0xffffff)).cursor_pointer()
+                                        .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| this.set_line_ending(other, cx)))
+                                        .child(buffer.line_ending.name())
+                                )
+                            })
+                            .when_some(self.active_buffer(), |el, buffer| {
+                                el.child(div().text_size(px(11.0)).text_color(rgb(0xffffff)).child(if buffer.read_only {
+                                    "Hexadecimal".to_string()
+                                } else {
+                                    buffer.encoding.name()
+                                }))
+                            })
+                            .child(
+                                div().text_size(px(11.0)).text_color(rgb(0xffffff)).cursor_pointer()
+                                    .on_mouse_down(MouseButton::Left, cx.listener(|this, _, window, cx| this.select_syntax(window, cx)))
+                                    .child(self.current_syntax_name.clone())
+                            )
                     )
             )
             .when(self.find_active, |el| {
                 el.child(
                     h_flex()
+                        .key_context("find_panel")
                         .absolute().bottom(px(footer_h)).left_0().right_0().h(px(find_bar_h))
                         .bg(rgb(0x2d2d2d)).border_t_1().border_color(rgb(0x454545)).px_4().gap_4().items_center()
                         .child(div().text_size(px(12.0)).text_color(rgb(0xcccccc)).child("Find:"))
@@ -524,8 +621,12 @@ impl Render for ScrollDemo {
                                         "backspace" => { this.find_query.pop(); this.perform_search(); }
                                         "enter" => { this.find_next(cx); }
                                         "escape" => { this.find_active = false; window.focus(&this.focus_handle); }
-                                        key if key.len() == 1 => { this.find_query.push_str(key); this.perform_search(); }
-                                        _ => {}
+                                        _ => {
+                                            if let Some(text) = &event.keystroke.key_char {
+                                                this.find_query.push_str(text);
+                                                this.perform_search();
+                                            }
+                                        }
                                     }
                                     cx.notify();
                                 }))
@@ -550,6 +651,7 @@ impl Render for ScrollDemo {
             .when(self.fif_active, |el| {
                 el.child(
                     v_flex()
+                        .key_context("find_in_files_panel")
                         .absolute().bottom(px(footer_h)).left_0().right_0().h(px(fif_bar_h))
                         .bg(rgb(0x2d2d2d)).border_t_1().border_color(rgb(0x454545)).px_4().py_2().gap_1()
                         .child(
@@ -565,8 +667,9 @@ impl Render for ScrollDemo {
                                                 "backspace" => { this.fif_query.pop(); }
                                                 "enter" => { this.perform_find_in_files(cx); }
                                                 "escape" => { this.fif_active = false; window.focus(&this.focus_handle); }
-                                                key if key.len() == 1 => { this.fif_query.push_str(key); }
-                                                _ => {}
+                                                _ => {
+                                                    if let Some(text) = &event.keystroke.key_char { this.fif_query.push_str(text); }
+                                                }
                                             }
                                             cx.notify();
                                         }))
@@ -589,8 +692,9 @@ impl Render for ScrollDemo {
                                         .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
                                             match event.keystroke.key.as_str() {
                                                 "backspace" => { this.fif_where.pop(); }
-                                                key if key.len() == 1 => { this.fif_where.push_str(key); }
-                                                _ => {}
+                                                _ => {
+                                                    if let Some(text) = &event.keystroke.key_char { this.fif_where.push_str(text); }
+                                                }
                                             }
                                             cx.notify();
                                         }))
@@ -614,8 +718,9 @@ impl Render for ScrollDemo {
                                         .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
                                             match event.keystroke.key.as_str() {
                                                 "backspace" => { this.fif_replace.pop(); }
-                                                key if key.len() == 1 => { this.fif_replace.push_str(key); }
-                                                _ => {}
+                                                _ => {
+                                                    if let Some(text) = &event.keystroke.key_char { this.fif_replace.push_str(text); }
+                                                }
                                             }
                                             cx.notify();
                                         }))
@@ -630,19 +735,7 @@ impl Render for ScrollDemo {
                 )
             })
             .when(self.open_menu != OpenMenu::None, |el| {
-                let items = match &self.open_menu { 
-                    OpenMenu::File => file_menu_items(), 
-                    OpenMenu::Edit => edit_menu_items(),
-                    OpenMenu::Selection => selection_menu_items(),
-                    OpenMenu::Find => find_menu_items(),
-                    OpenMenu::View => view_menu_items(),
-                    OpenMenu::Goto => goto_menu_items(),
-                    OpenMenu::Tools => tools_menu_items(),
-                    OpenMenu::Project => project_menu_items(),
-                    OpenMenu::Preferences => preferences_menu_items(),
-                    OpenMenu::Help => help_menu_items(),
-                    _ => vec![] 
-                };
+                let items = self.open_menu.items();
                 
                 let get_label_width = |label: &str, char_widths: &HashMap<char, f32>| {
                     label.chars()
@@ -652,7 +745,7 @@ impl Render for ScrollDemo {
 
                 let mut dropdown_left = 0.0f32;
                 let width_correction = if cfg!(target_os = "windows") { -1.0 } else { 1.4 };
-                for (label, variant) in menu_bar_labels.iter() {
+                for (label, variant) in MENU_BAR.iter() {
                     if variant == &self.open_menu { break; }
                     dropdown_left = dropdown_left + get_label_width(label, &self.char_widths) + width_correction;
                 }
@@ -661,37 +754,121 @@ impl Render for ScrollDemo {
                   .child(v_flex().absolute().top(px(menu_bar_h)).left(px(dropdown_left)).w(px(270.0)).bg(rgb(0x2d2d2d)).border_1().border_color(rgb(0x454545)).shadow_lg().py(px(4.0)).children(items.into_iter().map(|item| {
                       if item.is_separator { div().h(px(1.0)).my(px(3.0)).mx(px(8.0)).bg(rgb(0x444444)).into_any_element() }
                       else {
-                          let action = item.action.boxed_clone();
-                          let label = item.label;
-                          h_flex().id(label).justify_between().items_center().px(px(12.0)).py(px(3.0)).text_size(px(12.0)).text_color(rgb(0xcccccc)).hover(|s| s.bg(rgb(0x094771)).text_color(rgb(0xffffff))).cursor_pointer()
-                                  .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, window, cx| { 
-                                      let action_ref = action.as_ref();
-                                      if label == "Save" { this.save_active(cx); }
-                                      else if label == "Save All" { this.save_all(cx); }
-                                      else if label == "Save As..." { this.save_as(cx); }
-                                      else if label == "Find..." { 
-                                          this.find_active = true; 
-                                          this.fif_active = false;
-                                          window.focus(&this.find_focus_handle);
-                                      }
-                                      else if label == "Find in Files..." {
-                                          this.fif_active = true;
-                                          this.find_active = false;
-                                          window.focus(&this.fif_focus_find);
+                          let shortcut = item.command.and_then(|command| self.keymap.shortcut(command.id));
+                          h_flex().id(item.label).justify_between().items_center().px(px(12.0)).py(px(3.0)).text_size(px(12.0))
+                                  .text_color(if item.command.is_some() || item.has_arrow { rgb(0xcccccc) } else { rgb(0x6a6a6a) })
+                                  .when_some(item.command, |el, command| {
+                                      el.hover(|s| s.bg(rgb(0x094771)).text_color(rgb(0xffffff))).cursor_pointer()
+                                          .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, window, cx| {
+                                              this.open_menu = OpenMenu::None;
+                                              if window.focused(cx).is_none() { window.focus(&this.focus_handle); }
+                                              window.dispatch_action(command.action(), cx);
+                                              cx.notify();
+                                          }))
+                                  })
+                                  .child(item.label)
+                                  .when(item.has_arrow, |el| el.child(div().text_size(px(10.0)).text_color(rgb(0x888888)).child("▶")))
+                                  .when_some(shortcut, |el, sc| el.child(div().text_size(px(10.0)).text_color(rgb(0x888888)).child(sc)))
+                                  .into_any_element()
+                      }
+                  })))
+            })
+            .when_some(self.explorer_menu, |el, position| {
+                el.child(div().absolute().top_0().left_0().size_full().on_mouse_down(MouseButton::Left, cx.listener(|this, _, _, cx| { this.explorer_menu = None; cx.notify(); })))
+                  .child(v_flex().absolute().top(position.y).left(position.x).w(px(200.0)).bg(rgb(0x2d2d2d)).border_1().border_color(rgb(0x454545)).shadow_lg().py(px(4.0)).children(explorer_menu_items().into_iter().map(|item| {
+                      if item.is_separator { div().h(px(1.0)).my(px(3.0)).mx(px(8.0)).bg(rgb(0x444444)).into_any_element() }
+                      else {
+                          let command = item.command;
+                          h_flex().id(item.label).items_center().px(px(12.0)).py(px(3.0)).text_size(px(12.0)).text_color(rgb(0xcccccc)).hover(|s| s.bg(rgb(0x094771)).text_color(rgb(0xffffff))).cursor_pointer()
+                                  .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, window, cx| {
+                                      this.explorer_menu = None;
+                                      if let Some(command) = command {
+                                          window.dispatch_action(command.action(), cx);
                                       }
-                                      else if label == "Quit" || label == "Exit" { cx.quit(); }
-                                      else { cx.dispatch_action(action_ref); }
-                                      this.open_menu = OpenMenu::None;
                                       cx.notify();
                                   }))
                                   .child(item.label)
-                                  .when(item.has_arrow, |el| el.child(div().text_size(px(10.0)).text_color(rgb(0x888888)).child("▶")))
-                                  .when_some(item.shortcut, |el, sc| el.child(div().text_size(px(10.0)).text_color(rgb(0x888888)).child(sc)))
                                   .into_any_element()
                       }
                   })))
             })
-            .when_some(self.pending_close_path.clone(), |el, path| {
+            .when(!self.notifications.items().is_empty(), |el| {
+                el.child(
+                    v_flex()
+                        .absolute().bottom(px(footer_h + find_bar_h + fif_bar_h + 8.0)).right(px(16.0)).w(px(360.0)).gap_2()
+                        .children(self.notifications.items().iter().map(|notification| {
+                            let id = notification.id;
+                            let (bg, border) = match notification.severity {
+                                Severity::Info => (rgb(0x2d2d2d), rgb(0x454545)),
+                                Severity::Error => (rgb(0x5a1d1d), rgb(0xbe1100)),
+                            };
+                            h_flex()
+                                .justify_between().items_start().gap_2().px_3().py_2()
+                                .bg(bg).border_1().border_color(border).shadow_lg()
+                                .child(div().flex_1().text_size(px(12.0)).text_color(rgb(0xffffff)).child(notification.message.clone()))
+                                .child(
+                                    div().text_size(px(10.0)).text_color(rgb(0xaaaaaa)).hover(|s| s.text_color(rgb(0xffffff))).cursor_pointer()
+                                        .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| {
+                                            this.dismiss_notification(id, cx);
+                                            cx.stop_propagation();
+                                        }))
+                                        .child("✕")
+                                )
+                        }))
+                )
+            })
+            .when_some(self.picker.as_ref(), |el, picker| {
+                el.child(div().absolute().top_0().left_0().size_full().on_mouse_down(MouseButton::Left, cx.listener(|this, _, window, cx| this.close_picker(window, cx))))
+                  .child(
+                      h_flex().absolute().top(px(menu_bar_h + 8.0)).left_0().right_0().justify_center()
+                          .child(self.render_picker(picker, cx))
+                  )
+            })
+            .when_some(self.pending_reloads.first().cloned(), |el, tab| {
+                let buttons = [("Merge", ReloadChoice::Merge), ("Reload", ReloadChoice::Reload), ("Keep Mine", ReloadChoice::Keep)];
+                el.child(
+                    div()
+                        .absolute().top_0().left_0().size_full()
+                        .bg(rgba(0x000000aa))
+                        .flex().items_center().justify_center()
+                        .child(
+                            v_flex()
+                                .w(px(440.0))
+                                .bg(rgb(0x2d2d2d))
+                                .border_1().border_color(rgb(0x454545))
+                                .shadow_xl()
+                                .p_6()
+                                .child(div().text_size(px(16.0)).text_color(rgb(0xffffff)).child("File Changed on Disk"))
+                                .child(div().mt_4().text_size(px(13.0)).text_color(rgb(0xcccccc)).child(format!(
+                                    "{} changed on disk and has unsaved changes here. Merge both, reload it from disk (your changes stay in undo), or keep yours?",
+                                    tab.label()
+                                )))
+                                .child(
+                                    h_flex().mt_8().justify_end().gap_3().children(buttons.into_iter().map(|(label, choice)| {
+                                        let primary = choice == ReloadChoice::Merge;
+                                        div().px_4().py_2().cursor_pointer()
+                                            .bg(if primary { rgb(0x007acc) } else { rgb(0x3e3e3e) })
+                                            .hover(move |s| s.bg(if primary { rgb(0x0062a3) } else { rgb(0x4e4e4e) }))
+                                            .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| this.resolve_reload(choice, cx)))
+                                            .child(div().text_size(px(12.0)).text_color(rgb(0xffffff)).child(label))
+                                    }))
+                                )
+                        )
+                )
+            })
+            .when_some(self.pending_close.as_ref().and_then(|p| p.tab().map(|tab| (p.clone(), tab.clone()))), |el, (pending, tab)| {
+                let quitting = matches!(pending, PendingClose::Quit(_));
+                let mut message = format!("Do you want to save the changes you made to {}?", tab.label());
+                match pending.others() {
+                    0 => {}
+                    1 => message.push_str(" 1 other file also has unsaved changes."),
+                    n => message.push_str(&format!(" {n} other files also have unsaved changes.")),
+                }
+                let mut buttons = vec![("Save", CloseChoice::Save), ("Don't Save", CloseChoice::Discard)];
+                if quitting && pending.others() > 0 {
+                    buttons.extend([("Save All", CloseChoice::SaveAll), ("Discard All", CloseChoice::DiscardAll)]);
+                }
+                buttons.push(("Cancel", CloseChoice::Cancel));
                 el.child(
                     div()
                         .absolute().top_0().left_0().size_full()
@@ -699,51 +876,104 @@ impl Render for ScrollDemo {
                         .flex().items_center().justify_center()
                         .child(
                             v_flex()
-                                .w(px(400.0))
+                                .w(px(if buttons.len() > 3 { 560.0 } else { 400.0 }))
                                 .bg(rgb(0x2d2d2d))
                                 .border_1().border_color(rgb(0x454545))
                                 .shadow_xl()
                                 .p_6()
                                 .child(div().text_size(px(16.0)).text_color(rgb(0xffffff)).child("Unsaved Changes"))
-                                .child(div().mt_4().text_size(px(13.0)).text_color(rgb(0xcccccc)).child(format!("Do you want to save the changes you made to {}?", path.file_name().and_then(|n| n.to_str()).unwrap_or("this file"))))
+                                .child(div().mt_4().text_size(px(13.0)).text_color(rgb(0xcccccc)).child(message))
                                 .child(
-                                    h_flex().mt_8().justify_end().gap_3()
-                                        .child(
-                                            div().px_4().py_2().bg(rgb(0x007acc)).hover(|s| s.bg(rgb(0x0062a3))).cursor_pointer()
-                                                .on_mouse_down(MouseButton::Left, cx.listener({
-                                                    let path = path.clone();
-                                                    move |this, _, _, cx| {
-                                                        this.save_path(path.clone(), cx);
-                                                        this.close_tab(path.clone(), cx);
-                                                        this.pending_close_path = None;
-                                                        cx.notify();
-                                                    }
-                                                }))
-                                                .child(div().text_size(px(12.0)).text_color(rgb(0xffffff)).child("Save"))
-                                        )
-                                        .child(
-                                            div().px_4().py_2().bg(rgb(0x3e3e3e)).hover(|s| s.bg(rgb(0x4e4e4e))).cursor_pointer()
-                                                .on_mouse_down(MouseButton::Left, cx.listener({
-                                                    let path = path.clone();
-                                                    move |this, _, _, cx| {
-                                                        this.close_tab(path.clone(), cx);
-                                                        this.pending_close_path = None;
-                                                        cx.notify();
-                                                    }
-                                                }))
-                                                .child(div().text_size(px(12.0)).text_color(rgb(0xffffff)).child("Don't Save"))
-                                        )
-                                        .child(
-                                            div().px_4().py_2().bg(rgb(0x3e3e3e)).hover(|s| s.bg(rgb(0x4e4e4e))).cursor_pointer()
-                                                .on_mouse_down(MouseButton::Left, cx.listener(|this, _, _, cx| {
-                                                    this.pending_close_path = None;
-                                                    cx.notify();
-                                                }))
-                                                .child(div().text_size(px(12.0)).text_color(rgb(0xffffff)).child("Cancel"))
-                                        )
+                                    h_flex().mt_8().justify_end().gap_3().children(buttons.into_iter().map(|(label, choice)| {
+                                        let primary = choice == CloseChoice::Save;
+                                        div().px_4().py_2().cursor_pointer()
+                                            .bg(if primary { rgb(0x007acc) } else { rgb(0x3e3e3e) })
+                                            .hover(move |s| s.bg(if primary { rgb(0x0062a3) } else { rgb(0x4e4e4e) }))
+                                            .on_mouse_down(MouseButton::Left, cx.listener(move |this, _, _, cx| this.resolve_close(choice, cx)))
+                                            .child(div().text_size(px(12.0)).text_color(rgb(0xffffff)).child(label))
+                                    }))
                                 )
                         )
                 )
             })
     }
 }
+
+/// Everything drawn over a line's syntax colours, in byte columns.
+#[derive(Default)]
+struct LineDecorations {
+    carets: Vec<usize>,
+    selections: Vec<Range<usize>>,
+    selects_line_break: bool,
+    matches: Vec<(Range<usize>, bool)>,
+}
+
+/// Splits the highlighted spans of a line wherever a selection, find match or
+/// caret starts or ends, so each piece gets a single background.
+fn render_line_spans(ranges: &[(Style, &str)], decorations: Option<&LineDecorations>, colors: &EditorColors) -> Vec<AnyElement> {
+    let caret = || div().text_color(colors.caret).child("|").into_any_element();
+    let Some(decorations) = decorations else {
+        return ranges
+            .iter()
+            .map(|(style, text)| div().text_color(style_color(style)).child(text.to_string()).into_any_element())
+            .collect();
+    };
+
+    let mut boundaries: Vec<usize> = decorations.carets.clone();
+    for range in decorations.selections.iter().chain(decorations.matches.iter().map(|(range, _)| range)) {
+        boundaries.push(range.start);
+        boundaries.push(range.end);
+    }
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut elements = vec![];
+    let mut offset = 0;
+    for (style, text) in ranges {
+        let span_end = offset + text.len();
+        let mut cuts: Vec<usize> = boundaries
+            .iter()
+            .copied()
+            .filter(|&b| b > offset && b < span_end && text.is_char_boundary(b - offset))
+            .collect();
+        cuts.push(span_end);
+        let mut piece_start = offset;
+        for cut in cuts {
+            if decorations.carets.contains(&piece_start) {
+                elements.push(caret());
+            }
+            let piece = &text[piece_start - offset..cut - offset];
+            let in_range = |range: &Range<usize>| range.start <= piece_start && cut <= range.end;
+            let (background, foreground) = if decorations.matches.iter().any(|(range, active)| *active && in_range(range)) {
+                (Some(colors.find_highlight), colors.find_highlight_foreground)
+            } else if decorations.matches.iter().any(|(range, _)| in_range(range)) {
+                (Some(colors.highlight), None)
+            } else if decorations.selections.iter().any(in_range) {
+                (Some(colors.selection), colors.selection_foreground)
+            } else {
+                (None, None)
+            };
+            elements.push(
+                div()
+                    .text_color(foreground.unwrap_or_else(|| style_color(style)))
+                    .when_some(background, |el, bg| el.bg(bg))
+                    .child(piece.to_string())
+                    .into_any_element(),
+            );
+            piece_start = cut;
+        }
+        offset = span_end;
+    }
+    if decorations.carets.iter().any(|&col| col >= offset) {
+        elements.push(caret());
+    }
+    if decorations.selects_line_break {
+        elements.push(div().w(px(7.0)).h_full().bg(colors.selection).into_any_element());
+    }
+    elements
+}
+
+fn style_color(style: &Style) -> Rgba {
+    let color = style.foreground;
+    rgb(u32::from_be_bytes([0, color.r, color.g, color.b]))
+}
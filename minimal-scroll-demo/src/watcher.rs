@@ -0,0 +1,290 @@
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+use futures::StreamExt;
+use gpui::*;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::app_state::ScrollDemo;
+use crate::buffer::Buffer;
+use crate::color_scheme::is_color_scheme;
+use crate::keymap::Keymap;
+use crate::notifications::describe_io_error;
+use crate::project::Project;
+use crate::settings::config_dir;
+use crate::tab::TabId;
+
+/// How long to wait for a burst of events to finish (a save is usually a
+/// create, a write and a rename) before looking at the files.
+const SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+/// Watches the directories holding open files, and those the explorer has
+/// listed. Directories rather than the files themselves, since tools like git
+/// and our own saves replace a file by renaming over it, which a watch on the
+/// old file would not see.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    watched: HashSet<PathBuf>,
+}
+
+impl FileWatcher {
+    /// A watcher and the stream of paths it reports as changed.
+    pub fn new() -> notify::Result<(Self, UnboundedReceiver<PathBuf>)> {
+        let (sender, receiver) = unbounded();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else { return };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            for path in event.paths {
+                let _ = sender.unbounded_send(path);
+            }
+        })?;
+        Ok((Self { watcher, watched: HashSet::new() }, receiver))
+    }
+
+    /// Watches exactly `wanted`, none of them recursively.
+    pub fn sync(&mut self, wanted: HashSet<PathBuf>) {
+        for dir in self.watched.difference(&wanted) {
+            let _ = self.watcher.unwatch(dir);
+        }
+        self.watched.retain(|dir| wanted.contains(dir));
+        for dir in wanted {
+            if !self.watched.contains(&dir) && self.watcher.watch(&dir, RecursiveMode::NonRecursive).is_ok() {
+                self.watched.insert(dir);
+            }
+        }
+    }
+}
+
+/// What to do with a modified tab whose file changed on disk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReloadChoice {
+    /// Combine our edits and theirs against the last saved text.
+    Merge,
+    /// Take the file from disk; our edits stay reachable through undo.
+    Reload,
+    /// Keep the buffer as it is.
+    Keep,
+}
+
+impl ScrollDemo {
+    /// Starts the file watcher and the task that passes its events on.
+    pub fn start_file_watcher(&mut self, cx: &mut Context<Self>) {
+        let Ok((watcher, mut receiver)) = FileWatcher::new() else { return };
+        self.file_watcher = Some(watcher);
+        cx.spawn(async move |this, cx| {
+            while let Some(first) = receiver.next().await {
+                cx.background_executor().timer(SETTLE_DELAY).await;
+                let mut paths = HashSet::from([first]);
+                while let Ok(path) = receiver.try_recv() {
+                    paths.insert(path);
+                }
+                let updated = this.update(cx, |this, cx| {
+                    this.config_files_changed(&paths, cx);
+                    for path in paths {
+                        this.tree_path_changed(&path, cx);
+                        this.file_changed_on_disk(path, cx);
+                    }
+                });
+                if updated.is_err() {
+                    break;
+                }
+            }
+        })
+        .detach();
+        self.update_watches();
+    }
+
+    /// Points the watcher at the directories of the open file tabs, the
+    /// directories listed in the explorer, the one holding the project file
+    /// and the one holding the user's settings and keymap.
+    pub fn update_watches(&mut self) {
+        let Some(watcher) = self.file_watcher.as_mut() else { return };
+        let mut dirs: HashSet<PathBuf> =
+            self.open_tabs.iter().filter_map(TabId::path).filter_map(|path| path.parent()).map(PathBuf::from).collect();
+        dirs.extend(self.project_tree.loaded_dirs().cloned());
+        dirs.extend(self.project.file.as_deref().and_then(Path::parent).map(PathBuf::from));
+        dirs.extend(config_dir().filter(|dir| dir.is_dir()));
+        watcher.sync(dirs);
+    }
+
+    /// Reloads the keymap, the color schemes, the user settings or the
+    /// project settings when their files are among `paths`.
+    fn config_files_changed(&mut self, paths: &HashSet<PathBuf>, cx: &mut Context<Self>) {
+        if Keymap::user_path().is_some_and(|keymap| paths.contains(&keymap)) {
+            self.load_keymap(cx);
+        }
+        let dir = config_dir();
+        if paths.iter().any(|path| path.parent() == dir.as_deref() && is_color_scheme(path)) {
+            self.load_color_schemes(cx);
+        }
+        if paths.iter().any(|path| path.parent() == dir.as_deref() && path.extension().is_some_and(|ext| ext == "sublime-settings")) {
+            self.load_user_settings(cx);
+        }
+        let Some(file) = self.project.file.clone().filter(|file| paths.contains(file)) else { return };
+        match Project::load(&file) {
+            Ok(project) => {
+                self.project.settings = project.settings;
+                self.load_project_settings(cx);
+            }
+            Err(err) => self.notify_error(format!("Couldn't reload the project settings: {}", describe_io_error(&err, &file)), cx),
+        }
+    }
+
+    /// Brings an open tab in line with its file after a change on disk. Clean
+    /// buffers just reload; modified ones ask first. Changes that leave the
+    /// file matching the buffer's saved state, such as our own saves, are
+    /// ignored.
+    pub fn file_changed_on_disk(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        let tab = TabId::File(path.clone());
+        if !self.tab_contents.contains_key(&tab) {
+            return;
+        }
+        let Ok(bytes) = fs::read(&path) else {
+            if !path.exists() && self.deleted_tabs.insert(tab.clone()) {
+                self.sync_dirty(&tab);
+                self.notify_info(format!("{} was deleted on disk", tab.label()), cx);
+            }
+            return;
+        };
+        if self.deleted_tabs.remove(&tab) {
+            self.sync_dirty(&tab);
+            cx.notify();
+        }
+        if self.tab_contents.get(&tab).is_some_and(|buffer| matches_saved_state(buffer, &bytes)) {
+            return;
+        }
+        if self.dirty_tabs.contains(&tab) {
+            if !self.pending_reloads.contains(&tab) {
+                self.pending_reloads.push(tab);
+            }
+            cx.notify();
+        } else {
+            self.apply_reload(&tab, &bytes, ReloadChoice::Reload, cx);
+        }
+    }
+
+    /// Reload from Disk: rereads the active file, keeping the current text
+    /// reachable through undo.
+    pub fn reload_active(&mut self, cx: &mut Context<Self>) {
+        let Some(tab) = self.active_tab() else { return };
+        let Some(path) = tab.path().cloned() else { return };
+        match fs::read(&path) {
+            Ok(bytes) => self.apply_reload(&tab, &bytes, ReloadChoice::Reload, cx),
+            Err(err) => self.notify_error(format!("Couldn't reload {}", describe_io_error(&err, &path)), cx),
+        }
+    }
+
+    /// Acts on the choice made for the first tab waiting in `pending_reloads`.
+    pub fn resolve_reload(&mut self, choice: ReloadChoice, cx: &mut Context<Self>) {
+        if self.pending_reloads.is_empty() {
+            return;
+        }
+        let tab = self.pending_reloads.remove(0);
+        if choice != ReloadChoice::Keep {
+            let Some(path) = tab.path().cloned() else { return };
+            match fs::read(&path) {
+                Ok(bytes) => self.apply_reload(&tab, &bytes, choice, cx),
+                Err(err) => self.notify_error(format!("Couldn't reload {}", describe_io_error(&err, &path)), cx),
+            }
+        }
+        cx.notify();
+    }
+
+    fn apply_reload(&mut self, tab: &TabId, bytes: &[u8], choice: ReloadChoice, cx: &mut Context<Self>) {
+        let Some(buffer) = self.tab_contents.get_mut(tab) else { return };
+        match choice {
+            ReloadChoice::Keep => return,
+            ReloadChoice::Reload => buffer.reload(bytes),
+            ReloadChoice::Merge => match merge_from_disk(buffer, bytes) {
+                Some(true) => {}
+                Some(false) => {
+                    self.notify_error(format!("{} had conflicting changes; they are marked in the text", tab.label()), cx);
+                }
+                None => {
+                    let message = format!("Can't merge {}: its saved state is no longer in the undo history", tab.label());
+                    self.notify_error(message, cx);
+                    return;
+                }
+            },
+        }
+        if self.active_tab().as_ref() == Some(tab) {
+            self.clamp_selections();
+            self.refresh_find_matches();
+        }
+        self.sync_dirty(tab);
+        cx.notify();
+    }
+}
+
+/// Whether `bytes` are what saving the buffer's saved state would write, as
+/// after our own saves, so a change on disk to them needs no reload.
+fn matches_saved_state(buffer: &Buffer, bytes: &[u8]) -> bool {
+    let saved = if buffer.is_modified() { buffer.saved_text() } else { Some(buffer.text()) };
+    saved.is_some_and(|text| buffer.encoding.encode(&buffer.with_line_endings(&text)).is_ok_and(|encoded| encoded == bytes))
+}
+
+/// Reloads `bytes` into a modified buffer and merges the buffer's edits back
+/// in, against the last saved text. Returns whether the merge was clean;
+/// conflicts are left marked in the text. `None`, leaving the buffer alone,
+/// when the saved state is no longer in the undo history.
+fn merge_from_disk(buffer: &mut Buffer, bytes: &[u8]) -> Option<bool> {
+    let base = buffer.saved_text()?;
+    let mine = buffer.text();
+    buffer.reload(bytes);
+    let merged = diffy::merge(&base, &mine, &buffer.text());
+    let clean = merged.is_ok();
+    buffer.set_text(&merged.unwrap_or_else(|conflicted| conflicted));
+    Some(clean)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches_saved_state, merge_from_disk};
+    use crate::buffer::Buffer;
+
+    /// A buffer loaded with `saved` and since edited to `edited`.
+    fn edited_buffer(saved: &str, edited: &str) -> Buffer {
+        let mut buffer = Buffer::from_text(saved);
+        buffer.set_text(edited);
+        buffer
+    }
+
+    #[test]
+    fn clean_buffers_reload_changes_but_not_their_own_saves() {
+        let mut buffer = Buffer::from_text("one\ntwo\n");
+        assert!(matches_saved_state(&buffer, b"one\ntwo\n"));
+        assert!(!matches_saved_state(&buffer, b"one\n2\n"));
+        buffer.reload(b"one\n2\n");
+        assert_eq!(buffer.text(), "one\n2\n");
+        assert!(!buffer.is_modified());
+    }
+
+    #[test]
+    fn modified_buffers_compare_disk_with_their_saved_text() {
+        let buffer = edited_buffer("one\ntwo\n", "one\nTWO\n");
+        assert!(matches_saved_state(&buffer, b"one\ntwo\n"));
+        assert!(!matches_saved_state(&buffer, b"one\nTWO\n"));
+    }
+
+    #[test]
+    fn separate_edits_merge_cleanly() {
+        let mut buffer = edited_buffer("a\nb\nc\nd\ne\n", "A\nb\nc\nd\ne\n");
+        assert_eq!(merge_from_disk(&mut buffer, b"a\nb\nc\nd\nE\n"), Some(true));
+        assert_eq!(buffer.text(), "A\nb\nc\nd\nE\n");
+        assert!(buffer.is_modified());
+        assert!(matches_saved_state(&buffer, b"a\nb\nc\nd\nE\n"));
+    }
+
+    #[test]
+    fn overlapping_edits_leave_conflict_markers() {
+        let mut buffer = edited_buffer("a\nb\nc\n", "a\nmine\nc\n");
+        assert_eq!(merge_from_disk(&mut buffer, b"a\ntheirs\nc\n"), Some(false));
+        let text = buffer.text();
+        assert!(text.contains("<<<<<<<") && text.contains("mine") && text.contains("theirs"));
+        assert!(buffer.is_modified());
+    }
+}
@@ -1,7 +1,8 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
 use gpui::prelude::FluentBuilder;
 use gpui::*;
 use gpui_component::{init, Root};
-use serde_json;
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs; // New
@@ -184,6 +185,29 @@ fn help_menu_items() -> Vec<MenuItem> {
     ]
 }
 
+// ── File decoding ─────────────────────────────────────────────────────────────
+
+/// Turns a file's bytes into text to show. A byte order mark picks the
+/// encoding; otherwise valid UTF-8 is used as is and anything else is decoded
+/// in the encoding chardetng guesses, such as Latin-1 or Shift-JIS. Files with
+/// NUL bytes near the start are binary and only get a one-line placeholder.
+fn decode_file(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return encoding.decode_without_bom_handling(&bytes[bom_len..]).0.into_owned();
+    }
+    if bytes.iter().take(8192).any(|&b| b == 0) {
+        return format!("Binary file ({} bytes) not shown.", bytes.len());
+    }
+    let encoding = if std::str::from_utf8(bytes).is_ok() {
+        UTF_8
+    } else {
+        let mut detector = EncodingDetector::new();
+        detector.feed(bytes, true);
+        detector.guess(None, true)
+    };
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
 // ── App view ──────────────────────────────────────────────────────────────────
 
 struct AppView {
@@ -303,12 +327,10 @@ impl AppView {
                                             {
                                                 _this.active_tab_index = Some(pos);
                                             } else {
-                                                if let Ok(content) =
-                                                    fs::read_to_string(&_entry_path_clone)
-                                                {
+                                                if let Ok(bytes) = fs::read(&_entry_path_clone) {
                                                     _this
                                                         .tab_contents
-                                                        .insert(_entry_path_clone.clone(), content);
+                                                        .insert(_entry_path_clone.clone(), decode_file(&bytes));
                                                     _this.open_tabs.push(_entry_path_clone.clone());
                                                     _this.active_tab_index =
                                                         Some(_this.open_tabs.len() - 1);
@@ -388,12 +410,7 @@ impl Render for AppView {
             .on_mouse_move(cx.listener(|this, event: &MouseMoveEvent, _window, cx| {
                 if this.is_dragging_sidebar {
                     this.sidebar_width = event.position.x.into();
-                    if this.sidebar_width < 50.0 {
-                        this.sidebar_width = 50.0;
-                    }
-                    if this.sidebar_width > 600.0 {
-                        this.sidebar_width = 600.0;
-                    }
+                    this.sidebar_width = this.sidebar_width.clamp(50.0, 600.0);
                     cx.notify();
                 }
             }))
@@ -694,7 +711,7 @@ fn main() {
                 ..Default::default()
             },
             |window, cx| {
-                let view = cx.new(|cx| AppView::new(cx));
+                let view = cx.new(AppView::new);
                 // Root is required by gpui-component for event routing to work
                 cx.new(|cx| Root::new(view, window, cx))
             },